@@ -0,0 +1,689 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::path::Path;
+
+use itertools::Itertools;
+
+use crate::tag::{self, Tag};
+use crate::usage::Usage;
+
+/// A tag's path along with the chain of names leading to the tag.
+#[derive(Clone, Debug)]
+pub struct PathEntry {
+    /// The primary names of the tag and all of its parents, root first.
+    pub names: Vec<String>,
+    /// The resolved and normalized path of the tag.
+    pub path: String,
+}
+
+/// Summary of a collection of tags.
+#[derive(Clone, Debug, Default)]
+pub struct Stats {
+    /// The total number of tags, including subtags.
+    pub tags: usize,
+    /// The number of levels of the deepest tag, `1` for global tags.
+    pub max_depth: usize,
+    /// The number of tags with a URL.
+    pub urls: usize,
+    /// The number of tags with a file path.
+    pub files: usize,
+    /// The number of tags with no path or URL.
+    pub no_path: usize,
+    /// The total number of aliases, excluding the primary names.
+    pub aliases: usize,
+}
+
+/// Returns `true` if the path looks like a URL rather than a file path.
+pub fn is_url(path: &str) -> bool {
+    path.contains("://")
+}
+
+/// Returns the URL with the words joined by spaces as the value of the query
+/// parameter `param`, added before the fragment of the URL, if any.
+///
+/// The words are encoded as in HTML forms, with spaces as `+`, so that
+/// `["rust", "lifetimes"]` becomes `?search=rust+lifetimes`.
+pub fn with_query<S: AsRef<str>>(url: &str, param: &str, words: &[S]) -> String {
+    let (base, fragment) = match url.split_once('#') {
+        Some((base, fragment)) => (base, Some(fragment)),
+        None => (url, None),
+    };
+    let separator = match base.split_once('?') {
+        Some((_, "")) => "",
+        Some((_, query)) if query.ends_with('&') => "",
+        Some(_) => "&",
+        None => "?",
+    };
+    let words = words.iter().map(AsRef::as_ref).join(" ");
+
+    let mut url = format!(
+        "{}{}{}={}",
+        base,
+        separator,
+        encode_query(param),
+        encode_query(&words)
+    );
+    if let Some(fragment) = fragment {
+        url.push('#');
+        url.push_str(fragment);
+    }
+    url
+}
+
+/// Percent-encodes the text for a URL query, with spaces as `+`.
+fn encode_query(text: &str) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            },
+            b' ' => encoded.push('+'),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// Recursively collects the statistics of the tags.
+pub fn stats(tags: &[Tag]) -> Stats {
+    fn collect(tags: &[Tag], depth: usize, stats: &mut Stats) {
+        for tag in tags.iter().filter(|t| !t.names.is_empty()) {
+            stats.tags += 1;
+            stats.max_depth = stats.max_depth.max(depth);
+            stats.aliases += tag.names.len() - 1;
+
+            match tag.path() {
+                Some(p) if is_url(p) => stats.urls += 1,
+                Some(_) => stats.files += 1,
+                None => stats.no_path += 1,
+            }
+
+            collect(&tag.subtags, depth + 1, stats);
+        }
+    }
+
+    let mut stats = Stats::default();
+    collect(tags, 1, &mut stats);
+    stats
+}
+
+/// Expands the tilde and environment variables in the path.
+///
+/// The path is returned unchanged if an environment variable is not set.
+pub fn expand_path(path: &str) -> Cow<'_, str> {
+    shellexpand::full(path).unwrap_or(Cow::Borrowed(path))
+}
+
+/// Resolves the path of a tag to the path or the URL that is opened.
+///
+/// File paths are expanded as with [`expand_path`] and, if they are relative,
+/// joined to the expanded `root`, so that a tags file shared between machines
+/// can use paths relative to a synced directory. URLs are returned unchanged.
+pub fn resolve_path<'a>(path: &'a str, root: Option<&str>) -> Cow<'a, str> {
+    if is_url(path) || has_scheme(path) {
+        return Cow::Borrowed(path);
+    }
+
+    let expanded = expand_path(path);
+    match root {
+        Some(root) if Path::new(expanded.as_ref()).is_relative() => Cow::Owned(
+            Path::new(expand_path(root).as_ref())
+                .join(expanded.as_ref())
+                .to_string_lossy()
+                .into_owned(),
+        ),
+        _ => expanded,
+    }
+}
+
+/// Returns the kind of the tag: `secret`, `url`, `directory`, `file`, or
+/// `none` if it has no path or URL.
+///
+/// `resolved` is the path or the URL the tag opens, as with [`resolve_path`].
+pub fn tag_kind(tag: &Tag, resolved: Option<&str>) -> &'static str {
+    match resolved {
+        _ if tag.secret.is_some() => "secret",
+        Some(p) if is_url(p) || has_scheme(p) => "url",
+        Some(p) if Path::new(p).is_dir() => "directory",
+        Some(_) => "file",
+        None => "none",
+    }
+}
+
+/// Returns `true` if the resolved path can be opened, as far as can be told
+/// without opening it.
+///
+/// URLs are assumed to be available, and file paths are if they exist.
+pub fn is_available(path: &str) -> bool {
+    is_url(path) || has_scheme(path) || Path::new(path).exists()
+}
+
+/// Returns `true` if the path starts with a URL scheme without `//`, such as
+/// `mailto:`.
+///
+/// Single letters are drive letters on Windows rather than schemes.
+fn has_scheme(path: &str) -> bool {
+    path.split_once(':').is_some_and(|(scheme, _)| {
+        scheme.len() > 1
+            && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+            && scheme
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+    })
+}
+
+/// Returns why the path or the URL looks wrong, if it does.
+///
+/// A file path is wrong if nothing exists at it. A URL is wrong if its scheme
+/// is invalid, if it has whitespace, or if it is a web URL without a host.
+pub fn path_problem(path: &str) -> Option<String> {
+    let path = path.trim();
+    match path.split_once("://") {
+        Some((scheme, rest)) => {
+            let valid_scheme = scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+            let host = rest.split(['/', '?', '#']).next().unwrap_or_default();
+
+            if !valid_scheme {
+                Some(format!("`{}` has an invalid scheme", path))
+            } else if path.contains(char::is_whitespace) {
+                Some(format!("`{}` has whitespace, which URLs cannot have", path))
+            } else if ["http", "https"].contains(&scheme.to_lowercase().as_str()) && host.is_empty()
+            {
+                Some(format!("`{}` has no host", path))
+            } else {
+                None
+            }
+        },
+        None if !Path::new(expand_path(path).as_ref()).exists() => {
+            Some(format!("`{}` does not exist", path))
+        },
+        None => None,
+    }
+}
+
+/// Returns a name for a tag opening the path, if one can be made.
+///
+/// For URLs, it is the host without `www.` and its top-level domain, such as
+/// `github` for `https://www.github.com/rust-lang`. For file paths, it is the
+/// lowercased file name without its extension, with whitespace replaced by
+/// `-`.
+pub fn suggest_name(path: &str) -> Option<String> {
+    let path = path.trim();
+    let name = match path.split_once("://") {
+        Some((_, rest)) => {
+            let host = rest.split(['/', '?', '#', ':']).next()?;
+            let host = host.rsplit('@').next()?.trim_start_matches("www.");
+            match host.rsplit_once('.') {
+                Some((name, _)) => name.to_string(),
+                None => host.to_string(),
+            }
+        },
+        None => Path::new(path)
+            .file_stem()?
+            .to_string_lossy()
+            .split_whitespace()
+            .join("-"),
+    };
+
+    let name = name.to_lowercase();
+    (!name.is_empty()).then_some(name)
+}
+
+/// Expands and normalizes the path so that equivalent paths compare equal.
+///
+/// The scheme and the host of URLs are lowercased, and trailing slashes are
+/// removed from both URLs and file paths.
+pub fn normalize_path(path: &str) -> String {
+    let expanded = expand_path(path.trim());
+
+    let normalized = if let Some((scheme, rest)) = expanded.split_once("://") {
+        let (host, tail) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+        format!(
+            "{}://{}{}",
+            scheme.to_lowercase(),
+            host.to_lowercase(),
+            tail
+        )
+    } else {
+        expanded.into_owned()
+    };
+
+    match normalized.trim_end_matches('/') {
+        "" => normalized,
+        trimmed => trimmed.to_string(),
+    }
+}
+
+/// Recursively calls `f` with every tag and the primary names of the tag and
+/// all of its parents, root first.
+///
+/// Tags with no names are skipped along with their subtags.
+pub fn walk<'a, F: FnMut(&[String], &'a Tag)>(tags: &'a [Tag], mut f: F) {
+    fn walk_inner<'a, F: FnMut(&[String], &'a Tag)>(
+        tags: &'a [Tag],
+        names: &mut Vec<String>,
+        f: &mut F,
+    ) {
+        for tag in tags {
+            let name = match tag.names.first() {
+                Some(n) => n,
+                None => continue,
+            };
+
+            names.push(name.clone());
+            f(names, tag);
+            walk_inner(&tag.subtags, names, f);
+            names.pop();
+        }
+    }
+
+    walk_inner(tags, &mut Vec::new(), &mut f);
+}
+
+/// Recursively collects the normalized paths of all tags that have one.
+///
+/// Relative file paths are resolved against `root` first, as with
+/// [`resolve_path`].
+pub fn path_index(tags: &[Tag], root: Option<&str>) -> Vec<PathEntry> {
+    let mut index = Vec::new();
+    walk(tags, |names, tag| {
+        if let Some(path) = tag.path() {
+            index.push(PathEntry {
+                names: names.to_vec(),
+                path: normalize_path(&resolve_path(path, root)),
+            });
+        }
+    });
+    index
+}
+
+/// Returns the tags that were added or modified, most recent first, along
+/// with the primary names of the tag and all of its parents.
+pub fn recent(tags: &[Tag]) -> Vec<(Vec<String>, &Tag)> {
+    let mut recent = Vec::new();
+    walk(tags, |names, tag| {
+        if tag.last_changed().is_some() {
+            recent.push((names.to_vec(), tag));
+        }
+    });
+    recent.sort_by_key(|(_, t)| std::cmp::Reverse(t.last_changed()));
+    recent
+}
+
+/// Returns the tags whose review date has come, along with the primary names
+/// of the tag and all of its parents, by the review date, oldest first.
+///
+/// `now` is in seconds since the Unix epoch.
+pub fn stale(tags: &[Tag], now: u64) -> Vec<(Vec<String>, &Tag)> {
+    let mut stale = Vec::new();
+    walk(tags, |names, tag| {
+        if is_stale(tag, now) {
+            stale.push((names.to_vec(), tag));
+        }
+    });
+    stale.sort_by_key(|(_, t)| t.review_after.as_deref().and_then(parse_date));
+    stale
+}
+
+/// Returns the tags with a path that were not opened since `since`, along
+/// with the primary names of the tag and all of its parents and when the tag
+/// was last opened, or `0` if it never was. Tags never opened come first,
+/// then the tags opened least recently.
+///
+/// Without `since`, only the tags never opened are returned. With it, tags
+/// added since then are left out, as they had no time to be opened. Times are
+/// in seconds since the Unix epoch.
+pub fn unused<'a>(
+    tags: &'a [Tag],
+    usage: &Usage,
+    since: Option<u64>,
+) -> Vec<(Vec<String>, &'a Tag, u64)> {
+    let mut unused = Vec::new();
+    walk(tags, |names, tag| {
+        if tag.path().is_none() && tag.fallback_paths.is_empty() && tag.secret.is_none() {
+            return;
+        }
+
+        let last_opened = usage.get(&names.join("/")).map_or(0, |e| e.last_opened);
+        let is_unused = match since {
+            Some(since) => last_opened < since && tag.created.is_none_or(|c| c < since),
+            None => last_opened == 0,
+        };
+        if is_unused {
+            unused.push((names.to_vec(), tag, last_opened));
+        }
+    });
+    unused.sort_by_key(|(_, t, last_opened)| (*last_opened, t.created));
+    unused
+}
+
+/// Returns `true` if the review date of the tag has come.
+pub fn is_stale(tag: &Tag, now: u64) -> bool {
+    tag.review_after
+        .as_deref()
+        .and_then(parse_date)
+        .is_some_and(|date| date <= now)
+}
+
+/// Parses a date such as `2024-12-31` into the seconds since the Unix epoch
+/// at its start, in UTC.
+pub fn parse_date(date: &str) -> Option<u64> {
+    let mut parts = date.trim().splitn(3, '-');
+    let year = parts.next()?.parse::<i64>().ok()?;
+    let month = parts.next()?.parse::<i64>().ok()?;
+    let day = parts.next()?.parse::<i64>().ok()?;
+
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let days_in_month = match month {
+        2 if leap => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        1..=12 => 31,
+        _ => return None,
+    };
+    if !(1..=days_in_month).contains(&day) {
+        return None;
+    }
+
+    // days since the epoch of the proleptic Gregorian calendar, counting
+    // years from March so that the leap day is last
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+
+    u64::try_from(days).ok().map(|d| d * 60 * 60 * 24)
+}
+
+/// Parses an age such as `90d` into seconds. The units are `h` for hours, `d`
+/// for days, `w` for weeks, `m` for months of 30 days, and `y` for years of
+/// 365 days.
+pub fn parse_age(age: &str) -> Option<u64> {
+    let age = age.trim();
+    let unit = match age.chars().last()? {
+        'h' => 60 * 60,
+        'd' => 60 * 60 * 24,
+        'w' => 60 * 60 * 24 * 7,
+        'm' => 60 * 60 * 24 * 30,
+        'y' => 60 * 60 * 24 * 365,
+        _ => return None,
+    };
+    age[..age.len() - 1].parse::<u64>().ok()?.checked_mul(unit)
+}
+
+/// Formats seconds since the Unix epoch as a date and time such as
+/// `2024-12-31 18:05`, in UTC.
+pub fn format_time(secs: u64) -> String {
+    let days = i64::try_from(secs / (60 * 60 * 24)).unwrap_or(0);
+    let minutes = secs % (60 * 60 * 24) / 60;
+
+    // the inverse of `parse_date`, with years counted from March
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}",
+        year,
+        month,
+        day,
+        minutes / 60,
+        minutes % 60
+    )
+}
+
+/// Returns the tags that open the path, along with whether they open it
+/// exactly, first, or a parent of it, such as the directory of a file or the
+/// repository of an issue.
+///
+/// Paths are compared once resolved against `root` and normalized, as with
+/// [`path_index`].
+pub fn lookup(tags: &[Tag], path: &str, root: Option<&str>) -> Vec<(PathEntry, bool)> {
+    let path = normalize_path(path);
+    path_index(tags, root)
+        .into_iter()
+        .filter_map(|entry| {
+            if entry.path == path {
+                Some((entry, true))
+            } else if path
+                .strip_prefix(entry.path.as_str())
+                .is_some_and(|rest| rest.starts_with(['/', '\\', '?', '#']))
+            {
+                Some((entry, false))
+            } else {
+                None
+            }
+        })
+        .sorted_by_key(|(entry, exact)| (!exact, std::cmp::Reverse(entry.path.len())))
+        .collect()
+}
+
+/// Groups the tags that share the same normalized path.
+///
+/// Only paths used by more than one tag are returned.
+pub fn duplicate_paths(tags: &[Tag], root: Option<&str>) -> Vec<(String, Vec<Vec<String>>)> {
+    path_index(tags, root)
+        .into_iter()
+        .into_group_map_by(|e| e.path.clone())
+        .into_iter()
+        .filter(|(_, entries)| entries.len() > 1)
+        .map(|(path, entries)| (path, entries.into_iter().map(|e| e.names).collect()))
+        .sorted()
+        .collect()
+}
+
+/// Returns the names of the tag with the given primary names that tags
+/// elsewhere in the tree also have, along with the primary names of those
+/// tags, root first.
+pub fn name_clashes(tags: &[Tag], names: &[String]) -> Vec<(String, Vec<String>)> {
+    let tag = match tag::find_tag_by_names(tags, names) {
+        Some(tag) => tag,
+        None => return Vec::new(),
+    };
+
+    let mut clashes = Vec::new();
+    walk(tags, |other_names, other| {
+        if other_names == names {
+            return;
+        }
+        for name in tag.names.iter().filter(|n| other.has_name(n)) {
+            clashes.push((name.clone(), other_names.to_vec()));
+        }
+    });
+    clashes
+}
+
+/// A difference between two versions of the tags.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Change {
+    /// The tag was added.
+    Added {
+        /// The primary names of the tag and all of its parents, root first.
+        names: Vec<String>,
+        /// The path of the tag.
+        path: Option<String>,
+    },
+    /// The tag was removed.
+    Removed {
+        /// The primary names of the tag and all of its parents, root first.
+        names: Vec<String>,
+        /// The path of the tag.
+        path: Option<String>,
+    },
+    /// The tag was moved along with its subtags.
+    Moved {
+        /// The names leading to the tag before.
+        from: Vec<String>,
+        /// The names leading to the tag now.
+        to: Vec<String>,
+    },
+    /// Fields of the tag were changed.
+    Changed {
+        /// The primary names of the tag and all of its parents, root first.
+        names: Vec<String>,
+        /// The changed fields, with their old and new values.
+        fields: Vec<(&'static str, String, String)>,
+    },
+}
+
+/// Returns the differences between the old and the new tags.
+///
+/// Tags are matched by the primary names of the tag and its parents. A tag
+/// removed from one place and added in another with the same primary name and
+/// path is reported as moved, once for the whole subtree. Timestamps are
+/// ignored.
+pub fn diff(old: &[Tag], new: &[Tag]) -> Vec<Change> {
+    let collect = |tags| {
+        let mut entries = Vec::new();
+        walk(tags, |names, tag| entries.push((names.to_vec(), tag)));
+        entries
+    };
+    let (old, new) = (collect(old), collect(new));
+    let old_index: HashMap<_, _> = old.iter().map(|(n, t)| (n.clone(), *t)).collect();
+    let new_index: HashMap<_, _> = new.iter().map(|(n, t)| (n.clone(), *t)).collect();
+
+    // moved subtrees, from their old names to their new names
+    let mut moves: Vec<(Vec<String>, Vec<String>)> = Vec::new();
+    // the old names of the tags that are now elsewhere, and their new names
+    let mut renamed: HashMap<Vec<String>, Vec<String>> = HashMap::new();
+    for (names, tag) in &old {
+        if new_index.contains_key(names) {
+            continue;
+        }
+
+        // subtags of a moved tag move with it
+        let moved_parent = moves
+            .iter()
+            .find(|(from, _)| names.starts_with(from))
+            .map(|(from, to)| [to.as_slice(), &names[from.len()..]].concat())
+            .filter(|to| new_index.contains_key(to) && !old_index.contains_key(to));
+        if let Some(to) = moved_parent {
+            renamed.insert(names.clone(), to);
+            continue;
+        }
+
+        let destination = new.iter().find(|(new_names, new_tag)| {
+            !old_index.contains_key(new_names)
+                && !renamed.values().any(|n| n == new_names)
+                && new_tag.names.first() == tag.names.first()
+                && new_tag.path == tag.path
+        });
+        if let Some((to, _)) = destination {
+            moves.push((names.clone(), to.clone()));
+            renamed.insert(names.clone(), to.clone());
+        }
+    }
+
+    let mut changes = Vec::new();
+    for (names, tag) in &old {
+        if !new_index.contains_key(names) && !renamed.contains_key(names) {
+            changes.push(Change::Removed {
+                names: names.clone(),
+                path: tag.path.as_ref().map(ToString::to_string),
+            });
+        }
+    }
+    changes.extend(
+        moves
+            .into_iter()
+            .map(|(from, to)| Change::Moved { from, to }),
+    );
+
+    for (names, tag) in &new {
+        let old_names = renamed
+            .iter()
+            .find(|(_, to)| *to == names)
+            .map_or(names, |(from, _)| from);
+        match old_index.get(old_names) {
+            Some(old_tag) => {
+                let fields = changed_fields(old_tag, tag);
+                if !fields.is_empty() {
+                    changes.push(Change::Changed {
+                        names: names.clone(),
+                        fields,
+                    });
+                }
+            },
+            None => changes.push(Change::Added {
+                names: names.clone(),
+                path: tag.path.as_ref().map(ToString::to_string),
+            }),
+        }
+    }
+
+    changes
+}
+
+/// Returns the fields that differ between the tags, with their old and new
+/// values, ignoring subtags and timestamps.
+fn changed_fields(old: &Tag, new: &Tag) -> Vec<(&'static str, String, String)> {
+    fn fields(tag: &Tag) -> [(&'static str, String); 17] {
+        let text = |field: &Option<String>| field.clone().unwrap_or_default();
+        [
+            ("aliases", tag.names.iter().skip(1).join(", ")),
+            (
+                "path",
+                tag.path
+                    .as_ref()
+                    .map(ToString::to_string)
+                    .unwrap_or_default(),
+            ),
+            ("fallback_paths", tag.fallback_paths.join(", ")),
+            ("query_param", text(&tag.query_param)),
+            ("icon", text(&tag.icon)),
+            ("color", text(&tag.color)),
+            ("about", text(&tag.about)),
+            ("notes", text(&tag.notes)),
+            ("secret", text(&tag.secret)),
+            (
+                "app",
+                tag.app
+                    .as_ref()
+                    .map(ToString::to_string)
+                    .unwrap_or_default(),
+            ),
+            (
+                "background",
+                tag.background.map(|b| b.to_string()).unwrap_or_default(),
+            ),
+            ("cwd", text(&tag.cwd)),
+            (
+                "env",
+                tag.env
+                    .iter()
+                    .map(|(k, v)| format!("{}={}", k, v))
+                    .join(", "),
+            ),
+            ("labels", tag.labels.join(", ")),
+            ("review_after", text(&tag.review_after)),
+            ("pinned", tag.pinned.to_string()),
+            (
+                "other",
+                tag.extra
+                    .iter()
+                    .map(|(k, v)| format!("{}={}", k, v))
+                    .join(", "),
+            ),
+        ]
+    }
+
+    fields(old)
+        .into_iter()
+        .zip(fields(new))
+        .filter(|((_, old), (_, new))| old != new)
+        .map(|((field, old), (_, new))| (field, old, new))
+        .collect()
+}
@@ -1,6 +1,11 @@
-use clap::{Arg, ArgGroup, Command};
+use std::env;
+use std::ffi::OsString;
+use std::path::PathBuf;
 
-use crate::tag::{command_from_tag, Tags};
+use clap::{Arg, ArgGroup, ArgMatches, Command};
+
+use crate::color;
+use crate::tag::{self, Tag, Tags};
 
 const ABOUT: &str = "
 opentag (ot) opens a tagged path or URL using the configured system program.
@@ -22,10 +27,117 @@ const HELP_TEMPLATE: &str = "{before-help}{bin} {version}
 {usage-heading}
     ot <--add|--remove|--update|--list>
     ot [OPTIONS|--list] <TAG>
+    ot <COMMAND>
+
+{all-args}
+
+COMMANDS:
+    alias         Add or remove an alias of a tag.
+    aliases       Print shell aliases for the tags.
+    apply         Apply the operations in a file to the tags at once.
+    audit-urls    Check that the URLs of the tags still work.
+    capture       Add a tag for the path or the URL in the clipboard.
+    copy          Copy a tag and its subtags under another tag.
+    diff          Show how the tags changed since a backup or compared to another file.
+    dupes         List tags that open the same path or URL.
+    edit          Edit the tags file in the editor.
+    exec          Run a command with the path of a tag in place of `{}`.
+    export        Export the tags as a document.
+    grep          Search the paths, descriptions, and notes of all tags with a regex.
+    group         Open or list the groups of tags in the config.
+    history       List the tags opened most recently, or clear the list.
+    import        Import tags from a file.
+    info          Print which tags file and config file are used, and why.
+    init          Fill an empty tags file with example tags or the tags of a template.
+    lookup        List the tags that open a path or a URL.
+    merge         Move the names and subtags of a tag into another tag.
+    pick          Pick a tag to open from all tags interactively.
+    recent        List the most recently added or modified tags.
+    reorder       Reorder tags interactively.
+    shell-init    Print shell functions to change to the directory of a tag and complete tags.
+    sort          Sort tags alphabetically by their primary name.
+    stale         List the tags whose review date has come, oldest first.
+    stats         Summarize the tags and the tags file.
+    sync          Push or pull the tags file to or from the configured backend.
+    tag           Open a tag, including a tag named like a built-in command.
+    top           List the most opened tags.
+    trash         List, restore or empty the removed tags.
+    undo          Undo the last change to the tags.
+    unused        List the tags that were never opened, or not recently.
+    version       Print the version and build information.
+{after-help}";
 
-{all-args}{after-help}";
+/// Names of the built-in commands.
+///
+/// Tags with any of these names are shadowed by the command, but can still be
+/// invoked with `ot tag <NAME>`.
+pub const BUILTINS: &[&str] = &[
+    "__complete",
+    "alias",
+    "aliases",
+    "apply",
+    "audit-urls",
+    "capture",
+    "copy",
+    "diff",
+    "dupes",
+    "edit",
+    "exec",
+    "export",
+    "grep",
+    "group",
+    "history",
+    "import",
+    "info",
+    "init",
+    "lookup",
+    "merge",
+    "pick",
+    "recent",
+    "reorder",
+    "shell-init",
+    "sort",
+    "stale",
+    "stats",
+    "sync",
+    "tag",
+    "top",
+    "trash",
+    "undo",
+    "unused",
+    "version",
+];
+
+/// Returns the value of the global option with the long name, read from the
+/// arguments directly.
+///
+/// Used for options that must apply before the arguments are parsed, such as
+/// to errors raised while loading the tags.
+pub fn raw_option_value(long: &str) -> Option<String> {
+    let flag = format!("--{}", long);
+    let mut args = env::args_os().skip(1);
+    let mut value = None;
+    while let Some(arg) = args.next() {
+        match arg.to_str() {
+            Some("--") => break,
+            Some(arg) if arg == flag => value = args.next().and_then(|v| v.into_string().ok()),
+            Some(arg) if arg.starts_with(&flag) && arg[flag.len()..].starts_with('=') => {
+                value = Some(arg[flag.len() + 1..].to_string())
+            },
+            _ => {},
+        }
+    }
+    value
+}
 
-pub fn create_tags_app(tags: &Tags) -> Command {
+/// Returns `true` if `name` is the name of a built-in command.
+pub fn is_builtin(name: &str) -> bool {
+    BUILTINS.contains(&name)
+}
+
+/// Creates the app with the options and the built-in commands, but without the
+/// tags.
+pub fn create_base_app<'help>() -> Command<'help> {
     clap::command!()
         .arg_required_else_help(true)
         .subcommand_negates_reqs(true)
@@ -34,6 +146,7 @@ pub fn create_tags_app(tags: &Tags) -> Command {
         .long_about(ABOUT)
         .help_template(HELP_TEMPLATE)
         .hide_possible_values(true)
+        .color(color::clap_choice())
         .subcommand_help_heading("TAGS")
         .arg(
             Arg::new("print")
@@ -42,14 +155,86 @@ pub fn create_tags_app(tags: &Tags) -> Command {
                 .global(true)
                 .help("Print the path or the URL instead of opening it."),
         )
+        .arg(
+            Arg::new("print0")
+                .long("print0")
+                .conflicts_with_all(&["print", "print-quoted", "format"])
+                .global(true)
+                .help("Print the path or the URL terminated by a NUL character, such as for `xargs -0`."),
+        )
+        .arg(
+            Arg::new("print-quoted")
+                .long("print-quoted")
+                .conflicts_with_all(&["print", "format"])
+                .global(true)
+                .help("Print the path or the URL quoted for the shell."),
+        )
+        .arg(
+            Arg::new("output")
+                .long("output")
+                .takes_value(true)
+                .value_name("FORMAT")
+                .possible_values(["text", "json"])
+                .conflicts_with_all(&["print0", "print-quoted", "format", "paths", "list"])
+                .global(true)
+                .help(
+                    "Print the name, the path, the app, and the action of the opened tag as \
+                     text or as a JSON object. Defaults to text.",
+                ),
+        )
+        .arg(
+            Arg::new("paths")
+                .long("paths")
+                .conflicts_with_all(&["print", "print0", "print-quoted", "format", "list"])
+                .global(true)
+                .help("Print the expanded paths and URLs of the tag and its subtags, one per line."),
+        )
         .arg(
             Arg::new("app")
                 .short('A')
                 .long("app")
                 .takes_value(true)
-                .conflicts_with_all(&["print", "silent-copy"])
+                .conflicts_with_all(&["print", "print0", "print-quoted", "silent-copy"])
+                .global(true)
+                .help(
+                    "Specify the app to open the path or the URL with, or a JSON array of a \
+                     program and its arguments.",
+                ),
+        )
+        .arg(
+            Arg::new("background")
+                .short('b')
+                .long("background")
+                .global(true)
+                .help("Open the path or the URL without bringing the app to the foreground."),
+        )
+        .arg(
+            Arg::new("cwd")
+                .long("cwd")
+                .takes_value(true)
+                .value_name("DIR")
+                .global(true)
+                .help("Start the app that opens the path or the URL in the directory."),
+        )
+        .arg(
+            Arg::new("at")
+                .long("at")
+                .takes_value(true)
+                .value_name("LINE|#ANCHOR")
+                .conflicts_with_all(&["paths", "copy-about", "notes", "info"])
                 .global(true)
-                .help("Specify the app to open the path or the URL with."),
+                .help(
+                    "Open a file at the line, in editors that take one, or a URL at the \
+                     fragment, such as `--at 120` or `--at '#installation'`.",
+                ),
+        )
+        .arg(
+            Arg::new("wait")
+                .short('w')
+                .long("wait")
+                .conflicts_with("background")
+                .global(true)
+                .help("Wait for the app to exit and exit with its exit code."),
         )
         .arg(
             Arg::new("copy")
@@ -67,12 +252,281 @@ pub fn create_tags_app(tags: &Tags) -> Command {
                     "Copy the path or the URL to the system's clipboard without opening the path.",
                 ),
         )
+        .arg(
+            Arg::new("copy-about")
+                .long("copy-about")
+                .conflicts_with_all(&[
+                    "print",
+                    "print0",
+                    "print-quoted",
+                    "app",
+                    "copy",
+                    "silent-copy",
+                    "copy-format",
+                ])
+                .global(true)
+                .help(
+                    "Copy the description to the system's clipboard instead of opening the path.",
+                ),
+        )
+        .arg(
+            Arg::new("notes")
+                .long("notes")
+                .conflicts_with_all(&[
+                    "print",
+                    "print0",
+                    "print-quoted",
+                    "app",
+                    "copy",
+                    "silent-copy",
+                    "copy-about",
+                    "copy-format",
+                ])
+                .global(true)
+                .help("Print the notes of the tag instead of opening the path."),
+        )
+        .arg(
+            Arg::new("info")
+                .long("info")
+                .conflicts_with_all(&[
+                    "print",
+                    "print0",
+                    "print-quoted",
+                    "paths",
+                    "app",
+                    "copy",
+                    "silent-copy",
+                    "copy-about",
+                    "notes",
+                    "copy-format",
+                ])
+                .global(true)
+                .help(
+                    "Print the details of the tag, such as its resolved path and how often it \
+                     was opened, instead of opening the path. Use `--output json` to print them \
+                     as JSON.",
+                ),
+        )
+        .arg(
+            Arg::new("copy-format")
+                .long("copy-format")
+                .takes_value(true)
+                .value_name("FORMAT")
+                .possible_values(["markdown", "html", "org"])
+                .global(true)
+                .help(
+                    "Copy a link with the tag's name as its text instead of the bare path, with \
+                     `--copy` or `--silent-copy`. FORMAT is one of markdown, html, or org.",
+                ),
+        )
+        .arg(Arg::new("osc52").long("osc52").global(true).help(
+            "Copy through the terminal with the OSC 52 escape sequence instead of the \
+                     system's clipboard, such as over SSH.",
+        ))
+        .arg(
+            Arg::new("primary")
+                .long("primary")
+                .conflicts_with("osc52")
+                .global(true)
+                .help(
+                    "Copy to the primary selection instead of the system's clipboard, on Linux \
+                     and BSD.",
+                ),
+        )
+        .arg(
+            Arg::new("random")
+                .long("random")
+                .global(true)
+                .help("Open a random subtag of the tag that has a path or a URL."),
+        )
+        .arg(
+            Arg::new("color")
+                .long("color")
+                .takes_value(true)
+                .value_name("WHEN")
+                .possible_values(["auto", "always", "never"])
+                .global(true)
+                .help(
+                    "Color the output: auto, always, or never. Defaults to auto, which colors \
+                     output written to a terminal unless `NO_COLOR` is set.",
+                ),
+        )
+        .arg(
+            Arg::new("data-file")
+                .long("data-file")
+                .takes_value(true)
+                .value_name("PATH")
+                .global(true)
+                .help("Use the tags file at the path instead of `$OPENTAG_DATA` or the default one."),
+        )
+        .arg(
+            Arg::new("dry-run")
+                .long("dry-run")
+                .global(true)
+                .help(
+                    "Print what would be opened, copied, or written to the tags file instead of \
+                     doing it.",
+                ),
+        )
+        .arg(
+            Arg::new("error-format")
+                .long("error-format")
+                .takes_value(true)
+                .value_name("FORMAT")
+                .possible_values(["human", "json", "auto"])
+                .global(true)
+                .help(
+                    "Print errors as human-readable text or as JSON objects with the `code`, \
+                     `kind`, and `message` of the error. auto uses JSON if stderr is not a \
+                     terminal. Defaults to human.",
+                ),
+        )
+        .arg(
+            Arg::new("quiet")
+                .short('q')
+                .long("quiet")
+                .global(true)
+                .help("Do not print status messages, such as \"Added tag.\". Errors are still printed."),
+        )
+        .arg(
+            Arg::new("read-only")
+                .long("read-only")
+                .global(true)
+                .help(
+                    "Open, list, and search the tags without changing the tags file or the usage \
+                     data. Implied if the tags file is not writable.",
+                ),
+        )
+        .arg(
+            Arg::new("no-history")
+                .long("no-history")
+                .global(true)
+                .help(
+                    "Do not record opened or selected tags in the usage data or the history, \
+                     such as for private or automated invocations.",
+                ),
+        )
+        .arg(
+            Arg::new("no-defaults")
+                .long("no-defaults")
+                .global(true)
+                .help(
+                    "Ignore the `defaults` in the config, such as to open a tag when it is \
+                     printed by default.",
+                ),
+        )
+        .arg(
+            Arg::new("yes")
+                .short('y')
+                .long("yes")
+                .global(true)
+                .help("Answer yes to all confirmation prompts, such as in scripts."),
+        )
+        .arg(
+            Arg::new("verbose")
+                .short('v')
+                .long("verbose")
+                .multiple_occurrences(true)
+                .global(true)
+                .help(
+                    "Print diagnostics on stderr, such as the matched tag and how it is opened. \
+                     Give twice for more details.",
+                ),
+        )
+        .arg(
+            Arg::new("timings")
+                .long("timings")
+                .global(true)
+                .help("Print how long each phase of the invocation took."),
+        )
         .arg(
             Arg::new("add")
                 .short('a')
                 .long("add")
                 .help("Add a new tag."),
         )
+        .arg(
+            Arg::new("from-dir")
+                .long("from-dir")
+                .takes_value(true)
+                .value_name("DIR")
+                .requires("add")
+                .help("Add a tag for every file in the directory, with `--add`."),
+        )
+        .arg(
+            Arg::new("stdin")
+                .long("stdin")
+                .requires("add")
+                .conflicts_with("from-dir")
+                .help(
+                    "Add the tags read from stdin, one per line, as JSON objects or as a name and \
+                     a path separated by a tab, with `--add`.",
+                ),
+        )
+        .arg(
+            Arg::new("template")
+                .long("template")
+                .takes_value(true)
+                .number_of_values(2)
+                .value_names(&["TEMPLATE", "NAME"])
+                .requires("add")
+                .help(
+                    "Add a tag named NAME, and any aliases, comma-separated, from the template in \
+                     the config, with `--add`.",
+                ),
+        )
+        .arg(
+            Arg::new("ext")
+                .long("ext")
+                .takes_value(true)
+                .value_name("EXT")
+                .multiple_occurrences(true)
+                .requires("from-dir")
+                .help("Only add files with the extension, with `--from-dir`. Can be repeated."),
+        )
+        .arg(
+            Arg::new("parent")
+                .long("parent")
+                .takes_value(true)
+                .value_name("TAG")
+                .requires("bulk-add")
+                .help(
+                    "Add the tags under the tag, such as `web/github`, which is added if it does \
+                     not exist, with `--from-dir`, `--stdin`, or `--template`.",
+                ),
+        )
+        .arg(
+            Arg::new("strict")
+                .long("strict")
+                .requires("add")
+                .help(
+                    "Fail if the path of a new tag does not exist or its URL is malformed, \
+                     instead of warning, with `--add`.",
+                ),
+        )
+        .arg(
+            Arg::new("force")
+                .long("force")
+                .requires("add")
+                .help("Add tags without checking their paths and URLs, with `--add`."),
+        )
+        .arg(
+            Arg::new("edit-notes")
+                .long("edit-notes")
+                .requires("update")
+                .conflicts_with_all(&["pin", "unpin", "secret"])
+                .help("Edit the notes of the selected tag in `$EDITOR`, with `--update`."),
+        )
+        .arg(
+            Arg::new("secret")
+                .long("secret")
+                .requires("add-or-update")
+                .conflicts_with_all(&["pin", "unpin", "bulk-add"])
+                .help(
+                    "Encrypt the path and the description of the tag with `age`, with `--add` or \
+                     `--update`.",
+                ),
+        )
         .arg(
             Arg::new("remove")
                 .short('r')
@@ -85,6 +539,19 @@ pub fn create_tags_app(tags: &Tags) -> Command {
                 .long("update")
                 .help("Update an existing tag."),
         )
+        .arg(
+            Arg::new("pin")
+                .long("pin")
+                .requires("update")
+                .conflicts_with("unpin")
+                .help("Pin the selected tag so that it is shown first, with `--update`."),
+        )
+        .arg(
+            Arg::new("unpin")
+                .long("unpin")
+                .requires("update")
+                .help("Unpin the selected tag, with `--update`."),
+        )
         .arg(
             Arg::new("list")
                 .short('l')
@@ -92,15 +559,1190 @@ pub fn create_tags_app(tags: &Tags) -> Command {
                 .global(true)
                 .help("List all global tags or subtags of specified tag."),
         )
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .takes_value(true)
+                .value_name("TEMPLATE")
+                .global(true)
+                .help(
+                    "Format each tag with the template when listing or printing. Use {name}, \
+                     {aliases}, {icon}, {path}, {about}, {app}, and {labels} as placeholders.",
+                ),
+        )
+        .arg(
+            Arg::new("no-pager")
+                .long("no-pager")
+                .global(true)
+                .help("Print long listings and help directly instead of through `$PAGER`."),
+        )
+        .arg(
+            Arg::new("recursive")
+                .short('R')
+                .long("recursive")
+                .global(true)
+                .help("List the subtags of the listed tags as well, with `--list` or `--paths`."),
+        )
+        .arg(
+            Arg::new("depth")
+                .long("depth")
+                .takes_value(true)
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize))
+                .requires("list")
+                .global(true)
+                .help("List up to N levels of subtags, with `--list`."),
+        )
+        .arg(
+            Arg::new("filter")
+                .long("filter")
+                .takes_value(true)
+                .value_name("TEXT")
+                .requires("list")
+                .global(true)
+                .help("List only tags with a name containing the text, with `--list`."),
+        )
+        .arg(
+            Arg::new("filter-path")
+                .long("filter-path")
+                .takes_value(true)
+                .value_name("TEXT")
+                .requires("list")
+                .global(true)
+                .help("List only tags with a path or a URL containing the text, with `--list`."),
+        )
+        .arg(
+            Arg::new("label")
+                .long("label")
+                .takes_value(true)
+                .value_name("LABEL")
+                .multiple_occurrences(true)
+                .global(true)
+                .help(
+                    "List only tags with the label, or set the labels of the tag, with `--list`, \
+                     `--add`, or `--update`. Can be given more than once.",
+                ),
+        )
+        .arg(
+            Arg::new("has-path")
+                .long("has-path")
+                .requires("list")
+                .conflicts_with("no-path")
+                .global(true)
+                .help("List only tags with a path or a URL, with `--list`."),
+        )
+        .arg(
+            Arg::new("no-path")
+                .long("no-path")
+                .requires("list")
+                .global(true)
+                .help("List only tags without a path or a URL, with `--list`."),
+        )
+        .arg(
+            Arg::new("sort")
+                .long("sort")
+                .takes_value(true)
+                .value_name("ORDER")
+                .possible_values(["name", "recent", "usage", "kind"])
+                .requires("list")
+                .global(true)
+                .help(
+                    "List the tags at each level by name, most recently changed, most opened, \
+                     or kind, instead of in the order of the tags file, with `--list`. Pinned \
+                     tags are listed first.",
+                ),
+        )
+        .arg(
+            Arg::new("group-by")
+                .long("group-by")
+                .takes_value(true)
+                .value_name("FIELD")
+                .possible_values(["label", "kind"])
+                .requires("list")
+                .conflicts_with_all(&["format", "count"])
+                .global(true)
+                .help(
+                    "List the tags under a heading for each of their labels, or for their kind, \
+                     with `--list`.",
+                ),
+        )
+        .arg(
+            Arg::new("count")
+                .long("count")
+                .requires("list")
+                .conflicts_with_all(&["format", "no-pager"])
+                .global(true)
+                .help(
+                    "Print only the number of tags that would be listed, with `--list`, such as \
+                     the size of a tree with `--recursive`.",
+                ),
+        )
         .groups(&[
+            ArgGroup::new("bulk-add").args(&["from-dir", "stdin", "template"]),
+            ArgGroup::new("add-or-update").args(&["add", "update"]),
             ArgGroup::new("cmd-conflict")
                 .args(&["add", "remove", "update", "list"])
                 .multiple(false)
                 .conflicts_with("cmd-req")
                 .required(true),
             ArgGroup::new("cmd-req")
-                .args(&["print", "copy", "silent-copy", "app"])
+                .args(&[
+                    "print",
+                    "print0",
+                    "print-quoted",
+                    "paths",
+                    "copy",
+                    "silent-copy",
+                    "copy-about",
+                    "notes",
+                    "info",
+                    "app",
+                    "background",
+                    "cwd",
+                    "at",
+                    "wait",
+                    "random",
+                ])
                 .multiple(true),
         ])
-        .subcommands(tags.iter().map(command_from_tag))
+        .subcommands(builtin_commands())
+}
+
+/// Adds the tags to the app as subcommands.
+///
+/// Only the tags invoked in the command-line arguments get subcommands for
+/// their subtags, so that a command is not created for every tag on every
+/// invocation. The help and the errors of each level are unchanged.
+///
+/// With `ot tag`, all tags are added to the `tag` command instead, including
+/// those named like a built-in command.
+pub fn add_tags<'a>(app: Command<'a>, tags: &'a Tags, args: &[OsString]) -> Command<'a> {
+    let positionals = positional_indices(&app, args);
+    match positionals.first().and_then(|&i| args[i].to_str()) {
+        Some("tag") => {
+            let names = invoked_tag_names(tags, args, &positionals[1..]);
+            app.mut_subcommand("tag", |cmd| {
+                cmd.subcommands(tags.iter().map(|t| command_from_tag(t, &names)))
+            })
+        },
+        Some(name) if is_builtin(name) => app,
+        _ => {
+            let names = invoked_tag_names(tags, args, &positionals);
+            app.subcommands(
+                tags.iter()
+                    .filter(|t| !t.names.iter().any(|n| is_builtin(n)))
+                    .map(|t| command_from_tag(t, &names)),
+            )
+        },
+    }
+}
+
+/// Returns the words that complete the last word of the command line, with
+/// their descriptions.
+///
+/// The first word is the program. The other words are followed through the
+/// tags, skipping options, and the names and aliases at the level reached are
+/// completed, along with the built-in commands for the first word. Long
+/// options are completed for a word starting with `-`.
+///
+/// The line is split into words as the shell does, so names with spaces can
+/// be quoted or escaped, as in `ot 'Rust Book'` or `ot Rust\ B`.
+pub fn completions(tags: &[Tag], line: &str) -> Vec<(String, Option<String>)> {
+    let (words, complete) = shell_words(line);
+    let mut words = words.iter().skip(1).map(String::as_str).collect::<Vec<_>>();
+    let partial = if complete {
+        ""
+    } else {
+        match words.pop() {
+            Some(word) => word,
+            None => return Vec::new(),
+        }
+    };
+
+    let app = create_base_app();
+    if partial.starts_with('-') {
+        return app
+            .get_arguments()
+            .filter(|a| !a.is_hide_set())
+            .filter_map(|a| Some((format!("--{}", a.get_long()?), a.get_help())))
+            .filter(|(long, _)| long.starts_with(partial))
+            .map(|(long, help)| (long, help.map(str::to_string)))
+            .collect();
+    }
+
+    let words = words
+        .into_iter()
+        .filter(|w| !w.starts_with('-'))
+        .collect::<Vec<_>>();
+    let mut level = tags;
+    for (n, word) in words.iter().enumerate() {
+        if n == 0 && *word == "tag" {
+            continue;
+        }
+        if n == 0 && is_builtin(word) {
+            return Vec::new();
+        }
+        match level.iter().find(|t| t.has_name(word)) {
+            Some(tag) => level = &tag.subtags,
+            None => return Vec::new(),
+        }
+    }
+
+    // tags named like a built-in command can only be invoked with `ot tag`
+    let shadowed = |t: &Tag| words.is_empty() && t.names.iter().any(|n| is_builtin(n));
+    let mut candidates = level
+        .iter()
+        .filter(|t| !shadowed(t))
+        .flat_map(|t| t.names.iter().map(move |n| (n, t)))
+        .filter(|(n, _)| n.starts_with(partial))
+        .map(|(n, t)| {
+            (
+                n.clone(),
+                t.about
+                    .as_deref()
+                    .and_then(|a| a.lines().next())
+                    .map(str::to_string),
+            )
+        })
+        .collect::<Vec<_>>();
+    if words.is_empty() {
+        candidates.extend(
+            app.get_subcommands()
+                .filter(|c| !c.get_name().starts_with("__") && c.get_name().starts_with(partial))
+                .map(|c| (c.get_name().to_string(), c.get_about().map(str::to_string))),
+        );
+    }
+
+    candidates
+}
+
+/// Splits the command line into words as the shell does, following quotes and
+/// backslashes, and returns them with whether the last word is complete.
+///
+/// A quote that is not closed, as in a word being typed, ends with the line.
+fn shell_words(line: &str) -> (Vec<String>, bool) {
+    let mut words = Vec::new();
+    // the word being read, if any, which may be empty if it is quoted
+    let mut word: Option<String> = None;
+    let mut quote = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('"') | None, '\\') => {
+                if let Some(escaped) = chars.next() {
+                    word.get_or_insert_with(String::new).push(escaped);
+                }
+            },
+            (Some(_), c) => word.get_or_insert_with(String::new).push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            },
+            (None, c) if c.is_whitespace() => words.extend(word.take()),
+            (None, c) => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+
+    let complete = word.is_none();
+    words.extend(word);
+    (words, complete)
+}
+
+/// Creates a `clap` subcommand for the given tag.
+///
+/// Subcommands are only created for the subtags if `path` starts with a name
+/// of the tag, and recursively for the subtag named next in `path`.
+pub fn command_from_tag<'a, S: AsRef<str>>(tag: &'a Tag, path: &[S]) -> Command<'a> {
+    let mut cmd = Command::new(tag.names.first().expect("expected at least one name"))
+        .disable_help_subcommand(true);
+
+    if let Some(ref long_about) = tag.about {
+        cmd = cmd.about(long_about.lines().next());
+        cmd = cmd.long_about(long_about.as_str());
+    }
+
+    for alias in tag.names.iter().skip(1) {
+        cmd = cmd.visible_alias(alias.as_str());
+    }
+
+    // words that name no subtag are searched for with the tag's URL
+    if tag.query_param.is_some() {
+        cmd = cmd.arg(
+            Arg::new("query")
+                .value_name("WORDS")
+                .multiple_values(true)
+                .help("Search for the words with the URL of the tag."),
+        );
+    }
+
+    match path.split_first() {
+        Some((first, rest)) if tag.has_name(first.as_ref()) => {
+            cmd.subcommands(tag.subtags.iter().map(|t| command_from_tag(t, rest)))
+        },
+        _ => cmd,
+    }
+}
+
+/// Finds the tag matching the command invocation, with the primary names of
+/// the tag and all of its parents, root first.
+///
+/// Only the levels along the invoked path are searched, with
+/// [`Tag::has_name`], which does not allocate for names already normalized.
+/// There is deliberately no map of names by level: each level is searched
+/// once per run, and building its map would take as long as the scan itself,
+/// while indexing the whole tree up front costs more than the lookup saves.
+pub fn find_tag<'a>(
+    tags: &'a [Tag],
+    cmd: &str,
+    matches: &ArgMatches,
+) -> Option<(&'a Tag, Vec<String>)> {
+    let mut tag = tags.iter().find(|t| t.has_name(cmd))?;
+    let mut names = vec![tag.names[0].clone()];
+
+    let mut matches = matches;
+    while let Some((subcmd, sub_matches)) = matches.subcommand() {
+        tag = tag.subtags.iter().find(|t| t.has_name(subcmd))?;
+        names.push(tag.names[0].clone());
+        matches = sub_matches;
+    }
+
+    Some((tag, names))
+}
+
+/// Returns the words given after the invoked tag to search for with its URL,
+/// if it has a query parameter.
+pub fn query_words(matches: &ArgMatches) -> Vec<&str> {
+    let mut matches = matches;
+    while let Some((_, sub_matches)) = matches.subcommand() {
+        matches = sub_matches;
+    }
+
+    match matches.try_get_many::<String>("query") {
+        Ok(Some(words)) => words.map(String::as_str).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Returns the names of the tags invoked in the command-line arguments, root
+/// first, up to the first name that matches no tag.
+fn invoked_tag_names(tags: &Tags, args: &[OsString], positionals: &[usize]) -> Vec<String> {
+    let mut level = tags.as_slice();
+    let mut names = Vec::new();
+    for &i in positionals {
+        let arg = match args[i].to_str() {
+            Some(arg) => arg,
+            None => break,
+        };
+
+        match level.iter().find(|t| t.has_name(arg)) {
+            Some(tag) => level = &tag.subtags,
+            None => break,
+        }
+        names.push(arg.to_string());
+    }
+
+    names
+}
+
+/// Replaces the names of tags abbreviated to a unique prefix in the
+/// command-line arguments with the primary names of the tags.
+///
+/// The names are resolved level by level, so that `ot w g` becomes
+/// `ot web github` if no other tag at each level starts with the prefix.
+/// Names that match a tag exactly are kept as is. The walk stops at the first
+/// name that is a built-in command or that matches no tag or several tags.
+pub fn expand_tag_prefixes(app: &Command, tags: &Tags, mut args: Vec<OsString>) -> Vec<OsString> {
+    let mut level = tags.as_slice();
+    for (n, i) in positional_indices(app, &args).into_iter().enumerate() {
+        let arg = match args[i].to_str() {
+            Some(arg) if !(n == 0 && is_builtin(arg)) => arg.to_string(),
+            _ => break,
+        };
+
+        let candidates = level
+            .iter()
+            .filter(|t| t.names.iter().any(|n| n.starts_with(&arg)))
+            .collect::<Vec<_>>();
+        let tag = match level.iter().find(|t| t.names.contains(&arg)) {
+            Some(tag) => tag,
+            None if candidates.len() == 1 => {
+                args[i] = candidates[0].names[0].clone().into();
+                candidates[0]
+            },
+            None => break,
+        };
+
+        level = &tag.subtags;
+    }
+
+    args
+}
+
+/// Replaces the first name of the tag in the command-line arguments with the
+/// primary names of the nested tag it is an alias of, so that `ot gh` becomes
+/// `ot dev github`.
+///
+/// Nothing is changed if the name is a built-in command, matches a top-level
+/// tag, or is an alias of no nested tag or of several of them.
+pub fn expand_global_aliases(app: &Command, tags: &Tags, mut args: Vec<OsString>) -> Vec<OsString> {
+    let i = match positional_indices(app, &args).first() {
+        Some(&i) => i,
+        None => return args,
+    };
+    let name = match args[i].to_str() {
+        Some(name) if !is_builtin(name) && !tags.iter().any(|t| t.has_name(name)) => name,
+        _ => return args,
+    };
+
+    if let Some([names]) = tag::name_index(tags, false).get(name).map(Vec::as_slice) {
+        log::info!("resolved alias `{}` to `{}`", name, names.join("/"));
+        let names = names.iter().map(OsString::from).collect::<Vec<_>>();
+        args.splice(i..=i, names);
+    }
+
+    args
+}
+
+/// Returns the index of the first name of the tag in the command-line
+/// arguments and the primary names of the nested tags with that name, root
+/// first, if the name is not a built-in command and matches no top-level tag.
+///
+/// Returns `None` if no nested tag has the name either.
+pub fn nested_tags_named(
+    app: &Command,
+    tags: &Tags,
+    args: &[OsString],
+) -> Option<(usize, Vec<Vec<String>>)> {
+    let &i = positional_indices(app, args).first()?;
+    let name = args[i].to_str()?;
+    if is_builtin(name) || tags.iter().any(|t| t.has_name(name)) {
+        return None;
+    }
+
+    let mut index = tag::name_index(tags, true);
+    index.remove(name).map(|found| (i, found))
+}
+
+/// Replaces the names of the tag in the command-line arguments with the
+/// `pick` command if a name matches no tag.
+///
+/// The names are passed to `pick` as its query, separated by `/`, so that the
+/// picker starts with the tags closest to the invocation. Returns `None` if all
+/// names match a tag or if a built-in command is invoked.
+pub fn unknown_tag_to_pick(app: &Command, tags: &Tags, args: &[OsString]) -> Option<Vec<OsString>> {
+    let positionals = positional_indices(app, args);
+    let mut level = tags.as_slice();
+    let mut names = Vec::new();
+    for (n, &i) in positionals.iter().enumerate() {
+        let arg = args[i].to_str()?;
+        if n == 0 && is_builtin(arg) {
+            return None;
+        }
+
+        names.push(arg);
+        match level.iter().find(|t| t.has_name(arg)) {
+            Some(tag) => level = &tag.subtags,
+            None => {
+                let mut pick_args = args
+                    .iter()
+                    .enumerate()
+                    .filter(|(j, _)| !positionals[..=n].contains(j))
+                    .map(|(_, a)| a.clone())
+                    .collect::<Vec<_>>();
+                let end = pick_args
+                    .iter()
+                    .position(|a| a == "--")
+                    .unwrap_or(pick_args.len());
+                pick_args.splice(end..end, ["pick".into(), names.join("/").into()]);
+                return Some(pick_args);
+            },
+        }
+    }
+
+    None
+}
+
+/// Normalizes the positional arguments naming tags as the names of the tags
+/// are, so that they match however they were typed.
+///
+/// Nothing is changed if a built-in command other than `tag` is invoked, as
+/// its arguments may be file paths, which must be used as they are.
+pub fn normalize_tag_names(app: &Command, mut args: Vec<OsString>) -> Vec<OsString> {
+    for (n, i) in positional_indices(app, &args).into_iter().enumerate() {
+        let arg = match args[i].to_str() {
+            Some("tag") if n == 0 => continue,
+            Some(arg) if n == 0 && is_builtin(arg) => break,
+            Some(arg) => tag::normalize_name(arg),
+            None => break,
+        };
+        args[i] = arg.into();
+    }
+
+    args
+}
+
+/// Returns the external command for the invocation and the arguments to pass
+/// to it, like `git` and `cargo` do.
+///
+/// If the first positional argument is neither a built-in command nor a tag,
+/// `ot-<name>` is looked up on `PATH`. It is passed the arguments after the
+/// name.
+pub fn external_command(
+    app: &Command,
+    tags: &Tags,
+    args: &[OsString],
+) -> Option<(PathBuf, Vec<OsString>)> {
+    let &i = positional_indices(app, args).first()?;
+    let name = args[i].to_str()?;
+    if is_builtin(name) || tags.iter().any(|t| t.has_name(name)) {
+        return None;
+    }
+
+    let program = find_in_path(&format!("ot-{}", name))?;
+    Some((program, args[i + 1..].to_vec()))
+}
+
+/// Returns the path to the program in the first directory of `PATH` that has
+/// it.
+fn find_in_path(program: &str) -> Option<PathBuf> {
+    let file_name = if cfg!(windows) {
+        format!("{}.exe", program)
+    } else {
+        program.to_string()
+    };
+
+    env::split_paths(&env::var_os("PATH")?)
+        .map(|dir| dir.join(&file_name))
+        .find(|path| path.is_file())
+}
+
+/// Returns the indices of the positional command-line arguments before `--`,
+/// skipping the program name and the values of options.
+fn positional_indices(app: &Command, args: &[OsString]) -> Vec<usize> {
+    let value_args = app
+        .get_arguments()
+        .filter(|a| a.is_takes_value_set())
+        .collect::<Vec<_>>();
+    // the number of values that follow the option, such as 2 for `--template`
+    let value_count = |arg: &str| {
+        let (value_arg, inline) = match arg.strip_prefix("--") {
+            Some(long) => match long.split_once('=') {
+                Some((long, _)) => (value_args.iter().find(|a| a.get_long() == Some(long)), 1),
+                None => (value_args.iter().find(|a| a.get_long() == Some(long)), 0),
+            },
+            None => (
+                arg.chars()
+                    .last()
+                    .and_then(|c| value_args.iter().find(|a| a.get_short() == Some(c))),
+                0,
+            ),
+        };
+        value_arg.map_or(0, |a| a.get_num_vals().unwrap_or(1) - inline)
+    };
+
+    let mut positionals = Vec::new();
+    let mut i = 1;
+    while i < args.len() {
+        match args[i].to_str() {
+            Some("--") => break,
+            Some(arg) if arg.starts_with('-') && arg.len() > 1 => i += value_count(arg),
+            _ => positionals.push(i),
+        }
+        i += 1;
+    }
+
+    positionals
+}
+
+/// Returns the `--on-conflict` argument of the commands that add tags from
+/// a file.
+fn on_conflict_arg<'help>() -> Arg<'help> {
+    Arg::new("on-conflict")
+        .long("on-conflict")
+        .takes_value(true)
+        .value_name("ACTION")
+        .possible_values(["ask", "fail", "skip", "rename", "merge", "overwrite"])
+        .help(
+            "What to do with a tag whose name is taken at its level: skip it, rename it, merge \
+             it into the existing tag, or overwrite the existing tag. Defaults to asking for \
+             each, or failing when not run in a terminal.",
+        )
+}
+
+/// Creates the `clap` subcommands for the built-in commands.
+///
+/// The commands are hidden so that they are not listed with the tags; they
+/// are described in the help template instead.
+fn builtin_commands<'help>() -> Vec<Command<'help>> {
+    vec![
+        Command::new("__complete")
+            .about("Print the completions of a command line for the shell.")
+            .arg(
+                Arg::new("shell")
+                    .required(true)
+                    .value_name("SHELL")
+                    .possible_values(["bash", "zsh", "fish"])
+                    .help("The shell to print the completions for."),
+            )
+            .arg(
+                Arg::new("line")
+                    .value_name("LINE")
+                    .allow_hyphen_values(true)
+                    .help("The command line up to the cursor, including the program."),
+            ),
+        Command::new("alias")
+            .about("Add or remove an alias of a tag.")
+            .subcommand_required(true)
+            .disable_help_subcommand(true)
+            .subcommands([
+                Command::new("add")
+                    .about("Add an alias to a tag.")
+                    .arg(
+                        Arg::new("tag")
+                            .required(true)
+                            .value_name("TAG")
+                            .help("The tag, such as `web/github`."),
+                    )
+                    .arg(
+                        Arg::new("alias")
+                            .required(true)
+                            .value_name("ALIAS")
+                            .help("The alias to add, such as `gh`."),
+                    ),
+                Command::new("remove")
+                    .about("Remove an alias of a tag.")
+                    .arg(
+                        Arg::new("tag")
+                            .required(true)
+                            .value_name("TAG")
+                            .help("The tag, such as `web/github`."),
+                    )
+                    .arg(
+                        Arg::new("alias")
+                            .required(true)
+                            .value_name("ALIAS")
+                            .help("The alias to remove."),
+                    ),
+            ]),
+        Command::new("aliases")
+            .about("Print shell aliases for the tags.")
+            .arg(
+                Arg::new("prefix")
+                    .long("prefix")
+                    .takes_value(true)
+                    .default_value("o")
+                    .help("The text added before the name of each alias."),
+            )
+            .arg(
+                Arg::new("recursive")
+                    .short('R')
+                    .long("recursive")
+                    .help("Print aliases for the subtags as well."),
+            )
+            .arg(
+                Arg::new("separator")
+                    .long("separator")
+                    .takes_value(true)
+                    .default_value("-")
+                    .help("The text between the names of a subtag and its parents, with `--recursive`."),
+            ),
+        Command::new("apply")
+            .about("Apply the operations in a file to the tags at once.")
+            .arg(
+                Arg::new("file")
+                    .required(true)
+                    .value_name("FILE")
+                    .help(
+                        "The JSON or TOML file of operations, or `-` for JSON on stdin. Every \
+                         operation is checked before the tags file is written, once.",
+                    ),
+            )
+            .arg(on_conflict_arg()),
+        Command::new("audit-urls")
+            .about("Check that the URLs of the tags still work.")
+            .arg(
+                Arg::new("parallel")
+                    .long("parallel")
+                    .takes_value(true)
+                    .value_name("N")
+                    .value_parser(clap::value_parser!(usize))
+                    .default_value("8")
+                    .help("The number of URLs checked at a time."),
+            ),
+        Command::new("capture")
+            .about("Add a tag for the path or the URL in the clipboard.")
+            .arg(
+                Arg::new("name")
+                    .value_name("NAME")
+                    .help(
+                        "The name of the tag, and any aliases, comma-separated. A name based on \
+                         the URL's host or the file's name is suggested if not given.",
+                    ),
+            )
+            .arg(
+                Arg::new("parent")
+                    .long("parent")
+                    .takes_value(true)
+                    .value_name("TAG")
+                    .help(
+                        "Add the tag under the tag, such as `web/github`, which is added if it \
+                         does not exist, instead of selecting the parent interactively.",
+                    ),
+            ),
+        Command::new("copy")
+            .about("Copy a tag and its subtags under another tag.")
+            .arg(
+                Arg::new("source")
+                    .required(true)
+                    .value_name("SOURCE")
+                    .help("The tag to copy, such as `web/github`."),
+            )
+            .arg(
+                Arg::new("parent")
+                    .required(true)
+                    .value_name("PARENT")
+                    .help("The tag to copy under, or `/` for the global tags."),
+            )
+            .arg(
+                Arg::new("name")
+                    .short('n')
+                    .long("name")
+                    .takes_value(true)
+                    .help("The name of the copy. Aliases are not copied if given."),
+            ),
+        Command::new("diff")
+            .about("Show how the tags changed since a backup or compared to another file.")
+            .arg(
+                Arg::new("backup")
+                    .long("backup")
+                    .takes_value(true)
+                    .value_name("N")
+                    .value_parser(clap::value_parser!(usize))
+                    .conflicts_with("file")
+                    .help(
+                        "Compare to the Nth most recent backup, made before the tags file was \
+                         changed. Defaults to 1.",
+                    ),
+            )
+            .arg(
+                Arg::new("file")
+                    .long("file")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .help("Compare to the tags in the file instead of a backup."),
+            ),
+        Command::new("dupes").about("List tags that open the same path or URL."),
+        Command::new("edit")
+            .about("Edit the tags file in the editor.")
+            .arg(
+                Arg::new("tag")
+                    .value_name("TAG")
+                    .help("The tag to edit as TOML instead of the file, such as `web/github`."),
+            )
+            .arg(
+                Arg::new("subtags")
+                    .long("subtags")
+                    .requires("tag")
+                    .help("Edit the subtags of the tag as well."),
+            ),
+        Command::new("exec")
+            .about("Run a command with the path of a tag in place of `{}`.")
+            .arg(
+                Arg::new("tag")
+                    .required(true)
+                    .value_name("TAG")
+                    .help("The tag whose path is used, such as `web/github`."),
+            )
+            .arg(
+                Arg::new("command")
+                    .required(true)
+                    .last(true)
+                    .multiple_values(true)
+                    .value_name("COMMAND")
+                    .help(
+                        "The command to run, after `--`. Each `{}` in it is replaced with the \
+                         path, which is appended if there is none.",
+                    ),
+            )
+            .arg(
+                Arg::new("recursive")
+                    .short('R')
+                    .long("recursive")
+                    .help("Run the command for every subtag with a path as well, however deep."),
+            ),
+        Command::new("export")
+            .about("Export the tags as a document.")
+            .arg(
+                Arg::new("tag")
+                    .value_name("TAG")
+                    .help(
+                        "Export only the tag and its subtags, such as `work/projects`, instead \
+                         of all tags.",
+                    ),
+            )
+            .arg(
+                Arg::new("format")
+                    .long("format")
+                    .takes_value(true)
+                    .value_name("FORMAT")
+                    .possible_values(["markdown", "json", "alfred", "desktop-entries"])
+                    .default_value("markdown")
+                    .help(
+                        "The format of the document. `json` writes a tags file, which can be \
+                         used or merged by others as is. `alfred` writes the items of an Alfred \
+                         script filter, and `desktop-entries` writes a desktop entry for every \
+                         tag to the output directory, for launchers such as rofi or the GNOME \
+                         search.",
+                    ),
+            )
+            .arg(
+                Arg::new("output")
+                    .short('o')
+                    .long("output")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .help(
+                        "Write the document to the file instead of printing it, or the desktop \
+                         entries to the directory.",
+                    ),
+            ),
+        Command::new("grep")
+            .about("Search the paths, descriptions, and notes of all tags with a regex.")
+            .arg(
+                Arg::new("pattern")
+                    .required(true)
+                    .value_name("PATTERN")
+                    .help("The regular expression to search for."),
+            )
+            .arg(
+                Arg::new("ignore-case")
+                    .short('i')
+                    .long("ignore-case")
+                    .help("Match the pattern regardless of case."),
+            ),
+        Command::new("group")
+            .about("Open or list the groups of tags in the config.")
+            .subcommand_required(true)
+            .disable_help_subcommand(true)
+            .subcommands([
+                Command::new("open")
+                    .about("Open every tag of a group.")
+                    .arg(
+                        Arg::new("name")
+                            .required(true)
+                            .value_name("NAME")
+                            .help("The name of the group."),
+                    )
+                    .arg(
+                        Arg::new("parallel")
+                            .long("parallel")
+                            .help("Open the tags without waiting for their apps to exit (default)."),
+                    )
+                    .arg(
+                        Arg::new("sequential")
+                            .long("sequential")
+                            .conflicts_with("parallel")
+                            .help("Open each tag only after the app of the previous one exits."),
+                    )
+                    .arg(
+                        Arg::new("delay")
+                            .long("delay")
+                            .takes_value(true)
+                            .value_name("MS")
+                            .value_parser(clap::value_parser!(u64))
+                            .help("Wait this many milliseconds between opening the tags."),
+                    ),
+                Command::new("list").about("List the groups and their tags."),
+            ]),
+        Command::new("history")
+            .about("List the tags opened most recently, or clear the list.")
+            .disable_help_subcommand(true)
+            .args_conflicts_with_subcommands(true)
+            .arg(
+                Arg::new("count")
+                    .short('n')
+                    .takes_value(true)
+                    .value_name("N")
+                    .value_parser(clap::value_parser!(usize))
+                    .default_value("50")
+                    .help("The number of opened tags to list."),
+            )
+            .arg(
+                Arg::new("json")
+                    .long("json")
+                    .help("Print the opened tags as JSON, one object per line."),
+            )
+            .subcommand(Command::new("clear").about("Delete the list of opened tags.")),
+        Command::new("import")
+            .about("Import tags from a file.")
+            .arg(
+                Arg::new("file")
+                    .required_unless_present_any(["browser", "zoxide", "ssh-config"])
+                    .value_name("FILE")
+                    .help(
+                        "The file to import, or `-` for stdin. With `--browser`, the bookmarks \
+                         file of the browser, found in its default profile if not given. With \
+                         `--ssh-config`, the SSH config file, `~/.ssh/config` if not given.",
+                    ),
+            )
+            .arg(
+                Arg::new("browser")
+                    .long("browser")
+                    .takes_value(true)
+                    .value_name("BROWSER")
+                    .possible_values(["firefox", "chrome", "safari"])
+                    .conflicts_with_all(&["format", "mapping"])
+                    .help(
+                        "Import the bookmarks of the browser, keeping their folders. Bookmarks \
+                         whose URL is already tagged are skipped. Firefox requires `sqlite3`.",
+                    ),
+            )
+            .arg(
+                Arg::new("ssh-config")
+                    .long("ssh-config")
+                    .conflicts_with_all(&["browser", "format", "mapping"])
+                    .help(
+                        "Import the hosts of the SSH config file as `ssh://` URLs, under `ssh` \
+                         unless `--parent` is given. Hosts that are already tagged are skipped.",
+                    ),
+            )
+            .arg(
+                Arg::new("zoxide")
+                    .long("zoxide")
+                    .conflicts_with_all(&["file", "browser", "ssh-config", "format", "mapping"])
+                    .help(
+                        "Pick among the directories visited most often according to zoxide to \
+                         import. Directories that are already tagged are skipped.",
+                    ),
+            )
+            .arg(
+                Arg::new("count")
+                    .long("count")
+                    .takes_value(true)
+                    .value_name("N")
+                    .value_parser(clap::value_parser!(usize))
+                    .default_value("20")
+                    .help("The number of directories offered with `--zoxide`."),
+            )
+            .arg(
+                Arg::new("format")
+                    .long("format")
+                    .takes_value(true)
+                    .value_name("FORMAT")
+                    .possible_values(["csv"])
+                    .help("The format of the file. Defaults to csv."),
+            )
+            .arg(
+                Arg::new("parent")
+                    .long("parent")
+                    .takes_value(true)
+                    .value_name("TAG")
+                    .help(
+                        "The tag to import under, such as `web/github`, which is added if it does \
+                         not exist.",
+                    ),
+            )
+            .arg(
+                Arg::new("mapping")
+                    .long("mapping")
+                    .takes_value(true)
+                    .value_name("MAP")
+                    .help(
+                        "The headers of the columns of the fields of a tag, such as \
+                         `name=Service,path=URL`. The fields are name, aliases, path, about, and \
+                         app.",
+                    ),
+            )
+            .arg(on_conflict_arg()),
+        Command::new("info")
+            .about("Print which tags file and config file are used, and why.")
+            .arg(
+                Arg::new("json")
+                    .long("json")
+                    .help("Print the information as JSON."),
+            ),
+        Command::new("init")
+            .about("Fill an empty tags file with example tags or the tags of a template.")
+            .arg(
+                Arg::new("from")
+                    .long("from")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .help("Use the tags in the JSON file, written as in the tags file."),
+            )
+            .arg(
+                Arg::new("force")
+                    .long("force")
+                    .help("Replace the tags even if the tags file already has some."),
+            ),
+        Command::new("lookup")
+            .about("List the tags that open a path or a URL.")
+            .arg(
+                Arg::new("path")
+                    .required(true)
+                    .value_name("PATH")
+                    .help(
+                        "The path or the URL. Tags opening a parent of it, such as its \
+                         directory, are listed as well.",
+                    ),
+            ),
+        Command::new("merge")
+            .about("Move the names and subtags of a tag into another tag.")
+            .arg(
+                Arg::new("source")
+                    .required(true)
+                    .value_name("SOURCE")
+                    .help("The tag to merge and remove, such as `web/github`."),
+            )
+            .arg(
+                Arg::new("destination")
+                    .required(true)
+                    .value_name("DESTINATION")
+                    .help("The tag to merge into."),
+            )
+            .arg(
+                Arg::new("rename-conflicts")
+                    .long("rename-conflicts")
+                    .help("Add a number to conflicting names instead of failing."),
+            ),
+        Command::new("pick")
+            .about("Pick a tag to open from all tags interactively.")
+            .arg(
+                Arg::new("query")
+                    .value_name("QUERY")
+                    .help("The query the picker starts with."),
+            ),
+        Command::new("recent")
+            .about("List the most recently added or modified tags.")
+            .arg(
+                Arg::new("count")
+                    .short('n')
+                    .takes_value(true)
+                    .value_name("N")
+                    .value_parser(clap::value_parser!(usize))
+                    .default_value("20")
+                    .help("The number of tags to list."),
+            ),
+        Command::new("reorder")
+            .about("Reorder tags interactively.")
+            .arg(
+                Arg::new("tag")
+                    .value_name("TAG")
+                    .help("The tag whose subtags are reordered, such as `web/github`."),
+            ),
+        Command::new("shell-init")
+            .about("Print shell functions to change to the directory of a tag and complete tags.")
+            .arg(
+                Arg::new("shell")
+                    .required(true)
+                    .value_name("SHELL")
+                    .possible_values(["bash", "zsh", "fish"])
+                    .help("The shell to print the functions for."),
+            )
+            .arg(
+                Arg::new("name")
+                    .long("name")
+                    .takes_value(true)
+                    .default_value("otcd")
+                    .help("The name of the function."),
+            ),
+        Command::new("sort")
+            .about("Sort tags alphabetically by their primary name.")
+            .arg(
+                Arg::new("recursive")
+                    .short('R')
+                    .long("recursive")
+                    .help("Sort the subtags of every tag as well."),
+            )
+            .arg(
+                Arg::new("tag")
+                    .value_name("TAG")
+                    .help("The tag whose subtags are sorted, such as `web/github`."),
+            ),
+        Command::new("stale").about("List the tags whose review date has come, oldest first."),
+        Command::new("stats").about("Summarize the tags and the tags file."),
+        Command::new("tag")
+            .about("Open a tag, including a tag named like a built-in command.")
+            .subcommand_required(true)
+            .disable_help_subcommand(true),
+        Command::new("sync")
+            .about("Push or pull the tags file to or from the configured backend.")
+            .subcommand_required(true)
+            .disable_help_subcommand(true)
+            .subcommands([
+                Command::new("push")
+                    .about("Upload the tags file.")
+                    .arg(
+                        Arg::new("force")
+                            .long("force")
+                            .help("Overwrite the remote tags even if they changed since the last sync."),
+                    ),
+                Command::new("pull")
+                    .about("Replace the tags file with the remote one.")
+                    .arg(
+                        Arg::new("force")
+                            .long("force")
+                            .help("Overwrite the local tags even if they changed since the last sync."),
+                    ),
+                Command::new("status")
+                    .about("Show whether the local and the remote tags changed since the last sync."),
+            ]),
+        Command::new("top").about("List the most opened tags.").arg(
+            Arg::new("count")
+                .short('n')
+                .takes_value(true)
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("10")
+                .help("The number of tags to list."),
+        ),
+        Command::new("trash")
+            .about("List, restore or empty the removed tags.")
+            .disable_help_subcommand(true)
+            .subcommands([
+                Command::new("list").about("List the removed tags, most recent first."),
+                Command::new("restore")
+                    .about("Restore a removed tag under its original parent.")
+                    .arg(
+                        Arg::new("entry")
+                            .value_name("ENTRY")
+                            .help(
+                                "The number of the tag in `ot trash list`, or its name. Defaults \
+                                 to the most recently removed tag.",
+                            ),
+                    ),
+                Command::new("empty").about("Permanently delete the removed tags."),
+            ]),
+        Command::new("undo")
+            .about("Undo the last change to the tags."),
+        Command::new("unused")
+            .about("List the tags that were never opened, or not recently.")
+            .arg(
+                Arg::new("since")
+                    .long("since")
+                    .takes_value(true)
+                    .value_name("AGE")
+                    .help(
+                        "List the tags not opened in this long, such as `90d`, `12w`, `6m`, or \
+                         `1y`, instead of those never opened. Tags added since then are not \
+                         listed.",
+                    ),
+            ),
+        Command::new("version")
+            .about("Print the version and build information.")
+            .arg(
+                Arg::new("details")
+                    .long("details")
+                    .help("Print the build information and the tags file details."),
+            )
+            .arg(
+                Arg::new("json")
+                    .long("json")
+                    .help("Print all information as JSON."),
+            ),
+    ]
+    .into_iter()
+    .map(|c| c.hide(true))
+    .collect()
 }
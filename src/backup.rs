@@ -0,0 +1,81 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::{Error, ErrorKind, Result};
+use crate::tag::{self, FileStamp};
+
+/// The number of backups kept of the tags file.
+pub const MAX_BACKUPS: usize = 10;
+
+/// Returns the path to the `n`th most recent backup of the tags file, kept
+/// next to it, starting at `1`.
+pub fn get_backup_path(tags_path: &Path, n: usize) -> PathBuf {
+    tags_path.with_extension(format!("backup.{}.json", n))
+}
+
+/// Backs up the tags file before it is changed.
+///
+/// The existing backups are shifted by one and the oldest is removed if there
+/// are [`MAX_BACKUPS`] of them. Nothing is done if the file does not exist.
+pub fn create(tags_path: &Path) -> Result<()> {
+    if !tags_path.exists() {
+        return Ok(());
+    }
+
+    let error = |e: std::io::Error| {
+        Error::new(
+            ErrorKind::Io,
+            format!(
+                "unable to back up the tags file at path `{}`: {}",
+                tags_path.display(),
+                e
+            ),
+        )
+    };
+
+    for n in (1..MAX_BACKUPS).rev() {
+        let path = get_backup_path(tags_path, n);
+        if path.exists() {
+            fs::rename(&path, get_backup_path(tags_path, n + 1)).map_err(error)?;
+        }
+    }
+    fs::copy(tags_path, get_backup_path(tags_path, 1)).map_err(error)?;
+
+    Ok(())
+}
+
+/// Returns the paths to the backups of the tags file that exist, most recent
+/// first.
+pub fn list(tags_path: &Path) -> Vec<PathBuf> {
+    (1..=MAX_BACKUPS)
+        .map(|n| get_backup_path(tags_path, n))
+        .take_while(|p| p.exists())
+        .collect()
+}
+
+/// Restores the tags file from its most recent backup, which is removed so
+/// that the next restore goes back one more change.
+///
+/// `loaded` is the stamp of the tags file when the tags were loaded. Nothing
+/// is restored if the file changed since then, as with
+/// [`write_tags`](crate::tag::write_tags).
+pub fn restore(tags_path: &Path, loaded: Option<FileStamp>) -> Result<()> {
+    let latest = get_backup_path(tags_path, 1);
+    if !latest.exists() {
+        return Err(Error::new(ErrorKind::NotFound, "there is nothing to undo"));
+    }
+    if FileStamp::of(tags_path) != loaded {
+        return Err(tag::changed_error(tags_path));
+    }
+
+    fs::rename(&latest, tags_path)?;
+    for n in 2..=MAX_BACKUPS {
+        let path = get_backup_path(tags_path, n);
+        if !path.exists() {
+            break;
+        }
+        fs::rename(&path, get_backup_path(tags_path, n - 1))?;
+    }
+
+    Ok(())
+}
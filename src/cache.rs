@@ -0,0 +1,301 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use crate::error::Result;
+use crate::tag::{App, Tag, TagPath, Tags};
+
+/// Identifies the format of the cache file. Changing the format requires a
+/// new value so that old caches are ignored.
+const MAGIC: &[u8] = b"OTCACH15";
+
+/// Returns the path to the cache of the parsed tags, if there is a cache
+/// directory.
+pub fn get_cache_path() -> Option<PathBuf> {
+    dirs_next::cache_dir().map(|d| d.join("opentag/tags.cache"))
+}
+
+/// Returns the cached tags of the tags file at `path`, or `None` if there is
+/// no cache or if the file changed since the cache was written.
+///
+/// The file is considered changed if its path, its size, or its modification
+/// time differ from when the cache was written.
+pub fn read(path: &Path) -> Option<Tags> {
+    let key = cache_key(path)?;
+    let bytes = fs::read(get_cache_path()?).ok()?;
+
+    let mut reader = Reader { bytes: &bytes };
+    if reader.take(MAGIC.len())? != MAGIC || reader.take(key.len())? != key {
+        return None;
+    }
+    let tags = reader.tags()?;
+
+    reader.bytes.is_empty().then_some(tags)
+}
+
+/// Writes the tags of the tags file at `path` to the cache.
+pub fn write(path: &Path, tags: &[Tag]) -> Result<()> {
+    let (cache_path, key) = match (get_cache_path(), cache_key(path)) {
+        (Some(cache_path), Some(key)) => (cache_path, key),
+        _ => return Ok(()),
+    };
+
+    let mut bytes = Vec::new();
+    bytes.extend(MAGIC);
+    bytes.extend(key);
+    write_tags(&mut bytes, tags);
+
+    if let Some(parent) = cache_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(cache_path, bytes)?;
+
+    Ok(())
+}
+
+/// Returns the bytes identifying the current state of the tags file: its
+/// canonical path, its size, and its modification time.
+fn cache_key(path: &Path) -> Option<Vec<u8>> {
+    let path = fs::canonicalize(path).ok()?;
+    let metadata = fs::metadata(&path).ok()?;
+    let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+
+    let mut key = Vec::new();
+    write_str(&mut key, path.to_str()?);
+    key.extend(metadata.len().to_le_bytes());
+    key.extend(modified.as_nanos().to_le_bytes());
+    Some(key)
+}
+
+fn write_tags(bytes: &mut Vec<u8>, tags: &[Tag]) {
+    bytes.extend((tags.len() as u32).to_le_bytes());
+    for tag in tags {
+        // destructured so that new fields cannot be forgotten here
+        let Tag {
+            names,
+            path,
+            fallback_paths,
+            query_param,
+            icon,
+            color,
+            about,
+            notes,
+            secret,
+            app,
+            background,
+            cwd,
+            env,
+            labels,
+            review_after,
+            pinned,
+            created,
+            modified,
+            extra,
+            subtags,
+        } = tag;
+
+        write_strs(bytes, names);
+        write_option(bytes, path.as_ref(), write_tag_path);
+        write_strs(bytes, fallback_paths);
+        for field in [query_param, icon, color, about, notes, secret] {
+            write_option(bytes, field.as_deref(), write_str);
+        }
+        write_option(bytes, app.as_ref(), write_app);
+        write_option(bytes, *background, |b, v| b.push(v as u8));
+        write_option(bytes, cwd.as_deref(), write_str);
+        let env = env.iter().flat_map(|(k, v)| [k.clone(), v.clone()]);
+        write_strs(bytes, &env.collect::<Vec<_>>());
+        write_strs(bytes, labels);
+        write_option(bytes, review_after.as_deref(), write_str);
+        bytes.push(*pinned as u8);
+        for field in [created, modified] {
+            write_option(bytes, *field, |b, v| b.extend(v.to_le_bytes()));
+        }
+        let extra = extra.iter().flat_map(|(k, v)| [k.clone(), v.to_string()]);
+        write_strs(bytes, &extra.collect::<Vec<_>>());
+        write_tags(bytes, subtags);
+    }
+}
+
+fn write_str(bytes: &mut Vec<u8>, s: &str) {
+    bytes.extend((s.len() as u32).to_le_bytes());
+    bytes.extend(s.as_bytes());
+}
+
+fn write_strs(bytes: &mut Vec<u8>, strs: &[String]) {
+    bytes.extend((strs.len() as u32).to_le_bytes());
+    for s in strs {
+        write_str(bytes, s);
+    }
+}
+
+fn write_tag_path(bytes: &mut Vec<u8>, path: &TagPath) {
+    match path {
+        TagPath::Any(path) => {
+            bytes.push(0);
+            write_str(bytes, path);
+        },
+        TagPath::PerOs(paths) => {
+            bytes.push(1);
+            let paths = paths.iter().flat_map(|(k, v)| [k.clone(), v.clone()]);
+            write_strs(bytes, &paths.collect::<Vec<_>>());
+        },
+    }
+}
+
+fn write_app(bytes: &mut Vec<u8>, app: &App) {
+    match app {
+        App::Name(name) => {
+            bytes.push(0);
+            write_str(bytes, name);
+        },
+        App::Command(command) => {
+            bytes.push(1);
+            write_strs(bytes, command);
+        },
+        App::PerOs(apps) => {
+            bytes.push(2);
+            bytes.extend((apps.len() as u32).to_le_bytes());
+            for (os, app) in apps {
+                write_str(bytes, os);
+                write_app(bytes, app);
+            }
+        },
+    }
+}
+
+fn write_option<T, F: Fn(&mut Vec<u8>, T)>(bytes: &mut Vec<u8>, value: Option<T>, write: F) {
+    match value {
+        Some(value) => {
+            bytes.push(1);
+            write(bytes, value);
+        },
+        None => bytes.push(0),
+    }
+}
+
+/// Reads the values written by the `write_*` functions.
+///
+/// Every method returns `None` if the bytes are malformed.
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if len > self.bytes.len() {
+            return None;
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Some(taken)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.take(1).map(|b| b[0])
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.take(4)
+            .map(|b| u32::from_le_bytes(b.try_into().expect("expected 4 bytes")))
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        self.take(8)
+            .map(|b| u64::from_le_bytes(b.try_into().expect("expected 8 bytes")))
+    }
+
+    fn bool(&mut self) -> Option<bool> {
+        match self.u8()? {
+            0 => Some(false),
+            1 => Some(true),
+            _ => None,
+        }
+    }
+
+    fn string(&mut self) -> Option<String> {
+        let len = self.u32()? as usize;
+        String::from_utf8(self.take(len)?.to_vec()).ok()
+    }
+
+    fn strings(&mut self) -> Option<Vec<String>> {
+        let len = self.u32()? as usize;
+        (0..len).map(|_| self.string()).collect()
+    }
+
+    fn tag_path(&mut self) -> Option<TagPath> {
+        if self.bool()? {
+            let paths = self.strings()?;
+            Some(TagPath::PerOs(
+                paths
+                    .chunks_exact(2)
+                    .map(|kv| (kv[0].clone(), kv[1].clone()))
+                    .collect(),
+            ))
+        } else {
+            self.string().map(TagPath::Any)
+        }
+    }
+
+    fn app(&mut self) -> Option<App> {
+        match self.u8()? {
+            0 => self.string().map(App::Name),
+            1 => self.strings().map(App::Command),
+            2 => {
+                let len = self.u32()? as usize;
+                (0..len)
+                    .map(|_| Some((self.string()?, self.app()?)))
+                    .collect::<Option<_>>()
+                    .map(App::PerOs)
+            },
+            _ => None,
+        }
+    }
+
+    fn option<T, F: FnOnce(&mut Self) -> Option<T>>(&mut self, read: F) -> Option<Option<T>> {
+        if self.bool()? {
+            read(self).map(Some)
+        } else {
+            Some(None)
+        }
+    }
+
+    fn tags(&mut self) -> Option<Tags> {
+        let len = self.u32()? as usize;
+        let mut tags = Vec::with_capacity(len.min(self.bytes.len()));
+        for _ in 0..len {
+            tags.push(Tag {
+                names: self.strings()?,
+                path: self.option(Self::tag_path)?,
+                fallback_paths: self.strings()?,
+                query_param: self.option(Self::string)?,
+                icon: self.option(Self::string)?,
+                color: self.option(Self::string)?,
+                about: self.option(Self::string)?,
+                notes: self.option(Self::string)?,
+                secret: self.option(Self::string)?,
+                app: self.option(Self::app)?,
+                background: self.option(Self::bool)?,
+                cwd: self.option(Self::string)?,
+                env: self
+                    .strings()?
+                    .chunks_exact(2)
+                    .map(|kv| (kv[0].clone(), kv[1].clone()))
+                    .collect(),
+                labels: self.strings()?,
+                review_after: self.option(Self::string)?,
+                pinned: self.bool()?,
+                created: self.option(Self::u64)?,
+                modified: self.option(Self::u64)?,
+                extra: self
+                    .strings()?
+                    .chunks_exact(2)
+                    .map(|kv| Some((kv[0].clone(), serde_json::from_str(&kv[1]).ok()?)))
+                    .collect::<Option<_>>()?,
+                subtags: self.tags()?,
+            });
+        }
+
+        Some(tags)
+    }
+}
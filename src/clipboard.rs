@@ -0,0 +1,110 @@
+use std::env;
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+
+use arboard::Clipboard;
+use serde::Deserialize;
+
+use crate::error::{Error, ErrorKind, Result};
+
+/// The clipboard that text is copied to.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ClipboardKind {
+    /// The clipboard of the system, accessed directly.
+    #[default]
+    System,
+    /// The clipboard of the terminal, set with the OSC 52 escape sequence.
+    ///
+    /// Works over SSH if the local terminal supports it.
+    Osc52,
+    /// The primary selection, pasted with the middle mouse button.
+    ///
+    /// Only supported on Linux and BSD.
+    Primary,
+}
+
+/// Copies the text to the clipboard.
+pub fn copy(text: String, kind: ClipboardKind) -> Result<()> {
+    match kind {
+        ClipboardKind::System => {
+            let mut clipboard = Clipboard::new()?;
+            clipboard.set_text(text)?;
+        },
+        ClipboardKind::Osc52 => {
+            copy_osc52(&text).map_err(|e| Error::new(ErrorKind::Clipboard, e.to_string()))?
+        },
+        ClipboardKind::Primary => copy_primary(text)?,
+    }
+
+    Ok(())
+}
+
+/// Returns the text in the system's clipboard.
+pub fn paste() -> Result<String> {
+    let mut clipboard = Clipboard::new()?;
+    Ok(clipboard.get_text()?)
+}
+
+/// Copies the text to the primary selection.
+#[cfg(all(
+    unix,
+    not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))
+))]
+fn copy_primary(text: String) -> Result<()> {
+    use arboard::{ClipboardExtLinux, LinuxClipboardKind};
+
+    let mut clipboard = Clipboard::new()?;
+    clipboard.set_text_with_clipboard(text, LinuxClipboardKind::Primary)?;
+    Ok(())
+}
+
+/// Copies the text to the primary selection.
+#[cfg(not(all(
+    unix,
+    not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))
+)))]
+fn copy_primary(_text: String) -> Result<()> {
+    Err(Error::new(
+        ErrorKind::Clipboard,
+        "the primary selection is only supported on Linux and BSD",
+    ))
+}
+
+/// Copies the text by writing the OSC 52 escape sequence to the terminal.
+///
+/// The sequence is written to the controlling terminal if there is one so
+/// that it is not captured with the output, or to `stderr` otherwise. It is
+/// wrapped in a passthrough sequence inside `tmux`.
+fn copy_osc52(text: &str) -> io::Result<()> {
+    let mut sequence = format!("\x1b]52;c;{}\x07", base64(text.as_bytes()));
+    if env::var_os("TMUX").is_some() {
+        sequence = format!("\x1bPtmux;\x1b{}\x1b\\", sequence);
+    }
+
+    match OpenOptions::new().write(true).open("/dev/tty") {
+        Ok(mut tty) => tty.write_all(sequence.as_bytes()),
+        Err(_) => io::stderr().write_all(sequence.as_bytes()),
+    }
+}
+
+/// Encodes the bytes with the standard, padded base64 alphabet.
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
@@ -0,0 +1,145 @@
+use std::env;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use console::{Color, Style};
+
+use crate::app;
+
+/// When the output is colored.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum ColorChoice {
+    /// Color the output if it is written to a terminal.
+    Auto,
+    /// Always color the output.
+    Always,
+    /// Never color the output.
+    Never,
+}
+
+/// The color choice of the invocation.
+static CHOICE: AtomicU8 = AtomicU8::new(ColorChoice::Auto as u8);
+
+/// Sets the color choice of the invocation.
+///
+/// The choice is read from the `--color` argument directly, so that it also
+/// applies to errors raised before the arguments are parsed. Without it,
+/// colors are disabled if `NO_COLOR` is set.
+pub fn init() {
+    let choice = match app::raw_option_value("color").as_deref() {
+        Some("always") => ColorChoice::Always,
+        Some("never") => ColorChoice::Never,
+        _ if env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()) => ColorChoice::Never,
+        _ => ColorChoice::Auto,
+    };
+    CHOICE.store(choice as u8, Ordering::Relaxed);
+
+    console::set_colors_enabled(enabled(console::user_attended()));
+    console::set_colors_enabled_stderr(enabled(console::user_attended_stderr()));
+}
+
+/// Returns the color choice of the invocation.
+pub fn choice() -> ColorChoice {
+    match CHOICE.load(Ordering::Relaxed) {
+        1 => ColorChoice::Always,
+        2 => ColorChoice::Never,
+        _ => ColorChoice::Auto,
+    }
+}
+
+/// Returns `true` if output written to a stream is colored, given whether the
+/// stream is a terminal.
+pub fn enabled(is_terminal: bool) -> bool {
+    match choice() {
+        ColorChoice::Auto => is_terminal,
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+    }
+}
+
+/// Returns the color choice for `clap`.
+pub fn clap_choice() -> clap::ColorChoice {
+    match choice() {
+        ColorChoice::Auto => clap::ColorChoice::Auto,
+        ColorChoice::Always => clap::ColorChoice::Always,
+        ColorChoice::Never => clap::ColorChoice::Never,
+    }
+}
+
+/// Returns the style of a tag shown in the color.
+///
+/// The color is either the name of an ANSI color, such as `red` or
+/// `bright-blue`, or a hex color such as `#ff8800`, shown as the closest of the
+/// 256 terminal colors. The style is plain if the color is not recognized.
+pub fn tag_style(color: Option<&str>) -> Style {
+    let color = match color {
+        Some(color) => color.trim().to_lowercase(),
+        None => return Style::new(),
+    };
+
+    if let Some(hex) = color.strip_prefix('#') {
+        return match parse_hex(hex) {
+            Some(rgb) => Style::new().color256(rgb_to_256(rgb)),
+            None => Style::new(),
+        };
+    }
+
+    let (name, bright) = match color.strip_prefix("bright-") {
+        Some(name) => (name, true),
+        None => (color.as_str(), false),
+    };
+    let color = match name {
+        "black" => Color::Black,
+        "red" => Color::Red,
+        "green" => Color::Green,
+        "yellow" => Color::Yellow,
+        "blue" => Color::Blue,
+        "magenta" => Color::Magenta,
+        "cyan" => Color::Cyan,
+        "white" => Color::White,
+        _ => return Style::new(),
+    };
+
+    let style = Style::new().fg(color);
+    if bright {
+        style.bright()
+    } else {
+        style
+    }
+}
+
+/// Parses a hex color such as `ff8800` or `f80`.
+fn parse_hex(hex: &str) -> Option<(u8, u8, u8)> {
+    let channel = |s: &str| u8::from_str_radix(s, 16).ok();
+    match hex.len() {
+        6 => Some((
+            channel(&hex[0..2])?,
+            channel(&hex[2..4])?,
+            channel(&hex[4..6])?,
+        )),
+        3 => {
+            let (r, g, b) = (
+                channel(&hex[0..1])?,
+                channel(&hex[1..2])?,
+                channel(&hex[2..3])?,
+            );
+            Some((r * 17, g * 17, b * 17))
+        },
+        _ => None,
+    }
+}
+
+/// Returns the closest color in the 6x6x6 cube of the 256 terminal colors.
+fn rgb_to_256((r, g, b): (u8, u8, u8)) -> u8 {
+    // the levels of the cube are 0, 95, 135, 175, 215, and 255
+    let level = |c: u8| {
+        if c < 48 {
+            0
+        } else if c < 115 {
+            1
+        } else {
+            (c - 35) / 40
+        }
+    };
+    16 + 36 * level(r) + 6 * level(g) + level(b)
+}
@@ -1,95 +1,3162 @@
-use arboard::Clipboard;
-use clap::{ArgMatches, Command};
+use std::borrow::Cow;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashSet};
+use std::ffi::OsString;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+use std::time::{Duration, Instant};
+use std::{env, fs, io, thread};
+
+use clap::ArgMatches;
+use console::Style;
 use dialoguer::theme::ColorfulTheme;
-use dialoguer::{Editor, FuzzySelect, Input};
+use dialoguer::{Confirm, Editor, Input, MultiSelect, Sort};
 use itertools::Itertools;
+use regex::RegexBuilder;
+use serde::Serialize;
+
+use crate::clipboard::{self, ClipboardKind};
+use crate::config::{Config, Defaults, GroupBy, SortOrder, Uniqueness};
+use crate::error::{Error, ErrorKind, Result};
+use crate::history::{self, HistoryEntry};
+use crate::opener::OpenOptions;
+use crate::picker::Picker;
+use crate::progress::{self, Progress};
+use crate::tag::{self, App, FileStamp, TagPath, Tags};
+use crate::{
+    analysis,
+    app,
+    backup,
+    color,
+    import,
+    links,
+    opener,
+    ops,
+    output,
+    secret,
+    timings,
+    trash,
+    usage,
+    Tag,
+};
+
+/// Restricts which tags are listed.
+#[derive(Clone, Debug, Default)]
+pub struct ListFilter<'a> {
+    /// Only tags with a name containing this are listed.
+    pub name: Option<&'a str>,
+    /// Only tags with a path containing this are listed.
+    pub path: Option<&'a str>,
+    /// Only tags with (or without) a path are listed.
+    pub has_path: Option<bool>,
+    /// Only tags with all of these labels are listed.
+    pub labels: Vec<&'a str>,
+}
+
+impl ListFilter<'_> {
+    /// Returns `true` if the tag itself passes the filter.
+    pub fn matches(&self, tag: &Tag) -> bool {
+        let name_matches = self.name.is_none_or(|f| {
+            let f = f.to_lowercase();
+            tag.names.iter().any(|n| n.to_lowercase().contains(&f))
+        });
+        let path_matches = self.path.is_none_or(|f| {
+            tag.path()
+                .is_some_and(|p| p.to_lowercase().contains(&f.to_lowercase()))
+        });
+        let has_path_matches = self
+            .has_path
+            .is_none_or(|h| (tag.path().is_some() || tag.secret.is_some()) == h);
+
+        let labels_match = self
+            .labels
+            .iter()
+            .all(|l| tag.labels.iter().any(|t| t.eq_ignore_ascii_case(l)));
+
+        name_matches && path_matches && has_path_matches && labels_match
+    }
+
+    /// Returns `true` if the tag or any of its subtags pass the filter.
+    pub fn matches_recursive(&self, tag: &Tag) -> bool {
+        self.matches(tag) || tag.subtags.iter().any(|t| self.matches_recursive(t))
+    }
+}
+
+/// The kinds of tags in the order they are sorted and grouped in.
+const KINDS: [&str; 5] = ["directory", "file", "url", "secret", "none"];
+
+/// Lists the given tags, the subtags of the tag with the `parent` names,
+/// along with their aliases and descriptions.
+///
+/// Tags are listed if they or any of their subtags pass the filter. Subtags
+/// are listed below their parent tag, up to the requested depth, unless the
+/// tags are grouped, in which case each is listed with its path of names in
+/// each of its groups.
+pub fn list(
+    tags: &[Tag],
+    parent: &[String],
+    options: &MatchOptions,
+    config: &Config,
+) -> Result<()> {
+    let max_depth = match options.depth {
+        Some(depth) => depth,
+        None if options.recursive => usize::MAX,
+        None => 1,
+    };
+
+    let mut rows = Vec::new();
+    let mut names = parent.to_vec();
+    list_rows(tags, options, config, &mut names, 0, max_depth, &mut rows);
+
+    if options.count {
+        println!("{}", rows.len());
+        return Ok(());
+    }
+    if rows.is_empty() {
+        output::status("No tags!");
+        return Ok(());
+    }
+
+    let mut text = String::new();
+    if let Some(format) = options.format {
+        for (_, tag) in rows {
+            writeln!(text, "{}", output::format_tag(format, tag))?;
+        }
+        return output::page(&text, options.no_pager);
+    }
+
+    // grouped tags are listed with the names of their parents, if any
+    let relative = |names: &[String], tag: &Tag| {
+        let mut name = names[parent.len()..names.len() - 1]
+            .iter()
+            .map(|n| format!("{}/", n))
+            .collect::<String>();
+        name.push_str(&tag::display_name(tag));
+        name
+    };
+    match options.group_by {
+        Some(group_by) => {
+            let mut groups = BTreeMap::<_, Vec<_>>::new();
+            for (names, tag) in &rows {
+                let keys = match group_by {
+                    "kind" => {
+                        let kind = list_kind(tag, config);
+                        let rank = KINDS.iter().position(|&k| k == kind).unwrap_or_default();
+                        vec![(rank, kind.to_string())]
+                    },
+                    _ if tag.labels.is_empty() => vec![(1, String::new())],
+                    _ => tag.labels.iter().map(|l| (0, l.clone())).collect(),
+                };
+                for key in keys {
+                    groups
+                        .entry(key)
+                        .or_default()
+                        .push((relative(names, tag), *tag));
+                }
+            }
+
+            for (i, ((_, key), rows)) in groups.into_iter().enumerate() {
+                if i > 0 {
+                    writeln!(text)?;
+                }
+                let heading = match (group_by, key.as_str()) {
+                    ("kind", "directory") => "DIRECTORIES".to_string(),
+                    ("kind", "none") => "NO PATH".to_string(),
+                    ("kind", kind) => format!("{}S", kind.to_uppercase()),
+                    (_, "") => "NO LABEL".to_string(),
+                    (_, label) => label.to_uppercase(),
+                };
+                write_rows(&mut text, &heading, rows)?;
+            }
+        },
+        None => {
+            let rows = rows
+                .into_iter()
+                .map(|(names, tag)| {
+                    let level = names.len() - parent.len() - 1;
+                    (
+                        format!("{}{}", "    ".repeat(level), tag::display_name(tag)),
+                        tag,
+                    )
+                })
+                .collect();
+            write_rows(&mut text, "TAGS", rows)?;
+        },
+    }
+
+    output::page(&text, options.no_pager)
+}
+
+/// Writes the heading and the rows of tags, each a name followed by the
+/// description, aliases, and labels of the tag.
+fn write_rows(text: &mut String, heading: &str, rows: Vec<(String, &Tag)>) -> Result<()> {
+    // icons such as emoji take more than one column
+    let width = rows
+        .iter()
+        .map(|(n, _)| console::measure_text_width(n))
+        .max()
+        .unwrap_or(0);
+
+    writeln!(text, "{}", heading)?;
+    for (name, tag) in rows {
+        let mut about = match tag.about.as_deref().and_then(|a| a.lines().next()) {
+            Some(about) => about.to_string(),
+            None if tag.secret.is_some() => "(secret)".to_string(),
+            None => String::new(),
+        };
+        if tag.names.len() > 1 {
+            if !about.is_empty() {
+                about.push(' ');
+            }
+            about.push_str(&format!("[aliases: {}]", tag.names[1..].join(", ")));
+        }
+        if !tag.labels.is_empty() {
+            if !about.is_empty() {
+                about.push(' ');
+            }
+            about.push_str(&format!("[labels: {}]", tag.labels.join(", ")));
+        }
+
+        let name = color::tag_style(tag.color.as_deref())
+            .apply_to(name)
+            .to_string();
+        let name = console::pad_str(&name, width, console::Alignment::Left, None);
+        let line = format!("    {}    {}", name, about);
+        writeln!(text, "{}", line.trim_end())?;
+    }
+    Ok(())
+}
+
+/// Collects the tags to list along with their paths of names, which start
+/// with `names`, in the order given with `--sort` at each level, pinned tags
+/// first.
+fn list_rows<'a>(
+    tags: &'a [Tag],
+    options: &MatchOptions,
+    config: &Config,
+    names: &mut Vec<String>,
+    level: usize,
+    max_depth: usize,
+    rows: &mut Vec<(Vec<String>, &'a Tag)>,
+) {
+    if level >= max_depth {
+        return;
+    }
+
+    let mut level_tags = tags
+        .iter()
+        .filter(|t| !t.names.is_empty() && options.filter.matches_recursive(t))
+        .collect::<Vec<_>>();
+    match options.sort {
+        Some("name") => level_tags.sort_by_cached_key(|t| t.names[0].to_lowercase()),
+        Some("recent") => level_tags.sort_by_key(|t| Reverse(t.last_changed())),
+        Some("usage") => level_tags.sort_by_cached_key(|t| {
+            names.push(t.names[0].clone());
+            let count = usage::count(&options.usage, &names.join("/"));
+            names.pop();
+            Reverse(count)
+        }),
+        Some("kind") => level_tags.sort_by_cached_key(|t| {
+            let kind = list_kind(t, config);
+            KINDS.iter().position(|&k| k == kind)
+        }),
+        _ => {},
+    }
+    level_tags.sort_by_key(|t| !t.pinned);
+
+    for tag in level_tags {
+        names.push(tag.names[0].clone());
+        rows.push((names.clone(), tag));
+        list_rows(
+            &tag.subtags,
+            options,
+            config,
+            names,
+            level + 1,
+            max_depth,
+            rows,
+        );
+        names.pop();
+    }
+}
+
+/// Returns the kind of the tag to sort or group it by, as with `--info`.
+fn list_kind(tag: &Tag, config: &Config) -> &'static str {
+    let resolved = tag
+        .path()
+        .map(|p| analysis::resolve_path(p, config.root.as_deref()));
+    analysis::tag_kind(tag, resolved.as_deref())
+}
+
+/// Runs the alias command, which adds an alias to a tag or removes one.
+///
+/// An alias cannot be added if a tag at the same level already has the name,
+/// and the primary name of a tag cannot be removed.
+pub fn alias(tags: &mut Tags, matches: &ArgMatches) -> Result<()> {
+    let (action, sub_matches) = matches.subcommand().expect("subcommand is required");
+    let tag_path = sub_matches.value_of("tag").expect("required");
+    let names = tag::split_tag_path(tag_path);
+    let alias = tag::normalize_name(sub_matches.value_of("alias").expect("required").trim());
+    tag::check_name(&alias)?;
+
+    let (name, parents) = names
+        .split_last()
+        .ok_or_else(|| Error::new(ErrorKind::NotFound, "no tag found"))?;
+    let siblings = tag::find_subtags_by_names_mut(tags, parents)
+        .ok_or_else(|| Error::new(ErrorKind::NotFound, "no tag found"))?;
+    let i = siblings
+        .iter()
+        .position(|t| t.has_name(name))
+        .ok_or_else(|| Error::new(ErrorKind::NotFound, "no tag found"))?;
+
+    match action {
+        "add" => {
+            if siblings[i].has_name(&alias) {
+                return Err(
+                    format!("`{}` is already a name of the tag `{}`", alias, tag_path).into(),
+                );
+            }
+            if siblings.iter().any(|t| t.has_name(&alias)) {
+                return Err(format!("a tag with name `{}` already exists", alias).into());
+            }
+            siblings[i].names.push(alias.clone());
+            output::status(&format!("Added the alias `{}` to `{}`.", alias, tag_path));
+        },
+        "remove" => {
+            let tag = &mut siblings[i];
+            match tag.names.iter().position(|n| *n == alias) {
+                Some(0) => {
+                    return Err(format!(
+                        "`{}` is the primary name of the tag, not an alias; rename the tag with \
+                         `--update` instead",
+                        alias
+                    )
+                    .into())
+                },
+                Some(j) => {
+                    tag.names.remove(j);
+                },
+                None => {
+                    return Err(Error::new(
+                        ErrorKind::NotFound,
+                        format!("the tag `{}` has no alias `{}`", tag_path, alias),
+                    ))
+                },
+            }
+            output::status(&format!("Removed the alias `{}` of `{}`.", alias, tag_path));
+        },
+        _ => unreachable!("unhandled alias command `{}`", action),
+    }
+    siblings[i].modified = Some(usage::now());
+
+    Ok(())
+}
+
+/// Runs the aliases command.
+///
+/// Aliases are only printed for tags with a path or a URL, and with names that
+/// can be used in an alias.
+pub fn aliases(tags: &[Tag], matches: &ArgMatches) {
+    let prefix = matches.value_of("prefix").unwrap_or_default();
+    let separator = matches.value_of("separator").unwrap_or_default();
+    let recursive = matches.contains_id("recursive");
+
+    analysis::walk(tags, |names, tag| {
+        if tag.path().is_none() || (!recursive && names.len() > 1) || app::is_builtin(&names[0]) {
+            return;
+        }
+
+        let alias = format!("{}{}", prefix, names.join(separator));
+        if alias.is_empty() || alias.contains('/') || !is_shell_word(&alias) {
+            return;
+        }
+
+        let command = names
+            .iter()
+            .map(|n| {
+                if is_shell_word(n) {
+                    n.to_string()
+                } else {
+                    output::shell_quote(n)
+                }
+            })
+            .join(" ");
+        println!(
+            "alias {}={}",
+            alias,
+            output::shell_quote(&format!("ot {}", command))
+        );
+    });
+}
+
+/// Returns `true` if the text does not need to be quoted in the shell.
+fn is_shell_word(text: &str) -> bool {
+    text.chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | '/'))
+}
+
+/// Runs the diff command.
+///
+/// The tags are compared to the backup or the file given in the matches, or
+/// to the most recent backup. Added, removed, moved, and changed tags are
+/// printed.
+pub fn diff(tags: &[Tag], path: &Path, matches: &ArgMatches) -> Result<()> {
+    let other = match matches.value_of("file") {
+        Some(file) => PathBuf::from(analysis::expand_path(file).as_ref()),
+        None => {
+            let n = matches.get_one::<usize>("backup").copied().unwrap_or(1);
+            let backup = backup::get_backup_path(path, n);
+            if n == 0 || !backup.exists() {
+                return Err(Error::new(
+                    ErrorKind::NotFound,
+                    match backup::list(path).len() {
+                        0 => "there are no backups yet".to_string(),
+                        len => format!("there are only {} backups", len),
+                    },
+                ));
+            }
+            backup
+        },
+    };
+    let old = tag::get_tags(&other)?;
+
+    let changes = analysis::diff(&old, tags);
+    if changes.is_empty() {
+        output::status(&format!("No changes since `{}`.", other.display()));
+        return Ok(());
+    }
+
+    print_changes(changes);
+    Ok(())
+}
+
+/// Runs the undo command.
+///
+/// The tags file is restored from its most recent backup, and the changes
+/// that were undone are printed. In a dry run, they are only printed.
+pub fn undo(
+    tags: &[Tag],
+    path: &Path,
+    stamp: Option<FileStamp>,
+    matches: &ArgMatches,
+) -> Result<()> {
+    let latest = backup::get_backup_path(path, 1);
+    if !latest.exists() {
+        return Err(Error::new(ErrorKind::NotFound, "there is nothing to undo"));
+    }
+    let changes = analysis::diff(tags, &tag::get_tags(&latest)?);
+
+    if matches.contains_id("dry-run") {
+        println!(
+            "Would restore `{}` from `{}`.",
+            path.display(),
+            latest.display()
+        );
+    } else {
+        backup::restore(path, stamp)?;
+    }
+    print_changes(changes);
+
+    Ok(())
+}
+
+/// Prints the changes between two versions of the tags.
+fn print_changes(changes: Vec<analysis::Change>) {
+    let (added, removed, moved, changed) = (
+        Style::new().green(),
+        Style::new().red(),
+        Style::new().cyan(),
+        Style::new().yellow(),
+    );
+    for change in changes {
+        match change {
+            analysis::Change::Added { names, path } => {
+                let line = format!("+ {}    {}", names.join("/"), path.unwrap_or_default());
+                println!("{}", added.apply_to(line.trim_end()));
+            },
+            analysis::Change::Removed { names, path } => {
+                let line = format!("- {}    {}", names.join("/"), path.unwrap_or_default());
+                println!("{}", removed.apply_to(line.trim_end()));
+            },
+            analysis::Change::Moved { from, to } => {
+                let line = format!("> {} -> {}", from.join("/"), to.join("/"));
+                println!("{}", moved.apply_to(line));
+            },
+            analysis::Change::Changed { names, fields } => {
+                println!("{}", changed.apply_to(format!("~ {}", names.join("/"))));
+                for (field, old, new) in fields {
+                    let value = |v: String| match v.lines().count() {
+                        0 => "(none)".to_string(),
+                        1 => v,
+                        n => format!("({} lines)", n),
+                    };
+                    println!("    {}: {} -> {}", field, value(old), value(new));
+                }
+            },
+        }
+    }
+}
+
+/// Runs the dupes command.
+pub fn dupes(tags: &[Tag], config: &Config) {
+    let duplicates = analysis::duplicate_paths(tags, config.root.as_deref());
+    if duplicates.is_empty() {
+        output::status("No duplicates!");
+        return;
+    }
+
+    for (i, (path, names)) in duplicates.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        println!("{}", path);
+        for chain in names {
+            println!("    {}", chain.join(" "));
+        }
+    }
+}
+
+/// Runs the info command.
+///
+/// The tags file and the config file are printed with where their paths came
+/// from, to tell which files `ot` uses.
+pub fn info(
+    tags: &[Tag],
+    all_tags: &[Tag],
+    path: &Path,
+    config: &Config,
+    matches: &ArgMatches,
+) -> Result<()> {
+    let data_source = if app::raw_option_value("data-file").is_some() {
+        "--data-file"
+    } else if env::var_os("OPENTAG_DATA").is_some() {
+        "$OPENTAG_DATA"
+    } else {
+        "default"
+    };
+    let config_path = crate::config::get_config_path()?;
+    let config_source = if env::var_os("OPENTAG_CONFIG").is_some() {
+        "$OPENTAG_CONFIG"
+    } else {
+        "default"
+    };
+    let stats = analysis::stats(tags);
+    let source_tags = analysis::stats(all_tags).tags - stats.tags;
+
+    if matches.contains_id("json") {
+        let info = serde_json::json!({
+            "version": env!("CARGO_PKG_VERSION"),
+            "data_file": path,
+            "data_file_source": data_source.trim_start_matches(['-', '$']).to_lowercase(),
+            "config_file": config_path,
+            "config_file_source": config_source.trim_start_matches('$').to_lowercase(),
+            "config_file_exists": config_path.exists(),
+            "tags": stats.tags,
+            "global_tags": tags.len(),
+            "extra_sources": config.extra_sources,
+            "extra_source_tags": source_tags,
+        });
+        println!("{}", serde_json::to_string_pretty(&info)?);
+        return Ok(());
+    }
+
+    println!("Version:          {}", env!("CARGO_PKG_VERSION"));
+    println!("Tags file:        {} ({})", path.display(), data_source);
+    println!(
+        "Config file:      {} ({}{})",
+        config_path.display(),
+        config_source,
+        if config_path.exists() {
+            ""
+        } else {
+            ", missing"
+        }
+    );
+    println!("Tags:             {}", stats.tags);
+    println!("Global tags:      {}", tags.len());
+    if !config.extra_sources.is_empty() {
+        println!(
+            "Extra sources:    {} ({} tags)",
+            config.extra_sources.len(),
+            source_tags
+        );
+    }
+
+    Ok(())
+}
+
+/// Prints the details of the tag with the `names`, with `--info`.
+///
+/// Secret tags are not decrypted, so their paths are not shown. How often the
+/// tag was opened is read from the usage file next to the tags file at
+/// `path`.
+pub fn tag_info(
+    tag: &Tag,
+    names: &[String],
+    ancestor_app: Option<&App>,
+    path: &Path,
+    options: &MatchOptions,
+    config: &Config,
+) -> Result<()> {
+    let tag_path = names.join("/");
+    let resolved = tag_target(tag, config.root.as_deref());
+    let kind = analysis::tag_kind(tag, resolved.as_deref());
+    // only files are checked, as URLs cannot be without opening them
+    let exists = resolved
+        .as_deref()
+        .filter(|_| matches!(kind, "file" | "directory"))
+        .map(|p| Path::new(p).exists());
+    let app = tag.app.as_ref().or(ancestor_app);
+    let usage = usage::get_usage(usage::get_usage_path(path)).unwrap_or_else(|e| {
+        log::warn!("unable to read the usage data: {}", e);
+        usage::Usage::new()
+    });
+    let entry = usage.get(&tag_path).cloned().unwrap_or_default();
+
+    if options.json {
+        let info = serde_json::json!({
+            "name": tag.names[0],
+            "aliases": tag.names[1..],
+            "tag": tag_path,
+            "kind": kind,
+            "path": tag.path(),
+            "resolved_path": resolved,
+            "exists": exists,
+            "query_param": tag.query_param,
+            "about": tag.about,
+            "notes": tag.notes,
+            "app": app,
+            "app_inherited": tag.app.is_none() && app.is_some(),
+            "labels": tag.labels,
+            "pinned": tag.pinned,
+            "review_after": tag.review_after,
+            "created": tag.created,
+            "modified": tag.modified,
+            "opened": entry.count,
+            "last_opened": Some(entry.last_opened).filter(|&t| t > 0),
+            "subtags": tag.subtags.len(),
+        });
+        println!("{}", serde_json::to_string_pretty(&info)?);
+        return Ok(());
+    }
+
+    let time = |t: Option<u64>| t.map_or_else(|| "-".to_string(), analysis::format_time);
+    println!("Name:           {}", tag.names[0]);
+    if tag.names.len() > 1 {
+        println!("Aliases:        {}", tag.names[1..].join(", "));
+    }
+    println!("Tag:            {}", tag_path);
+    println!("Kind:           {}", kind);
+    if let Some(raw) = tag.path() {
+        println!("Path:           {}", raw);
+    }
+    if let Some(ref resolved) = resolved {
+        match exists {
+            Some(false) => println!("Resolved path:  {} (missing)", resolved),
+            _ => println!("Resolved path:  {}", resolved),
+        }
+    }
+    if let Some(ref query_param) = tag.query_param {
+        println!("Query param:    {}", query_param);
+    }
+    if let Some(ref about) = tag.about {
+        println!("About:          {}", about);
+    }
+    if let Some(app) = app {
+        match tag.app {
+            Some(_) => println!("App:            {}", app),
+            None => println!("App:            {} (inherited)", app),
+        }
+    }
+    if !tag.labels.is_empty() {
+        println!("Labels:         {}", tag.labels.join(", "));
+    }
+    if tag.pinned {
+        println!("Pinned:         yes");
+    }
+    if let Some(ref review_after) = tag.review_after {
+        println!("Review after:   {}", review_after);
+    }
+    println!("Created:        {}", time(tag.created));
+    println!("Modified:       {}", time(tag.modified));
+    println!(
+        "Opened:         {} ({})",
+        match entry.count {
+            1 => "1 time".to_string(),
+            n => format!("{} times", n),
+        },
+        match entry.last_opened {
+            0 => "never".to_string(),
+            t => format!("last {}", analysis::format_time(t)),
+        }
+    );
+    println!("Subtags:        {}", tag.subtags.len());
+    if let Some(ref notes) = tag.notes {
+        println!("\n{}", notes.trim_end());
+    }
+
+    Ok(())
+}
+
+/// The tags written by the init command without `--from`, as examples to
+/// start from.
+const STARTER_TAGS: &str = r#"[
+  {
+    "names": ["home", "~"],
+    "path": "~",
+    "about": "The home directory."
+  },
+  {
+    "names": ["web"],
+    "about": "Websites, opened with `ot web <NAME>`.",
+    "subtags": [
+      {
+        "names": ["opentag", "ot"],
+        "path": "https://github.com/nextonesfaster/opentag",
+        "about": "The opentag repository."
+      },
+      {
+        "names": ["search"],
+        "path": "https://duckduckgo.com",
+        "query_param": "q",
+        "about": "Search the web with `ot web search <WORDS>`."
+      }
+    ]
+  }
+]"#;
+
+/// Runs the init command, which replaces the tags with example tags or the
+/// tags of a template file.
+///
+/// The tags are only replaced if there are none, unless `--force` is given.
+/// Returns the number of new top-level tags, to report once they are saved.
+pub fn init(tags: &mut Tags, path: &Path, matches: &ArgMatches) -> Result<usize> {
+    if !tags.is_empty() && !matches.contains_id("force") {
+        return Err(Error::new(
+            ErrorKind::Usage,
+            format!(
+                "the tags file at path `{}` already has tags; use `--force` to replace them",
+                path.display()
+            ),
+        ));
+    }
+
+    let new_tags = match matches.value_of("from") {
+        Some(file) => {
+            let file = analysis::expand_path(file);
+            let contents = fs::read_to_string(file.as_ref()).map_err(|e| {
+                Error::new(ErrorKind::Io, format!("unable to read `{}`: {}", file, e))
+            })?;
+            tag::parse_tags(&contents).map_err(|e| {
+                Error::new(
+                    ErrorKind::Data,
+                    format!("json error at path `{}`: {}", file, e),
+                )
+            })?
+        },
+        None => tag::parse_tags(STARTER_TAGS).expect("starter tags are valid"),
+    };
+
+    *tags = new_tags;
+    Ok(tags.len())
+}
+
+/// Runs the lookup command.
+///
+/// Relative file paths are resolved against the current directory. Errors if
+/// no tag opens the path.
+pub fn lookup(tags: &[Tag], config: &Config, matches: &ArgMatches) -> Result<()> {
+    let path = matches.value_of("path").expect("required");
+    let absolute;
+    let path = if !analysis::is_url(path)
+        && Path::new(analysis::expand_path(path).as_ref()).is_relative()
+    {
+        absolute = env::current_dir()?
+            .join(path)
+            .to_string_lossy()
+            .into_owned();
+        absolute.as_str()
+    } else {
+        path
+    };
+
+    let found = analysis::lookup(tags, path, config.root.as_deref());
+    if found.is_empty() {
+        return Err(Error::new(
+            ErrorKind::NotFound,
+            format!("no tag opens `{}`", path),
+        ));
+    }
+
+    for (entry, exact) in found {
+        if exact {
+            println!("{}    {}", entry.names.join(" "), entry.path);
+        } else {
+            println!("{}    {} (parent)", entry.names.join(" "), entry.path);
+        }
+    }
+
+    Ok(())
+}
+
+/// Runs the edit command.
+///
+/// All tags are edited as JSON, or the given tag as TOML, in the editor until
+/// they are valid or the user gives up. Returns `true` if the tags were
+/// changed.
+pub fn edit(tags: &mut Tags, matches: &ArgMatches) -> Result<bool> {
+    let names = match matches.value_of("tag") {
+        Some(tag_path) => tag::split_tag_path(tag_path),
+        None => {
+            let text = tag::serialize_tags(tags)?;
+            let edited = edit_until_valid(&text, ".json", |edited| {
+                let edited_tags = tag::parse_tags(edited)
+                    .map_err(|e| Error::new(ErrorKind::Data, format!("json error: {}", e)))?;
+                tag::validate_tags(&edited_tags)?;
+                Ok(edited_tags)
+            })?;
+
+            return Ok(edited.map(|edited| *tags = edited).is_some());
+        },
+    };
+
+    let with_subtags = matches.contains_id("subtags");
+    let (last, parent_names) = names
+        .split_last()
+        .ok_or_else(|| Error::new(ErrorKind::NotFound, "tag not found"))?;
+    let siblings = tag::find_subtags_by_names_mut(tags, parent_names)
+        .ok_or_else(|| Error::new(ErrorKind::NotFound, "tag not found"))?;
+    let i = siblings
+        .iter()
+        .position(|t| t.has_name(last))
+        .ok_or_else(|| Error::new(ErrorKind::NotFound, "tag not found"))?;
+
+    let mut shown = siblings[i].clone();
+    if !with_subtags {
+        shown.subtags.clear();
+    }
+    let text = tag_to_toml(&shown)?;
+
+    let edited = edit_until_valid(&text, ".toml", |edited| {
+        let edited_tag = toml::from_str::<Tag>(edited)
+            .map_err(|e| Error::new(ErrorKind::Data, format!("toml error: {}", e)))?;
+        tag::validate_tags(std::slice::from_ref(&edited_tag))?;
+        for (j, sibling) in siblings.iter().enumerate() {
+            if let Some(name) = edited_tag
+                .names
+                .iter()
+                .find(|n| j != i && sibling.names.contains(n))
+            {
+                return Err(format!("a tag with name `{}` already exists", name).into());
+            }
+        }
+        Ok(edited_tag)
+    })?;
+
+    let mut edited = match edited {
+        Some(edited) => edited,
+        None => return Ok(false),
+    };
+    if !with_subtags {
+        edited.subtags = std::mem::take(&mut siblings[i].subtags);
+    }
+    edited.modified = Some(usage::now());
+    siblings[i] = edited;
+
+    Ok(true)
+}
+
+/// Serializes the tag as TOML to be edited.
+///
+/// TOML cannot have a value after a table, so the tag goes through a
+/// [`toml::Value`], which writes the values of a table before its tables,
+/// such as a path by operating system, while keeping the order of the fields.
+fn tag_to_toml(tag: &Tag) -> Result<String> {
+    Ok(toml::to_string_pretty(&toml::Value::try_from(tag)?)?)
+}
+
+/// Opens the text in the editor until `parse` accepts the edited text or the
+/// user gives up.
+///
+/// Returns `None` if the text was not changed.
+fn edit_until_valid<T, F>(text: &str, extension: &str, parse: F) -> Result<Option<T>>
+where
+    F: Fn(&str) -> Result<T>,
+{
+    let mut current = text.to_string();
+
+    loop {
+        let edited = match Editor::new().extension(extension).edit(&current)? {
+            Some(edited) if edited.trim() != text.trim() => edited,
+            _ => {
+                output::status("No changes.");
+                return Ok(None);
+            },
+        };
+
+        match parse(&edited) {
+            Ok(value) => return Ok(Some(value)),
+            Err(e) => eprintln!("The edited tags are invalid: {}", e),
+        }
+
+        if !Confirm::new()
+            .with_prompt("Edit them again?")
+            .default(true)
+            .interact()?
+        {
+            return Err(Error::new(ErrorKind::Abort, "the changes were discarded"));
+        }
+        current = edited;
+    }
+}
+
+/// Runs the grep command.
+///
+/// Every line of the path, the description, and the notes of every tag that
+/// matches the pattern is printed after the full name of the tag and the field,
+/// with the matches highlighted.
+pub fn grep(tags: &[Tag], matches: &ArgMatches) -> Result<()> {
+    let pattern = matches.value_of("pattern").expect("required");
+    let regex = RegexBuilder::new(pattern)
+        .case_insensitive(matches.contains_id("ignore-case"))
+        .build()
+        .map_err(|e| {
+            // syntax errors span several lines, the last of which is the reason
+            let e = e.to_string();
+            let reason = e.lines().last().unwrap_or_default();
+            Error::new(
+                ErrorKind::Usage,
+                format!(
+                    "invalid pattern `{}`: {}",
+                    pattern,
+                    reason.trim_start_matches("error: ")
+                ),
+            )
+        })?;
+
+    let highlight = Style::new().red().bold();
+    let mut found = false;
+    analysis::walk(tags, |names, tag| {
+        for (field, text) in [
+            ("path", tag.path.as_ref().map(ToString::to_string)),
+            ("about", tag.about.clone()),
+            ("notes", tag.notes.clone()),
+        ] {
+            for line in text.iter().flat_map(|t| t.lines()) {
+                let mut highlighted = String::new();
+                let mut end = None;
+                for m in regex.find_iter(line) {
+                    highlighted.push_str(&line[end.unwrap_or(0)..m.start()]);
+                    highlighted.push_str(&highlight.apply_to(m.as_str()).to_string());
+                    end = Some(m.end());
+                }
+                let end = match end {
+                    Some(end) => end,
+                    None => continue,
+                };
+                highlighted.push_str(&line[end..]);
+
+                found = true;
+                println!("{} ({}): {}", names.join("/"), field, highlighted);
+            }
+        }
+    });
+
+    if !found {
+        output::status("No matches!");
+    }
+
+    Ok(())
+}
+
+/// Runs the exec command, returning the exit code of the first command that
+/// failed, or `0`.
+///
+/// With `--recursive`, the command is run for the tag and each of its
+/// subtags with a path, in order; secret subtags are skipped. A secret tag is
+/// only decrypted if it is the one given.
+pub fn exec(
+    tags: &[Tag],
+    path: &Path,
+    config: &Config,
+    matches: &ArgMatches,
+    sub_matches: &ArgMatches,
+) -> Result<i32> {
+    let tag_path = sub_matches.value_of("tag").expect("required");
+    let names = tag::split_tag_path(tag_path);
+    let tag = tag::find_tag_by_names(tags, &names)
+        .ok_or_else(|| Error::new(ErrorKind::NotFound, "no tag found"))?;
+    let command = sub_matches
+        .values_of("command")
+        .expect("required")
+        .collect::<Vec<_>>();
+
+    let revealed;
+    let tag = match tag.secret {
+        Some(_) => match secret::reveal(tag, tag_path, &config.secrets)? {
+            Some(tag) => {
+                revealed = tag;
+                &revealed
+            },
+            None => return Ok(0),
+        },
+        None => tag,
+    };
+
+    let mut targets = vec![tag];
+    if sub_matches.contains_id("recursive") {
+        analysis::walk(&tag.subtags, |_, t| targets.push(t));
+    } else if tag.path().is_none() && tag.fallback_paths.is_empty() {
+        return Err("tag has no path or url".into());
+    }
+
+    let dry_run = matches.contains_id("dry-run");
+    let mut code = 0;
+    for target in targets.into_iter().filter(|t| t.secret.is_none()) {
+        let target_path = match tag_target(target, config.root.as_deref()) {
+            Some(path) => path,
+            None => continue,
+        };
+
+        let mut args = command
+            .iter()
+            .map(|a| a.replace("{}", &target_path))
+            .collect::<Vec<_>>();
+        if !command.iter().any(|a| a.contains("{}")) {
+            args.push(target_path.into_owned());
+        }
+
+        if dry_run {
+            println!(
+                "Would run `{}`.",
+                args.iter().map(|a| output::shell_quote(a)).join(" ")
+            );
+            continue;
+        }
+
+        let mut cmd = Command::new(&args[0]);
+        cmd.args(&args[1..]);
+        if let Some(ref cwd) = target.cwd {
+            cmd.current_dir(analysis::expand_path(cwd).as_ref());
+        }
+        cmd.envs(
+            target
+                .env
+                .iter()
+                .map(|(k, v)| (k, analysis::expand_path(v).into_owned())),
+        );
+        log::info!("running {:?}", args);
+        let status = cmd
+            .status()
+            .map_err(|e| format!("unable to run `{}`: {}", args[0], e))?;
+        if code == 0 {
+            code = status.code().unwrap_or(1);
+        }
+    }
+
+    if !dry_run {
+        record_open(tags, path, &names.join("/"), "exec", config)?;
+    }
+
+    Ok(code)
+}
+
+/// Runs the export command.
+///
+/// Only the tag given in the matches and its subtags are exported if one is
+/// given. The document is written to the output file given in the matches, or
+/// printed.
+pub fn export(tags: &[Tag], matches: &ArgMatches) -> Result<()> {
+    let subtree;
+    let mut parent = Vec::new();
+    let tags = match matches.value_of("tag") {
+        Some(path) => {
+            let names = tag::split_tag_path(path);
+            let tag = tag::find_tag_by_names(tags, &names)
+                .ok_or_else(|| Error::new(ErrorKind::NotFound, "no tag found"))?;
+            parent = names[..names.len() - 1]
+                .iter()
+                .map(|n| n.to_string())
+                .collect();
+            subtree = [tag.clone()];
+            &subtree[..]
+        },
+        None => tags,
+    };
+
+    let document = match matches.value_of("format") {
+        Some("markdown") => output::markdown(tags),
+        Some("json") => tag::serialize_tags(tags)? + "\n",
+        Some("alfred") => output::alfred(tags, &parent)?,
+        Some("desktop-entries") => return export_desktop_entries(tags, &parent, matches),
+        format => unreachable!("unhandled export format `{:?}`", format),
+    };
+
+    match matches.value_of("output") {
+        Some(file) if matches.contains_id("dry-run") => {
+            println!("Would write to `{}`:\n{}", file, document);
+        },
+        Some(file) => {
+            let path = analysis::expand_path(file);
+            fs::write(path.as_ref(), document).map_err(|e| {
+                Error::new(ErrorKind::Io, format!("unable to write `{}`: {}", path, e))
+            })?;
+        },
+        None => print!("{}", document),
+    }
+
+    Ok(())
+}
+
+/// Writes a desktop entry for every tag that can be opened to the output
+/// directory given in the matches, creating it if needed.
+///
+/// The entries run this program, so that launchers find it wherever it is
+/// installed.
+fn export_desktop_entries(tags: &[Tag], parent: &[String], matches: &ArgMatches) -> Result<()> {
+    let dir = match matches.value_of("output") {
+        Some(dir) => analysis::expand_path(dir).into_owned(),
+        None => {
+            return Err(Error::new(
+                ErrorKind::Usage,
+                "`--format desktop-entries` requires the directory to write them to with \
+                 `--output`, such as `~/.local/share/applications`",
+            ))
+        },
+    };
+    let program = env::current_exe()?;
+    let entries = output::desktop_entries(tags, parent, &program.to_string_lossy());
+
+    if matches.contains_id("dry-run") {
+        for (file_name, entry) in &entries {
+            println!(
+                "Would write to `{}`:\n{}",
+                Path::new(&dir).join(file_name).display(),
+                entry
+            );
+        }
+        return Ok(());
+    }
+
+    fs::create_dir_all(&dir)
+        .map_err(|e| Error::new(ErrorKind::Io, format!("unable to create `{}`: {}", dir, e)))?;
+    for (file_name, entry) in &entries {
+        let path = Path::new(&dir).join(file_name);
+        fs::write(&path, entry).map_err(|e| {
+            Error::new(
+                ErrorKind::Io,
+                format!("unable to write `{}`: {}", path.display(), e),
+            )
+        })?;
+    }
+    output::status(&match entries.len() {
+        1 => format!("Wrote 1 desktop entry to `{}`.", dir),
+        n => format!("Wrote {} desktop entries to `{}`.", n, dir),
+    });
+
+    Ok(())
+}
+
+/// Runs the group command, returning the exit code of the first tag that
+/// failed to open, or `0`.
+///
+/// Every tag of the group is found before any is opened, so that a missing
+/// tag does not leave the group half open.
+///
+/// The tags are opened one after the other without waiting for their apps,
+/// unless `--sequential` is given, and `--delay` staggers them so that many
+/// tabs are not opened at once.
+pub fn group(
+    tags: &[Tag],
+    path: &Path,
+    config: &Config,
+    matches: &ArgMatches,
+    sub_matches: &ArgMatches,
+) -> Result<i32> {
+    let open_matches = match sub_matches.subcommand() {
+        Some(("open", open_matches)) => open_matches,
+        _ => {
+            if config.groups.is_empty() {
+                output::status("No groups yet! Add them to the `groups` key of the config file.");
+            }
+            for (name, members) in config.groups.iter().sorted_by_key(|(n, _)| *n) {
+                println!("{}    {}", name, members.join(", "));
+            }
+            return Ok(0);
+        },
+    };
+
+    let name = open_matches.value_of("name").expect("required");
+    let members = config
+        .groups
+        .get(name)
+        .ok_or_else(|| Error::new(ErrorKind::NotFound, format!("no group named `{}`", name)))?;
+    let targets = members
+        .iter()
+        .map(|member| {
+            tag::find_tag_by_names(tags, &tag::split_tag_path(member))
+                .map(|t| (member, t))
+                .ok_or_else(|| {
+                    Error::new(
+                        ErrorKind::NotFound,
+                        format!("group `{}` has no tag `{}`", name, member),
+                    )
+                })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    // secret tags are decrypted first, as they are confirmed one at a time
+    let mut opened = Vec::new();
+    for (member, tag) in targets {
+        let names = tag_names_of(tags, tag).expect("found tag is in the tags");
+        match tag.secret {
+            Some(_) => {
+                if let Some(tag) = secret::reveal(tag, member, &config.secrets)? {
+                    opened.push((names, Cow::Owned(tag)));
+                }
+            },
+            None => opened.push((names, Cow::Borrowed(tag))),
+        }
+    }
+
+    let mut options = MatchOptions::from_matches(matches, &config.defaults);
+    options.load_usage(path);
+    let sequential = open_matches.contains_id("sequential");
+    options.wait |= sequential;
+    let delay = Duration::from_millis(open_matches.get_one::<u64>("delay").copied().unwrap_or(0));
+
+    // opening a tag only waits for its app with `--sequential`, so the tags
+    // are opened on this thread, which keeps the working directory and the
+    // environment of each from reaching the others
+    let results = opened
+        .iter()
+        .enumerate()
+        .map(|(i, (names, tag))| {
+            if i > 0 && !options.dry_run {
+                thread::sleep(delay);
+            }
+            run_tag(tag, names, tag::ancestor_app(tags, names), &options, config)
+        })
+        .collect::<Vec<_>>();
+
+    let mut code = 0;
+    for ((names, _), result) in opened.iter().zip(results) {
+        let tag_code = result?;
+        if !options.dry_run {
+            record_open(tags, path, &names.join("/"), options.action(), config)?;
+        }
+        if code == 0 {
+            code = tag_code;
+        }
+    }
+
+    Ok(code)
+}
+
+/// Runs the history command.
+///
+/// The most recently opened tags are listed oldest first, so that the last
+/// one opened is printed last.
+pub fn history(path: &Path, matches: &ArgMatches, sub_matches: &ArgMatches) -> Result<()> {
+    let history_path = history::get_history_path(path);
+    if sub_matches.subcommand_name() == Some("clear") {
+        if matches.contains_id("dry-run") {
+            println!("Would delete `{}`.", history_path.display());
+        } else {
+            history::clear(&history_path)?;
+            output::status("Cleared the history.");
+        }
+        return Ok(());
+    }
+
+    let entries = history::get_history(&history_path)?;
+    if entries.is_empty() {
+        output::status("No tags opened yet!");
+        return Ok(());
+    }
+
+    let count = *sub_matches.get_one::<usize>("count").expect("default");
+    for entry in &entries[entries.len().saturating_sub(count)..] {
+        if sub_matches.contains_id("json") {
+            println!("{}", serde_json::to_string(entry)?);
+            continue;
+        }
+        let time = analysis::format_time(entry.time);
+        match entry.target {
+            Some(ref target) => println!(
+                "{}    {:<5}    {}    {}",
+                time, entry.action, entry.tag, target
+            ),
+            None => println!("{}    {:<5}    {}", time, entry.action, entry.tag),
+        }
+    }
+    Ok(())
+}
+
+/// Runs the import command.
+pub fn import(tags: &mut Tags, matches: &ArgMatches) -> Result<()> {
+    if matches.contains_id("zoxide") {
+        let count = *matches.get_one::<usize>("count").expect("default");
+        return import_dirs(tags, import::tags_from_zoxide(count)?, matches);
+    }
+    if matches.contains_id("ssh-config") {
+        return import_ssh_hosts(tags, matches);
+    }
+
+    if let Some(browser) = matches.value_of("browser") {
+        let start = Instant::now();
+        let file = matches.value_of("file").map(analysis::expand_path);
+        let mut new_tags = import::tags_from_browser(browser, file.as_deref().map(Path::new))?;
+
+        // bookmarks are URLs, which do not depend on the root
+        let mut known = analysis::path_index(tags, None)
+            .into_iter()
+            .map(|e| e.path)
+            .collect::<HashSet<_>>();
+        let parent_names = tag::split_tag_path(matches.value_of("parent").unwrap_or_default());
+        let siblings = tag::find_or_create_subtags_mut(tags, &parent_names);
+        let skipped = skip_known_bookmarks(&mut new_tags, &mut known);
+        let imported = merge_bookmarks(siblings, new_tags);
+
+        let took = progress::took(start);
+        match imported {
+            1 => output::status(&format!("Imported 1 bookmark{}.", took)),
+            n => output::status(&format!("Imported {} bookmarks{}.", n, took)),
+        }
+        match skipped {
+            0 => {},
+            1 => output::status("Skipped 1 bookmark that is already tagged."),
+            n => output::status(&format!("Skipped {} bookmarks that are already tagged.", n)),
+        }
+        return Ok(());
+    }
+
+    let file = matches.value_of("file").expect("required");
+    let text = if file == "-" {
+        io::read_to_string(io::stdin())?
+    } else {
+        let path = analysis::expand_path(file);
+        fs::read_to_string(path.as_ref())
+            .map_err(|e| Error::new(ErrorKind::Io, format!("unable to read `{}`: {}", path, e)))?
+    };
+
+    let new_tags = match matches.value_of("format") {
+        Some("csv") | None => {
+            let mapping = import::parse_mapping(matches.value_of("mapping").unwrap_or_default())?;
+            import::tags_from_csv(&text, &mapping)?
+        },
+        format => unreachable!("unhandled import format `{:?}`", format),
+    };
+
+    let parent = matches.value_of("parent").unwrap_or_default();
+    let siblings = tag::find_or_create_subtags_mut(tags, &tag::split_tag_path(parent));
+    let mut on_conflict = OnConflict::from_matches(matches);
+    let (mut imported, mut skipped, mut merged) = (0, 0, 0);
+    let progress = Progress::new("Importing", new_tags.len());
+    for tag in new_tags {
+        // conflicts are asked about without the bar in the way
+        let resolved = if on_conflict == OnConflict::Ask {
+            progress.suspend(|| add_resolving(siblings, tag, parent, &mut on_conflict))?
+        } else {
+            add_resolving(siblings, tag, parent, &mut on_conflict)?
+        };
+        match resolved {
+            Resolved::Added => imported += 1,
+            Resolved::Skipped => skipped += 1,
+            Resolved::Merged => merged += 1,
+        }
+        progress.inc();
+    }
+    let took = progress.took();
+    drop(progress);
+
+    match imported {
+        1 => output::status(&format!("Imported 1 tag{}.", took)),
+        n => output::status(&format!("Imported {} tags{}.", n, took)),
+    }
+    print_resolved(skipped, merged);
+    Ok(())
+}
+
+/// Prints how many added tags were skipped or merged into existing tags
+/// because their names were taken.
+fn print_resolved(skipped: usize, merged: usize) {
+    match skipped {
+        0 => {},
+        1 => output::status("Skipped 1 tag whose name is taken."),
+        n => output::status(&format!("Skipped {} tags whose names are taken.", n)),
+    }
+    match merged {
+        0 => {},
+        1 => output::status("Merged 1 tag into an existing tag."),
+        n => output::status(&format!("Merged {} tags into existing tags.", n)),
+    }
+}
+
+/// Adds the hosts of the SSH config file under the tag given with `--parent`,
+/// or `ssh`, skipping the hosts that are already tagged.
+fn import_ssh_hosts(tags: &mut Tags, matches: &ArgMatches) -> Result<()> {
+    let file = match matches.value_of("file") {
+        Some(file) => PathBuf::from(analysis::expand_path(file).as_ref()),
+        None => dirs_next::home_dir()
+            .ok_or("unable to find the home directory; pass the SSH config file")?
+            .join(".ssh/config"),
+    };
+    let text = fs::read_to_string(&file).map_err(|e| {
+        Error::new(
+            ErrorKind::Io,
+            format!("unable to read `{}`: {}", file.display(), e),
+        )
+    })?;
+    let new_tags = import::tags_from_ssh_config(&text);
+
+    let mut known = analysis::path_index(tags, None)
+        .into_iter()
+        .map(|e| e.path)
+        .collect::<HashSet<_>>();
+    let parent_names = tag::split_tag_path(matches.value_of("parent").unwrap_or("ssh"));
+    let siblings = tag::find_or_create_subtags_mut(tags, &parent_names);
+
+    let mut imported = 0;
+    let mut skipped = 0;
+    for mut new_tag in new_tags {
+        let path = new_tag.path().map(analysis::normalize_path);
+        if !path.is_some_and(|p| known.insert(p)) {
+            skipped += 1;
+            continue;
+        }
+        new_tag.names = new_tag
+            .names
+            .iter()
+            .filter(|n| !siblings.iter().any(|t| t.has_name(n)))
+            .cloned()
+            .collect();
+        if new_tag.names.is_empty() {
+            let host = new_tag
+                .path()
+                .unwrap_or_default()
+                .trim_start_matches("ssh://");
+            let name = tag::unique_name(host, |n| siblings.iter().any(|t| t.has_name(n)));
+            new_tag.names.push(name);
+        }
+        siblings.push(new_tag);
+        imported += 1;
+    }
+
+    match imported {
+        1 => output::status("Imported 1 host."),
+        n => output::status(&format!("Imported {} hosts.", n)),
+    }
+    match skipped {
+        0 => {},
+        1 => output::status("Skipped 1 host that is already tagged."),
+        n => output::status(&format!("Skipped {} hosts that are already tagged.", n)),
+    }
+    Ok(())
+}
+
+/// Adds the tags picked among the tags of directories under the tag given with
+/// `--parent`, skipping the directories that are already tagged.
+///
+/// All the tags are added without asking with `--yes`.
+fn import_dirs(tags: &mut Tags, new_tags: Tags, matches: &ArgMatches) -> Result<()> {
+    let known = analysis::path_index(tags, None)
+        .into_iter()
+        .map(|e| e.path)
+        .collect::<HashSet<_>>();
+    let parent_names = tag::split_tag_path(matches.value_of("parent").unwrap_or_default());
+    let siblings = tag::find_subtags_by_names_mut(tags, &parent_names)
+        .map(|s| s.as_slice())
+        .unwrap_or_default();
+
+    let mut new_tags = new_tags
+        .into_iter()
+        .filter(|t| {
+            t.path()
+                .is_some_and(|p| !known.contains(&analysis::normalize_path(p)))
+        })
+        .collect::<Vec<_>>();
+    for new_tag in &mut new_tags {
+        let taken = |n: &str| siblings.iter().any(|t| t.has_name(n));
+        new_tag.names[0] = tag::unique_name(&new_tag.names[0], taken);
+    }
+    if new_tags.is_empty() {
+        output::status("No new directories to import.");
+        return Ok(());
+    }
+
+    let picked = if output::assume_yes() {
+        (0..new_tags.len()).collect()
+    } else {
+        let width = new_tags.iter().map(|t| t.names[0].len()).max().unwrap_or(0);
+        let items = new_tags
+            .iter()
+            .map(|t| {
+                format!(
+                    "{:width$}    {}",
+                    t.names[0],
+                    t.path().unwrap_or_default(),
+                    width = width
+                )
+            })
+            .collect::<Vec<_>>();
+        MultiSelect::with_theme(&ColorfulTheme::default())
+            .with_prompt(
+                "Press `space` to pick the directories to import and `enter` to import them \
+                 (press `esc` to quit)",
+            )
+            .items(&items)
+            .defaults(&vec![true; items.len()])
+            .interact_opt()?
+            .unwrap_or_default()
+    };
+
+    let imported = picked.len();
+    if imported == 0 {
+        output::status("No directories imported.");
+        return Ok(());
+    }
+
+    let siblings = tag::find_or_create_subtags_mut(tags, &parent_names);
+    for (i, new_tag) in new_tags.into_iter().enumerate() {
+        if picked.contains(&i) {
+            siblings.push(new_tag);
+        }
+    }
+
+    match imported {
+        1 => output::status("Imported 1 directory."),
+        n => output::status(&format!("Imported {} directories.", n)),
+    }
+    Ok(())
+}
+
+/// Removes the bookmarks with a path in `known`, and the folders left without
+/// bookmarks, returning the number of bookmarks removed.
+///
+/// The paths of the bookmarks that are kept are added to `known`, so that
+/// duplicate bookmarks are only kept once.
+fn skip_known_bookmarks(tags: &mut Tags, known: &mut HashSet<String>) -> usize {
+    let mut skipped = 0;
+    tags.retain_mut(|t| {
+        skipped += skip_known_bookmarks(&mut t.subtags, known);
+        match t.path() {
+            Some(path) if known.insert(analysis::normalize_path(path)) => true,
+            Some(_) => {
+                skipped += 1;
+                false
+            },
+            None => !t.subtags.is_empty(),
+        }
+    });
+    skipped
+}
+
+/// Adds the imported bookmarks to the siblings, returning the number of
+/// bookmarks added.
+///
+/// Folders are merged into the sibling with the same name, if any. Other names
+/// taken by a sibling are made unique.
+fn merge_bookmarks(siblings: &mut Tags, new_tags: Tags) -> usize {
+    let mut imported = 0;
+    for mut new_tag in new_tags {
+        let subtags = std::mem::take(&mut new_tag.subtags);
+        let existing = siblings.iter().position(|t| t.has_name(&new_tag.names[0]));
+        let i = match existing {
+            Some(i) if new_tag.path.is_none() => i,
+            _ => {
+                if new_tag.path.is_some() {
+                    imported += 1;
+                }
+                let taken = siblings
+                    .iter()
+                    .flat_map(|t| t.names.clone())
+                    .collect::<Vec<_>>();
+                new_tag.names[0] =
+                    tag::unique_name(&new_tag.names[0], |n| taken.iter().any(|t| t == n));
+                siblings.push(new_tag);
+                siblings.len() - 1
+            },
+        };
+        imported += merge_bookmarks(&mut siblings[i].subtags, subtags);
+    }
+    imported
+}
+
+/// Runs the stats command.
+pub fn stats(tags: &[Tag], path: &Path) -> Result<()> {
+    let stats = analysis::stats(tags);
+    let size = fs::metadata(path)?.len();
+
+    println!("Location:     {}", path.display());
+    println!("Size:         {} bytes", size);
+    println!("Tags:         {}", stats.tags);
+    println!("Max depth:    {}", stats.max_depth);
+    println!("URLs:         {}", stats.urls);
+    println!("Files:        {}", stats.files);
+    println!("No path:      {}", stats.no_path);
+    println!("Aliases:      {}", stats.aliases);
+
+    Ok(())
+}
+
+/// Asks the user to choose one of the tags with the same name, given by the
+/// primary names of each tag and its parents, and returns its index, or `None`
+/// if the user pressed `esc`.
+pub fn choose_tag(found: &[Vec<String>], config: &Config) -> Result<Option<usize>> {
+    let items = found.iter().map(|n| n.join("/")).collect::<Vec<_>>();
+    Ok(Picker::new(
+        "Several tags have this name, select one (press `esc` to quit)",
+        &items,
+        &config.fuzzy,
+    )
+    .interact_opt()?)
+}
+
+/// Prompts the user to pick a subtag of the tag with the `names`, such as to
+/// invoke instead of a tag without a path.
+///
+/// Returns `None` if the user quits the picker.
+pub fn select_subtag<'a>(
+    tag: &'a Tag,
+    names: &[String],
+    config: &Config,
+) -> Result<Option<&'a Tag>> {
+    let subtags = tag
+        .subtags
+        .iter()
+        .filter(|t| !t.names.is_empty())
+        .collect::<Vec<_>>();
+    let items = subtags
+        .iter()
+        .map(|t| tag::display_name(t))
+        .collect::<Vec<_>>();
+    let previews = subtags.iter().map(|t| tag_preview(t)).collect::<Vec<_>>();
+    let prompt = format!(
+        "`{}` has no path; select a subtag (press `esc` to quit)",
+        names.join("/")
+    );
+    let selected = Picker::new(&prompt, &items, &config.fuzzy)
+        .with_previews(&previews)
+        .interact_opt()?;
+
+    Ok(selected.map(|i| subtags[i]))
+}
+
+/// Runs the pick command.
+///
+/// All tags with a path, or all tags with `--list`, are shown with their full
+/// path of names. The selected tag is run with the options in `matches`.
+pub fn pick(
+    tags: &[Tag],
+    path: &Path,
+    config: &Config,
+    matches: &ArgMatches,
+    sub_matches: &ArgMatches,
+) -> Result<()> {
+    let mut options = MatchOptions::from_matches(matches, &config.defaults);
+    options.load_usage(path);
+
+    let mut candidates = Vec::new();
+    analysis::walk(tags, |names, tag| {
+        if (options.list || tag.path().is_some()) && !app::is_builtin(&names[0]) {
+            candidates.push((names.to_vec(), tag));
+        }
+    });
+    if candidates.is_empty() {
+        return Err(Error::new(ErrorKind::NotFound, "no tags to pick from"));
+    }
+
+    let items = candidates
+        .iter()
+        .map(|(n, _)| n.join("/"))
+        .collect::<Vec<_>>();
+    let previews = candidates
+        .iter()
+        .map(|(_, t)| tag_preview(t))
+        .collect::<Vec<_>>();
+    let selected = Picker::new("Select a tag (press `esc` to quit)", &items, &config.fuzzy)
+        .with_query(sub_matches.value_of("query").unwrap_or_default())
+        .with_previews(&previews)
+        .interact_opt()?;
+
+    if let Some(i) = selected {
+        let (ref names, tag) = candidates[i];
+        let ancestor_app = tag::ancestor_app(tags, names);
+        let code = run_tag(tag, names, ancestor_app, &options, config)?;
+        if !options.list && !options.dry_run {
+            record_open(tags, path, &names.join("/"), options.action(), config)?;
+        }
+        if code != 0 {
+            process::exit(code);
+        }
+    }
+
+    Ok(())
+}
+
+/// Runs the recent command.
+pub fn recent(tags: &[Tag], matches: &ArgMatches) {
+    let count = *matches.get_one::<usize>("count").expect("default");
+    let recent = analysis::recent(tags);
+
+    if recent.is_empty() {
+        output::status("No recently added or modified tags!");
+        return;
+    }
+
+    let now = usage::now();
+    for (names, tag) in recent.into_iter().take(count) {
+        let age = format_age(now.saturating_sub(tag.last_changed().unwrap_or(now)));
+        match tag.path() {
+            Some(path) => println!("{:>14}    {}    {}", age, names.join("/"), path),
+            None => println!("{:>14}    {}", age, names.join("/")),
+        }
+    }
+}
+
+/// Runs the stale command.
+pub fn stale(tags: &[Tag]) {
+    let now = usage::now();
+    let stale = analysis::stale(tags, now);
+    if stale.is_empty() {
+        output::status("No tags to review!");
+        return;
+    }
+
+    for (names, tag) in stale {
+        let date = tag.review_after.as_deref().unwrap_or_default();
+        match tag.path() {
+            Some(path) => println!("{}    {}    {}", date, names.join("/"), path),
+            None => println!("{}    {}", date, names.join("/")),
+        }
+    }
+}
+
+/// Runs the unused command.
+///
+/// Tags are listed with when they were last opened, as recorded in the usage
+/// file next to the tags file at `path`.
+pub fn unused(tags: &[Tag], path: &Path, matches: &ArgMatches) -> Result<()> {
+    let now = usage::now();
+    let since = match matches.value_of("since") {
+        Some(age) => Some(now.saturating_sub(analysis::parse_age(age).ok_or_else(|| {
+            Error::new(
+                ErrorKind::Usage,
+                format!(
+                    "`{}` is not an age such as `90d`, `12w`, `6m`, or `1y`",
+                    age
+                ),
+            )
+        })?)),
+        None => None,
+    };
+    let usage = usage::get_usage(usage::get_usage_path(path))?;
+
+    let unused = analysis::unused(tags, &usage, since);
+    if unused.is_empty() {
+        output::status("No unused tags!");
+        return Ok(());
+    }
+
+    for (names, tag, last_opened) in &unused {
+        let opened = match last_opened {
+            0 => "never opened".to_string(),
+            t => format!("opened {}", format_age(now.saturating_sub(*t))),
+        };
+        match tag.path() {
+            Some(path) => println!("{:>20}    {}    {}", opened, names.join("/"), path),
+            None => println!("{:>20}    {}", opened, names.join("/")),
+        }
+    }
+
+    Ok(())
+}
+
+/// Formats a number of seconds as a rough age, such as `3 days ago`.
+fn format_age(secs: u64) -> String {
+    let (value, unit) = match secs {
+        s if s < 60 => return "just now".to_string(),
+        s if s < 60 * 60 => (s / 60, "minute"),
+        s if s < 60 * 60 * 24 => (s / (60 * 60), "hour"),
+        s if s < 60 * 60 * 24 * 30 => (s / (60 * 60 * 24), "day"),
+        s if s < 60 * 60 * 24 * 365 => (s / (60 * 60 * 24 * 30), "month"),
+        s => (s / (60 * 60 * 24 * 365), "year"),
+    };
+
+    format!(
+        "{} {}{} ago",
+        value,
+        unit,
+        if value == 1 { "" } else { "s" }
+    )
+}
+
+/// Runs the sync command.
+pub fn sync(
+    tags: &[Tag],
+    path: &Path,
+    stamp: Option<FileStamp>,
+    config: &Config,
+    matches: &ArgMatches,
+    sub_matches: &ArgMatches,
+) -> Result<()> {
+    let backend = crate::sync::backend(
+        config
+            .sync
+            .as_ref()
+            .ok_or("no sync backend is configured; see the `sync` key of the config file")?,
+    )?;
+    let url = backend.url();
+    let state_path = crate::sync::get_state_path(path);
+    let state = crate::sync::get_state(&state_path)?;
+    let contents = fs::read_to_string(path)?;
+    let local_changed = state
+        .as_ref()
+        .map_or(!tags.is_empty(), |s| s.contents != contents);
+    let synced_etag = state.as_ref().and_then(|s| s.etag.as_deref());
+
+    match sub_matches.subcommand() {
+        Some(("push", push_matches)) => {
+            let remote_etag = crate::sync::head(backend.as_ref())?;
+            let remote_changed = remote_etag.as_deref() != synced_etag;
+            if !local_changed && !remote_changed {
+                output::status("Already up to date.");
+                return Ok(());
+            }
+
+            let force = push_matches.contains_id("force");
+            if remote_changed && remote_etag.is_some() && !force {
+                return Err(crate::sync::conflict_error());
+            }
+            if matches.contains_id("dry-run") {
+                println!("Would upload `{}` to `{}`.", path.display(), url);
+                return Ok(());
+            }
+
+            // the upload is conditional so that a change made since the
+            // remote tags were checked is not overwritten either
+            let condition = match remote_etag.as_deref() {
+                _ if force => None,
+                None => Some(""),
+                Some("") => None,
+                Some(etag) => Some(etag),
+            };
+            let etag = crate::sync::put(backend.as_ref(), path, condition)?;
+            crate::sync::write_state(&state_path, &crate::sync::SyncState {
+                etag: Some(etag),
+                contents,
+            })?;
+            output::status(&format!("Pushed the tags to `{}`.", url));
+        },
+        Some(("pull", pull_matches)) => {
+            let (remote, etag) = crate::sync::get(backend.as_ref())?.ok_or_else(|| {
+                Error::new(
+                    ErrorKind::NotFound,
+                    format!("there are no tags at `{}` yet; push them first", url),
+                )
+            })?;
+            if !etag.is_empty() && synced_etag == Some(etag.as_str()) {
+                output::status("Already up to date.");
+                return Ok(());
+            }
+            if local_changed && remote != contents && !pull_matches.contains_id("force") {
+                return Err(
+                    "the local tags changed since the last sync; push them first, or \
+                            use `--force` to overwrite them"
+                        .into(),
+                );
+            }
+
+            let new_tags = tag::parse_tags(&remote).map_err(|e| {
+                Error::new(
+                    ErrorKind::Data,
+                    format!("json error in the remote tags at `{}`: {}", url, e),
+                )
+            })?;
+            tag::validate_tags(&new_tags)?;
+            let changes = analysis::diff(tags, &new_tags);
+
+            if matches.contains_id("dry-run") {
+                println!(
+                    "Would replace `{}` with the tags at `{}`.",
+                    path.display(),
+                    url
+                );
+            } else {
+                tag::write_tags(new_tags, path, stamp)?;
+                crate::sync::write_state(&state_path, &crate::sync::SyncState {
+                    etag: Some(etag),
+                    contents: fs::read_to_string(path)?,
+                })?;
+                output::status(&format!("Pulled the tags from `{}`.", url));
+            }
+            print_changes(changes);
+        },
+        _ => {
+            let remote_etag = crate::sync::head(backend.as_ref())?;
+            println!("URL:       {}", url);
+            println!("Local:     {}", match state {
+                None => "never synced",
+                Some(_) if local_changed => "changed since the last sync",
+                Some(_) => "unchanged since the last sync",
+            });
+            println!("Remote:    {}", match remote_etag {
+                None => "missing",
+                Some(ref etag) if Some(etag.as_str()) != synced_etag => {
+                    "changed since the last sync"
+                },
+                Some(_) => "unchanged since the last sync",
+            });
+
+            if let Some(state) = state.filter(|_| local_changed) {
+                let synced = tag::parse_tags(&state.contents).unwrap_or_default();
+                println!();
+                print_changes(analysis::diff(&synced, tags));
+            }
+        },
+    }
+
+    Ok(())
+}
+
+/// Runs the top command.
+pub fn top(tags: &[Tag], path: &Path, matches: &ArgMatches) -> Result<()> {
+    let count = *matches.get_one::<usize>("count").expect("default");
+    let usage = usage::get_usage(usage::get_usage_path(path))?;
+
+    let top = usage
+        .iter()
+        .filter(|(_, e)| e.count > 0)
+        .filter(|(p, _)| tag::find_tag_by_names(tags, &tag::split_tag_path(p)).is_some())
+        .sorted_by(|(_, a), (_, b)| b.count.cmp(&a.count))
+        .take(count)
+        .collect::<Vec<_>>();
+
+    if top.is_empty() {
+        output::status("No tags opened yet!");
+        return Ok(());
+    }
+
+    let width = top[0].1.count.to_string().len();
+    for (tag_path, entry) in top {
+        println!("{:>width$}    {}", entry.count, tag_path, width = width);
+    }
+
+    Ok(())
+}
+
+/// Runs the trash command.
+///
+/// If a tag was restored, the remaining trash is returned so that it can be
+/// written once the tags are saved.
+pub fn trash(tags: &mut Tags, path: &Path, matches: &ArgMatches) -> Result<Option<trash::Trash>> {
+    let trash_path = trash::get_trash_path(path);
+    let mut removed = trash::get_trash(&trash_path)?;
+
+    match matches.subcommand() {
+        Some(("restore", sub_matches)) => {
+            let i = find_trash_entry(&removed, sub_matches.get_one::<String>("entry"))?;
+            let entry = removed.remove(i);
+            let mut tag = entry.tag;
+
+            let siblings = tag::find_or_create_subtags_mut(tags, &entry.parent);
+            let taken = siblings
+                .iter()
+                .flat_map(|t| t.names.clone())
+                .collect::<Vec<_>>();
+            let name = tag::unique_name(&tag.names[0], |n| taken.iter().any(|t| t == n));
+            tag.names.retain(|n| !taken.contains(n));
+            tag.names.insert(0, name);
+            tag.names.dedup();
+
+            let mut names = entry.parent;
+            names.push(tag.names[0].clone());
+            siblings.push(tag);
+            output::status(&format!("Restored `{}`.", names.join("/")));
+
+            Ok(Some(removed))
+        },
+        Some(("empty", _)) => {
+            if removed.is_empty() {
+                output::status("The trash is empty!");
+            } else if matches.contains_id("dry-run") {
+                println!("Would remove `{}`.", trash_path.display());
+            } else if output::confirm(
+                &format!(
+                    "Permanently delete {} removed tag{}?",
+                    removed.len(),
+                    if removed.len() == 1 { "" } else { "s" }
+                ),
+                false,
+            )? {
+                trash::write_trash(&trash_path, &[])?;
+            }
+            Ok(None)
+        },
+        _ => {
+            if removed.is_empty() {
+                output::status("The trash is empty!");
+                return Ok(None);
+            }
+
+            let now = usage::now();
+            let width = removed.len().to_string().len();
+            for (n, entry) in removed.iter().rev().enumerate() {
+                let names = entry
+                    .parent
+                    .iter()
+                    .chain(std::iter::once(&entry.tag.names[0]))
+                    .join("/");
+                println!(
+                    "{:>width$}    {}    {}",
+                    n + 1,
+                    names,
+                    format_age(now.saturating_sub(entry.deleted)),
+                    width = width
+                );
+            }
+            Ok(None)
+        },
+    }
+}
+
+/// Returns the index of the trash entry with the number (as listed, most
+/// recent first) or the name, or the most recent entry if neither is given.
+fn find_trash_entry(removed: &[trash::TrashEntry], entry: Option<&String>) -> Result<usize> {
+    let not_found = || Error::new(ErrorKind::NotFound, "no removed tag found");
+    let entry = match entry {
+        Some(entry) => entry,
+        None => return removed.len().checked_sub(1).ok_or_else(not_found),
+    };
+
+    if let Ok(n) = entry.parse::<usize>() {
+        return if (1..=removed.len()).contains(&n) {
+            Ok(removed.len() - n)
+        } else {
+            Err(not_found())
+        };
+    }
+
+    let names = tag::split_tag_path(entry);
+    let (last, parents) = names.split_last().ok_or_else(not_found)?;
+    removed
+        .iter()
+        .rposition(|e| {
+            e.tag.has_name(last) && (parents.is_empty() || parents.iter().eq(e.parent.iter()))
+        })
+        .ok_or_else(not_found)
+}
+
+/// Runs the version command.
+pub fn version(tags: &[Tag], path: &Path, matches: &ArgMatches) -> Result<()> {
+    let version = env!("CARGO_PKG_VERSION");
+    let commit = env!("OPENTAG_GIT_COMMIT");
+    let build_date = env!("OPENTAG_BUILD_DATE");
+    let features = env!("OPENTAG_FEATURES");
+    let stats = analysis::stats(tags);
+
+    if matches.contains_id("json") {
+        let info = serde_json::json!({
+            "version": version,
+            "commit": commit,
+            "build_date": build_date,
+            "features": features.split_terminator(',').collect::<Vec<_>>(),
+            "data_file": path,
+            "data_format": "json",
+            "tags": stats.tags,
+        });
+        println!("{}", serde_json::to_string_pretty(&info)?);
+    } else if matches.contains_id("details") {
+        println!("Version:        {}", version);
+        println!("Commit:         {}", commit);
+        println!("Build date:     {}", build_date);
+        println!(
+            "Features:       {}",
+            if features.is_empty() {
+                "none"
+            } else {
+                features
+            }
+        );
+        println!("Data file:      {}", path.display());
+        println!("Data format:    json");
+        println!("Tags:           {}", stats.tags);
+    } else {
+        println!("opentag {}", version);
+    }
+
+    Ok(())
+}
+
+/// Runs the reorder command.
+pub fn reorder(tags: &mut Tags, matches: &ArgMatches) -> Result<()> {
+    let tags = match matches.value_of("tag") {
+        Some(path) => tag::find_subtags_by_names_mut(tags, &tag::split_tag_path(path))
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, "no tag found"))?,
+        None => tags,
+    };
+    tags.retain(|t| !t.names.is_empty());
+
+    if tags.len() < 2 {
+        return Err("there must be at least two tags to reorder".into());
+    }
+
+    let order = Sort::with_theme(&ColorfulTheme::default())
+        .with_prompt(
+            "Press `space` to pick a tag and the arrow keys to move it (press `esc` to quit)",
+        )
+        .items(&tags.iter().map(|t| &t.names[0]).collect::<Vec<_>>())
+        .interact_opt()?;
+
+    if let Some(order) = order {
+        let mut old = std::mem::take(tags)
+            .into_iter()
+            .map(Some)
+            .collect::<Vec<_>>();
+        tags.extend(order.into_iter().filter_map(|i| old[i].take()));
+    }
+
+    Ok(())
+}
+
+/// Function to change to the directory of a tag in POSIX shells.
+///
+/// The directory of the file is used for tags with a file path.
+const POSIX_CD: &str = r#"NAME() {
+    local dir
+    dir="$(command ot "$@" --print)" || return
+    if [ -f "$dir" ]; then
+        dir="$(dirname -- "$dir")"
+    fi
+    cd -- "$dir"
+}
+"#;
+
+/// Function to change to the directory of a tag in fish.
+const FISH_CD: &str = r#"function NAME
+    set -l dir (command ot $argv --print); or return
+    if test -f "$dir"
+        set dir (dirname -- "$dir")
+    end
+    cd $dir
+end
+"#;
+
+/// Completion of `ot` and the function in bash, using `ot __complete`.
+const BASH_COMPLETE: &str = r#"_ot_complete() {
+    local IFS=$'\n'
+    COMPREPLY=($(command ot __complete bash "${COMP_LINE:0:COMP_POINT}" 2>/dev/null))
+}
+complete -F _ot_complete ot NAME
+"#;
+
+/// Completion of `ot` and the function in zsh, using `ot __complete`.
+const ZSH_COMPLETE: &str = r#"_ot_complete() {
+    local -a candidates
+    candidates=("${(@f)$(command ot __complete zsh "${(j: :)words[1,CURRENT]}" 2>/dev/null)}")
+    _describe 'tag' candidates
+}
+(( $+functions[compdef] )) && compdef _ot_complete ot NAME
+"#;
+
+/// Completion of `ot` and the function in fish, using `ot __complete`.
+const FISH_COMPLETE: &str = r#"complete -c ot -c NAME -f -a '(command ot __complete fish (commandline -cp))'
+"#;
+
+/// Runs the shell-init command.
+pub fn shell_init(matches: &ArgMatches) {
+    let name = matches.value_of("name").expect("expected default value");
+    let (function, completion) = match matches.value_of("shell") {
+        Some("bash") => (POSIX_CD, BASH_COMPLETE),
+        Some("zsh") => (POSIX_CD, ZSH_COMPLETE),
+        Some("fish") => (FISH_CD, FISH_COMPLETE),
+        shell => unreachable!("unhandled shell `{:?}`", shell),
+    };
+    print!(
+        "{}\n{}",
+        function.replace("NAME", name),
+        completion.replace("NAME", name)
+    );
+}
+
+/// Runs the hidden command that prints the completions of a command line, one
+/// per line, for the completion functions printed by `shell-init`.
+///
+/// zsh and fish also get the description of each completion.
+pub fn complete(tags: &[Tag], matches: &ArgMatches) {
+    let line = matches.value_of("line").unwrap_or_default();
+    for (word, about) in app::completions(tags, line) {
+        match (matches.value_of("shell"), about) {
+            (Some("zsh"), Some(about)) => println!("{}:{}", word.replace(':', "\\:"), about),
+            (Some("zsh"), None) => println!("{}", word.replace(':', "\\:")),
+            (Some("fish"), Some(about)) => println!("{}\t{}", word, about),
+            (Some("fish"), None) => println!("{}", word),
+            // unlike zsh and fish, bash inserts the words as they are
+            _ => println!("{}", shell_escape(&word)),
+        }
+    }
+}
+
+/// Escapes the characters of the word that the shell would otherwise split
+/// or expand with backslashes, such as `Rust\ Book`.
+fn shell_escape(word: &str) -> String {
+    let mut escaped = String::with_capacity(word.len());
+    for c in word.chars() {
+        if !is_shell_word(c.encode_utf8(&mut [0; 4])) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Runs the sort command.
+pub fn sort(tags: &mut Tags, matches: &ArgMatches) -> Result<()> {
+    let recursive = matches.contains_id("recursive");
+
+    if let Some(tag_path) = matches.value_of("tag") {
+        let tag = tag::find_tag_by_names_mut(tags, &tag::split_tag_path(tag_path))
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, "no tag found"))?;
+        tag::sort_tags(&mut tag.subtags, recursive);
+    } else {
+        tag::sort_tags(tags, recursive);
+    }
+
+    Ok(())
+}
+
+/// Runs the copy command.
+///
+/// The source tag and all of its subtags are copied under the parent tag.
+pub fn copy(tags: &mut Tags, matches: &ArgMatches) -> Result<()> {
+    let src_names = tag::split_tag_path(matches.value_of("source").expect("required"));
+    let parent_names = tag::split_tag_path(matches.value_of("parent").expect("required"));
+
+    let mut copy = tag::find_tag_by_names_mut(tags, &src_names)
+        .ok_or_else(|| Error::new(ErrorKind::NotFound, "source tag not found"))?
+        .clone();
+    if let Some(name) = matches.value_of("name") {
+        copy.names = vec![name.to_string()];
+    }
+    copy.created = Some(usage::now());
+    copy.modified = copy.created;
+
+    let siblings = tag::find_subtags_by_names_mut(tags, &parent_names)
+        .ok_or_else(|| Error::new(ErrorKind::NotFound, "parent tag not found"))?;
+    for name in &copy.names {
+        if siblings.iter().flat_map(|t| &t.names).contains(name) {
+            return Err(format!("a tag with name `{}` already exists", name).into());
+        }
+    }
+    siblings.push(copy);
+
+    Ok(())
+}
+
+/// Runs the merge command.
+///
+/// The names, subtags, and any missing fields of the source tag are moved to
+/// the destination tag and the source tag is removed.
+pub fn merge(tags: &mut Tags, matches: &ArgMatches) -> Result<()> {
+    let src_names = tag::split_tag_path(matches.value_of("source").expect("required"));
+    let dest_names = tag::split_tag_path(matches.value_of("destination").expect("required"));
+    let rename = matches.contains_id("rename-conflicts");
+
+    if dest_names.starts_with(&src_names) {
+        return Err("cannot merge a tag into itself or its subtag".into());
+    }
+
+    let mut src = tag::remove_tag_by_names(tags, &src_names)
+        .ok_or_else(|| Error::new(ErrorKind::NotFound, "source tag not found"))?;
+
+    let (dest_name, dest_parents) = dest_names
+        .split_last()
+        .ok_or_else(|| Error::new(ErrorKind::NotFound, "destination tag not found"))?;
+    let siblings = tag::find_subtags_by_names_mut(tags, dest_parents)
+        .ok_or_else(|| Error::new(ErrorKind::NotFound, "destination tag not found"))?;
+    let i = siblings
+        .iter()
+        .position(|t| t.has_name(dest_name))
+        .ok_or_else(|| Error::new(ErrorKind::NotFound, "destination tag not found"))?;
+
+    let mut taken: Vec<_> = siblings
+        .iter()
+        .enumerate()
+        .filter(|(j, _)| *j != i)
+        .flat_map(|(_, t)| t.names.clone())
+        .collect();
+    let mut dest_names = siblings[i].names.clone();
+    for name in std::mem::take(&mut src.names) {
+        if dest_names.contains(&name) {
+            continue;
+        }
+        let name = resolve_conflict(name, &taken, rename)?;
+        taken.push(name.clone());
+        dest_names.push(name);
+    }
+
+    let dest = &mut siblings[i];
+    dest.names = dest_names;
+    merge_into(dest, src, rename)
+}
+
+/// Moves the subtags and any missing fields of the source tag to the
+/// destination tag, keeping the names of the destination tag.
+///
+/// Subtags whose names are taken under the destination tag are numbered if
+/// `rename` is `true`, and are an error otherwise.
+fn merge_into(dest: &mut Tag, src: Tag, rename: bool) -> Result<()> {
+    dest.path = dest.path.take().or(src.path);
+    for path in src.fallback_paths {
+        if !dest.fallback_paths.contains(&path) {
+            dest.fallback_paths.push(path);
+        }
+    }
+    dest.query_param = dest.query_param.take().or(src.query_param);
+    dest.icon = dest.icon.take().or(src.icon);
+    dest.color = dest.color.take().or(src.color);
+    dest.about = dest.about.take().or(src.about);
+    dest.notes = dest.notes.take().or(src.notes);
+    dest.secret = dest.secret.take().or(src.secret);
+    dest.app = dest.app.take().or(src.app);
+    dest.background = dest.background.or(src.background);
+    dest.cwd = dest.cwd.take().or(src.cwd);
+    dest.review_after = dest.review_after.take().or(src.review_after);
+    for (key, value) in src.env {
+        dest.env.entry(key).or_insert(value);
+    }
+    for label in src.labels {
+        if !dest.labels.contains(&label) {
+            dest.labels.push(label);
+        }
+    }
+    for (key, value) in src.extra {
+        dest.extra.entry(key).or_insert(value);
+    }
+    dest.modified = Some(usage::now());
+
+    for mut subtag in src.subtags {
+        let taken: Vec<_> = dest.subtags.iter().flat_map(|t| t.names.clone()).collect();
+        subtag.names = subtag
+            .names
+            .into_iter()
+            .map(|n| resolve_conflict(n, &taken, rename))
+            .collect::<Result<_>>()?;
+        dest.subtags.push(subtag);
+    }
+
+    Ok(())
+}
+
+/// Returns the name if it is not taken, or a numbered variant of it if
+/// `rename` is `true`.
+fn resolve_conflict(name: String, taken: &[String], rename: bool) -> Result<String> {
+    if !taken.contains(&name) {
+        Ok(name)
+    } else if rename {
+        Ok(tag::unique_name(&name, |n| taken.iter().any(|t| t == n)))
+    } else {
+        Err(format!("a tag with name `{}` already exists", name).into())
+    }
+}
+
+/// What is done with a tag added by `import` or `apply` whose names are taken
+/// by another tag at its level.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OnConflict {
+    /// Ask for each tag, or fail if the input is not a terminal.
+    Ask,
+    /// Fail without adding any tag.
+    Fail,
+    /// Leave the tag out.
+    Skip,
+    /// Add the tag with a numbered name.
+    Rename,
+    /// Merge the tag into the existing tag, as with `ot merge`.
+    Merge,
+    /// Replace the existing tag with the tag.
+    Overwrite,
+}
+
+impl OnConflict {
+    /// Returns the action given with `--on-conflict`, or [`OnConflict::Ask`].
+    pub fn from_matches(matches: &ArgMatches) -> Self {
+        match matches.value_of("on-conflict") {
+            Some("fail") => Self::Fail,
+            Some("skip") => Self::Skip,
+            Some("rename") => Self::Rename,
+            Some("merge") => Self::Merge,
+            Some("overwrite") => Self::Overwrite,
+            _ => Self::Ask,
+        }
+    }
+}
+
+/// How a tag was added by [`add_resolving`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Resolved {
+    /// The tag was added, possibly renamed or replacing another tag.
+    Added,
+    /// The tag was left out.
+    Skipped,
+    /// The tag was merged into an existing tag.
+    Merged,
+}
+
+/// Adds the tag to the siblings, under the parent with the tag path `parent`,
+/// resolving a conflict with a sibling that has one of its names as
+/// `on_conflict` says.
+///
+/// When asked, the user may also skip all the remaining conflicts, which
+/// changes `on_conflict` for the next tags.
+pub fn add_resolving(
+    siblings: &mut Tags,
+    mut tag: Tag,
+    parent: &str,
+    on_conflict: &mut OnConflict,
+) -> Result<Resolved> {
+    let (i, name) = match siblings.iter().enumerate().find_map(|(i, s)| {
+        tag.names
+            .iter()
+            .find(|n| s.has_name(n))
+            .map(|n| (i, n.clone()))
+    }) {
+        Some(conflict) => conflict,
+        None => {
+            siblings.push(tag);
+            return Ok(Resolved::Added);
+        },
+    };
+
+    let asked =
+        *on_conflict == OnConflict::Ask && !output::assume_yes() && console::user_attended_stderr();
+    let action = if asked {
+        let location = match parent {
+            "" => format!("`{}` already exists", name),
+            parent => format!("`{}` already exists under `{}`", name, parent),
+        };
+        let choice = dialoguer::Select::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!("{} (press `esc` to cancel)", location))
+            .items(&[
+                "Skip it",
+                "Rename it",
+                "Merge it into the existing tag",
+                "Overwrite the existing tag",
+                "Skip it and every other conflict",
+            ])
+            .default(0)
+            .interact_opt()?;
+        match choice {
+            Some(0) => OnConflict::Skip,
+            Some(1) => OnConflict::Rename,
+            Some(2) => OnConflict::Merge,
+            Some(3) => OnConflict::Overwrite,
+            Some(_) => {
+                *on_conflict = OnConflict::Skip;
+                OnConflict::Skip
+            },
+            None => return Err(Error::new(ErrorKind::Abort, "the changes were discarded")),
+        }
+    } else {
+        *on_conflict
+    };
+
+    match action {
+        OnConflict::Ask | OnConflict::Fail => {
+            Err(format!("a tag with name `{}` already exists", name).into())
+        },
+        OnConflict::Skip => Ok(Resolved::Skipped),
+        OnConflict::Rename => {
+            let taken = siblings
+                .iter()
+                .flat_map(|t| t.names.clone())
+                .collect::<Vec<_>>();
+            let unique = tag::unique_name(&name, |n| taken.iter().any(|t| t == n));
+            let new_name = if asked {
+                Input::<String>::with_theme(&ColorfulTheme::default())
+                    .with_prompt("New name")
+                    .with_initial_text(unique)
+                    .validate_with(|n: &String| {
+                        if n.trim().is_empty() {
+                            Err("the name cannot be empty")
+                        } else if taken.iter().any(|t| t == n.trim()) {
+                            Err("a tag with this name already exists")
+                        } else {
+                            Ok(())
+                        }
+                    })
+                    .interact_text()?
+            } else {
+                unique
+            };
+            tag.names.retain(|n| !taken.contains(n));
+            tag.names.insert(0, tag::normalize_name(new_name.trim()));
+            tag.names.dedup();
+            siblings.push(tag);
+            Ok(Resolved::Added)
+        },
+        OnConflict::Merge => {
+            merge_into(&mut siblings[i], tag, true)?;
+            Ok(Resolved::Merged)
+        },
+        OnConflict::Overwrite => {
+            siblings.retain(|s| !tag.names.iter().any(|n| s.has_name(n)));
+            siblings.push(tag);
+            Ok(Resolved::Added)
+        },
+    }
+}
+
+/// Options of a tag invocation.
+#[derive(Clone, Debug, Default)]
+pub struct MatchOptions<'a> {
+    /// List the subtags instead of opening the tag.
+    pub list: bool,
+    /// Print the path instead of opening it.
+    pub print: bool,
+    /// Print the path terminated by a NUL character instead of opening it.
+    pub print0: bool,
+    /// Print the path quoted for the shell instead of opening it.
+    pub print_quoted: bool,
+    /// Print the expanded paths of the tag and its subtags instead of opening
+    /// it.
+    pub paths: bool,
+    /// Copy the path to the clipboard.
+    pub copy: bool,
+    /// Copy the path to the clipboard without opening it.
+    pub silent_copy: bool,
+    /// Copy the description to the clipboard instead of opening the path.
+    pub copy_about: bool,
+    /// Print the notes instead of opening the path.
+    pub notes: bool,
+    /// Print the details of the tag instead of opening the path.
+    pub info: bool,
+    /// The app to open the path with.
+    pub app: Option<&'a str>,
+    /// Open the path without bringing the app to the foreground.
+    pub background: bool,
+    /// The working directory of the app instead of the tag's.
+    pub cwd: Option<&'a str>,
+    /// The line to open a file at, or the fragment to open a URL at.
+    pub at: Option<&'a str>,
+    /// The words to search for with the URL of the tag, given after it.
+    pub query: Vec<&'a str>,
+    /// Wait for the app to exit and exit with its exit code.
+    pub wait: bool,
+    /// Open a random subtag instead of the tag.
+    pub random: bool,
+    /// Restricts which subtags are listed.
+    pub filter: ListFilter<'a>,
+    /// List the subtags of the listed tags as well.
+    pub recursive: bool,
+    /// The number of levels of subtags to list.
+    pub depth: Option<usize>,
+    /// The template to list or print the tags with.
+    pub format: Option<&'a str>,
+    /// Print the listing directly instead of through the pager.
+    pub no_pager: bool,
+    /// Print the number of listed tags instead of listing them.
+    pub count: bool,
+    /// The order the tags are listed in at each level, instead of the order
+    /// in the tags file.
+    pub sort: Option<&'a str>,
+    /// What the listed tags are grouped by.
+    pub group_by: Option<&'a str>,
+    /// The usage data the tags are sorted by with `--sort usage`.
+    pub usage: usage::Usage,
+    /// The link format to copy the path in instead of the bare path.
+    pub copy_format: Option<&'a str>,
+    /// The clipboard to copy the path to instead of the configured one.
+    pub clipboard: Option<ClipboardKind>,
+    /// Print what would be opened or copied instead of doing it.
+    pub dry_run: bool,
+    /// Print the name, the path, the app, and the action as a JSON object.
+    pub json: bool,
+}
+
+impl<'a> MatchOptions<'a> {
+    /// Returns the options given in the matches, along with the configured
+    /// `defaults` that the matches do not override, unless `--no-defaults` is
+    /// given.
+    pub fn from_matches(matches: &'a ArgMatches, defaults: &'a Defaults) -> Self {
+        let mut options = Self {
+            list: matches.contains_id("list"),
+            print: matches.contains_id("print"),
+            print0: matches.contains_id("print0"),
+            print_quoted: matches.contains_id("print-quoted"),
+            paths: matches.contains_id("paths"),
+            copy: matches.contains_id("copy"),
+            silent_copy: matches.contains_id("silent-copy"),
+            copy_about: matches.contains_id("copy-about"),
+            notes: matches.contains_id("notes"),
+            info: matches.contains_id("info"),
+            app: matches.value_of("app"),
+            background: matches.contains_id("background"),
+            cwd: matches.value_of("cwd"),
+            at: matches.value_of("at"),
+            query: Vec::new(),
+            wait: matches.contains_id("wait"),
+            random: matches.contains_id("random"),
+            filter: ListFilter {
+                name: matches.value_of("filter"),
+                path: matches.value_of("filter-path"),
+                has_path: if matches.contains_id("has-path") {
+                    Some(true)
+                } else if matches.contains_id("no-path") {
+                    Some(false)
+                } else {
+                    None
+                },
+                labels: matches
+                    .values_of("label")
+                    .map(Iterator::collect)
+                    .unwrap_or_default(),
+            },
+            recursive: matches.contains_id("recursive"),
+            depth: matches.get_one::<usize>("depth").copied(),
+            format: matches.value_of("format"),
+            no_pager: matches.contains_id("no-pager"),
+            count: matches.contains_id("count"),
+            sort: matches.value_of("sort"),
+            group_by: matches.value_of("group-by"),
+            usage: usage::Usage::new(),
+            copy_format: matches.value_of("copy-format"),
+            clipboard: if matches.contains_id("osc52") {
+                Some(ClipboardKind::Osc52)
+            } else if matches.contains_id("primary") {
+                Some(ClipboardKind::Primary)
+            } else {
+                None
+            },
+            dry_run: matches.contains_id("dry-run"),
+            json: matches.value_of("output") == Some("json"),
+        };
+        if !matches.contains_id("no-defaults") {
+            options.apply_defaults(defaults);
+        }
+        options
+    }
+
+    /// Applies the configured defaults that these options do not override.
+    ///
+    /// What is done with an invoked tag, such as copying it, is only defaulted
+    /// if no other action is given, and list options only apply to listings.
+    fn apply_defaults(&mut self, defaults: &'a Defaults) {
+        let acts = self.list
+            || self.print
+            || self.print0
+            || self.print_quoted
+            || self.paths
+            || self.copy
+            || self.silent_copy
+            || self.copy_about
+            || self.notes
+            || self.info;
+        if !acts {
+            self.copy = defaults.copy;
+            self.silent_copy = defaults.silent_copy;
+            self.print = defaults.print;
+        }
+        self.wait |= defaults.wait && !self.background;
+
+        if self.list {
+            let list = &defaults.list;
+            self.recursive |= list.recursive;
+            self.depth = self.depth.or(list.depth);
+            self.sort = self.sort.or(list.sort.map(SortOrder::as_str));
+            if self.format.is_none() && !self.count {
+                self.group_by = self.group_by.or(list.group_by.map(GroupBy::as_str));
+            }
+            if self.group_by.is_none() && !self.count {
+                self.format = self.format.or(list.format.as_deref());
+            }
+            self.no_pager |= list.no_pager;
+        }
+    }
+
+    /// Loads the usage data next to the tags file at the path, if the tags are
+    /// listed by usage.
+    pub fn load_usage(&mut self, path: &Path) {
+        if self.sort == Some("usage") {
+            self.usage = usage::get_usage(usage::get_usage_path(path)).unwrap_or_else(|e| {
+                log::warn!("unable to read the usage data: {}", e);
+                usage::Usage::new()
+            });
+        }
+    }
+
+    /// Returns what is done with an invoked tag with these options, as
+    /// recorded in the history.
+    pub fn action(&self) -> &'static str {
+        if self.print || self.print0 || self.print_quoted || self.paths {
+            "print"
+        } else if self.notes {
+            "notes"
+        } else if self.silent_copy || self.copy_about {
+            "copy"
+        } else {
+            "open"
+        }
+    }
+}
+
+/// Runs the command for the given tag, with the path of `names`.
+///
+/// A tag without an app of its own is opened with `ancestor_app`, the app of
+/// its nearest ancestor that has one.
+///
+/// Returns the exit code of the app the tag was opened with if `--wait` is
+/// given, or `0` otherwise.
+pub fn run_tag(
+    tag: &Tag,
+    names: &[String],
+    ancestor_app: Option<&App>,
+    options: &MatchOptions,
+    config: &Config,
+) -> Result<i32> {
+    if options.list {
+        list(&tag.subtags, names, options, config)?;
+        return Ok(0);
+    }
+
+    if options.paths {
+        print_paths(tag, true, options.recursive, config.root.as_deref());
+        return Ok(0);
+    }
+
+    if options.copy_about {
+        let about = tag.about.as_deref().ok_or("tag has no description")?;
+        copy_text(about.to_string(), options, config)?;
+        return Ok(0);
+    }
+
+    if options.notes {
+        let notes = tag.notes.as_deref().ok_or("tag has no notes")?;
+        output::page(&format!("{}\n", notes), options.no_pager)?;
+        return Ok(0);
+    }
+
+    let resolved = tag_target(tag, config.root.as_deref()).ok_or("tag has no path or url")?;
+    let path = resolved.as_ref();
+    if Some(path) != tag.path() {
+        log::debug!(
+            "resolved `{}` to `{}`",
+            tag.path().unwrap_or_default(),
+            path
+        );
+    }
+
+    // words given after a search tag are passed in the query of its URL
+    let searched;
+    let path = match (options.query.is_empty(), tag.query_param.as_deref()) {
+        (true, _) => path,
+        (false, Some(param)) if analysis::is_url(path) => {
+            searched = analysis::with_query(path, param, &options.query);
+            searched.as_str()
+        },
+        (false, _) => {
+            return Err(Error::new(
+                ErrorKind::Usage,
+                format!(
+                    "the tag `{}` has no url with a `query_param` to search for the words with",
+                    names.join("/")
+                ),
+            ))
+        },
+    };
+
+    // a URL is opened at the fragment, and a file at the line in its editor
+    let mut line = None;
+    let anchored;
+    let path = match options.at {
+        Some(at) if analysis::is_url(path) => {
+            let base = path.split_once('#').map_or(path, |(base, _)| base);
+            anchored = format!("{}#{}", base, at.trim_start_matches('#'));
+            anchored.as_str()
+        },
+        Some(at) => {
+            let number = at.parse::<u32>().ok().filter(|&n| n > 0).ok_or_else(|| {
+                Error::new(
+                    ErrorKind::Usage,
+                    "`--at` takes a line number for files, such as `--at 120`",
+                )
+            })?;
+            line = Some(number);
+            path
+        },
+        None => path,
+    };
+
+    if options.copy_format.is_some() && !options.copy && !options.silent_copy {
+        return Err("`--copy-format` requires `--copy` or `--silent-copy`".into());
+    }
+
+    if options.copy || options.silent_copy {
+        let text = match options.copy_format {
+            Some(format) => output::format_link(format, &tag.names[0], path),
+            None => path.to_string(),
+        };
+        copy_text(text, options, config)?;
+    }
+
+    let app = options.app.map(App::parse);
+    let app = app
+        .as_ref()
+        .and_then(App::current)
+        .or_else(|| tag.app.as_ref().or(ancestor_app).and_then(App::current))
+        .or_else(|| config.app_for(path).and_then(App::current));
+
+    if options.print0 {
+        print!("{}\0", path);
+    } else if options.print_quoted {
+        println!("{}", output::shell_quote(path));
+    } else if options.print && options.json {
+        print_target(tag, path, app, "print")?;
+    } else if options.print {
+        match options.format {
+            Some(format) => println!("{}", output::format_tag(format, tag)),
+            None => println!("{}", path),
+        }
+    } else if !options.silent_copy {
+        let cwd = options
+            .cwd
+            .or(tag.cwd.as_deref())
+            .map(analysis::expand_path);
+        let env = tag
+            .env
+            .iter()
+            .map(|(k, v)| (k.clone(), analysis::expand_path(v).into_owned()))
+            .collect::<Vec<_>>();
+        let open_options = OpenOptions {
+            app,
+            background: options.background || tag.background.unwrap_or(config.background),
+            cwd: cwd.as_deref().map(Path::new),
+            env: &env,
+            wait: options.wait,
+            line,
+            dry_run: options.dry_run,
+        };
+        let code = opener::open(path, open_options, config).map_err(|e| {
+            Error::new(ErrorKind::Open, format!("unable to open `{}`: {}", path, e))
+        })?;
+        timings::mark("launch");
+        if options.json {
+            print_target(tag, path, app, "open")?;
+        }
+        return Ok(code);
+    } else if options.json {
+        print_target(tag, path, app, "copy")?;
+    }
+
+    Ok(0)
+}
+
+/// What was done with an invoked tag, printed with `--output json`.
+#[derive(Serialize)]
+struct Target<'a> {
+    name: &'a str,
+    path: &'a str,
+    app: Option<&'a App>,
+    action: &'a str,
+}
+
+/// Prints the name, the path, the app, and the action taken on the tag as a
+/// JSON object on one line, for wrappers such as launcher scripts.
+fn print_target(tag: &Tag, path: &str, app: Option<&App>, action: &str) -> Result<()> {
+    let target = Target {
+        name: &tag.names[0],
+        path,
+        app,
+        action,
+    };
+    println!("{}", serde_json::to_string(&target)?);
+    Ok(())
+}
 
-use crate::error::Result;
-use crate::tag::{command_from_tag, Tags};
-use crate::Tag;
-
-/// Runs the command for the given tag.
-pub fn run_tag(tag: &Tag, matches: &ArgMatches) -> Result<()> {
-    if matches.contains_id("list") {
-        // TODO: This is a terrible hack. Write own implementation.
-        if !tag.subtags.is_empty() {
-            let mut app = Command::new("list-subcommands")
-                .subcommands(tag.subtags.iter().map(command_from_tag))
-                .disable_help_subcommand(true)
-                .help_template("TAGS\n{subcommands}");
-            app.print_help()?;
-        } else {
-            println!("No tags!");
-        }
+/// Records that the tag with the tag path was opened now, in the usage data
+/// and in the history along with what was done with it.
+///
+/// The targets of secret tags are not written in the history.
+pub fn record_open(
+    tags: &[Tag],
+    path: &Path,
+    tag_path: &str,
+    action: &str,
+    config: &Config,
+) -> Result<()> {
+    usage::record_open(usage::get_usage_path(path), tag_path)?;
+
+    let target = tag::find_tag_by_names(tags, &tag::split_tag_path(tag_path))
+        .filter(|t| t.secret.is_none())
+        .and_then(|t| tag_target(t, config.root.as_deref()))
+        .map(Cow::into_owned);
+    let entry = HistoryEntry {
+        time: usage::now(),
+        tag: tag_path.to_string(),
+        target,
+        action: action.to_string(),
+    };
+    history::record(history::get_history_path(path), &entry)
+}
+
+/// Checks the path or the URL of a tag being added.
+///
+/// A warning is printed if it looks wrong, or an error is returned with
+/// `--strict`. Nothing is checked with `--force`.
+fn check_new_path(path: &str, matches: &ArgMatches) -> Result<()> {
+    if matches.contains_id("force") {
         return Ok(());
     }
 
-    let cow;
-    let path = if let Some(ref path) = tag.path {
-        if path.starts_with('~') {
-            cow = shellexpand::tilde(path);
-            cow.as_ref()
-        } else {
-            path.as_ref()
-        }
-    } else {
-        return Err("tag has no path or url".into());
-    };
+    match analysis::path_problem(path) {
+        Some(problem) if matches.contains_id("strict") => Err(Error::new(
+            ErrorKind::Data,
+            format!("{}; use `--force` to add it anyway", problem),
+        )),
+        Some(problem) => {
+            output::warn(&problem);
+            Ok(())
+        },
+        None => Ok(()),
+    }
+}
 
-    let silent_copy = matches.contains_id("silent-copy");
+/// Returns the labels given in the matches for new or updated tags.
+///
+/// Empty labels are ignored, so that `--label ""` removes the labels of a tag.
+fn new_labels(matches: &ArgMatches) -> Vec<String> {
+    matches
+        .values_of("label")
+        .map(|l| {
+            l.map(str::trim)
+                .filter(|l| !l.is_empty())
+                .map(ToString::to_string)
+                .unique()
+                .collect()
+        })
+        .unwrap_or_default()
+}
 
-    if matches.contains_id("copy") || silent_copy {
-        let mut clipboard = Clipboard::new()?;
-        clipboard.set_text(path.to_string())?;
+/// Copies the text to the clipboard chosen in the options or the config.
+///
+/// The text is printed instead in a dry run. If the clipboard cannot be used,
+/// such as in a bare TTY, the text is copied with OSC 52 if the config enables
+/// it or printed with a warning otherwise, so that the tag is still opened.
+fn copy_text(text: String, options: &MatchOptions, config: &Config) -> Result<()> {
+    let kind = options.clipboard.unwrap_or(config.clipboard);
+    if options.dry_run {
+        let target = match kind {
+            ClipboardKind::System => "the clipboard",
+            ClipboardKind::Osc52 => "the terminal clipboard",
+            ClipboardKind::Primary => "the primary selection",
+        };
+        println!("Would copy `{}` to {}.", text, target);
+        return Ok(());
     }
 
-    if matches.contains_id("print") {
-        println!("{}", path);
-    } else if !silent_copy {
-        if let Some(app) = matches.value_of("app").or(tag.app.as_deref()) {
-            open::with(path, app)
-        } else {
-            open::that(path)
+    let err = match clipboard::copy(text.clone(), kind) {
+        Ok(()) => return Ok(()),
+        Err(err) if kind == ClipboardKind::Osc52 => return Err(err),
+        Err(err) => err,
+    };
+
+    if config.osc52_fallback {
+        match clipboard::copy(text.clone(), ClipboardKind::Osc52) {
+            Ok(()) => {
+                output::warn(&format!(
+                    "unable to copy: {}; copied with OSC 52 instead",
+                    err
+                ));
+                return Ok(());
+            },
+            Err(e) => log::warn!("unable to copy with OSC 52: {}", e),
         }
-        .map_err(|e| format!("unable to open `{}`: {}", path, e))?;
     }
 
+    output::warn(&format!("unable to copy: {}", err));
+    println!("{}", text);
     Ok(())
 }
 
-/// Prompts user to recursively select a tag.
-fn select_tag<'a>(
-    tags: &'a mut Tags,
+/// Returns the preview of the tag shown in the interactive pickers.
+///
+/// It has the path, the first line of the description, and the number of
+/// subtags of the tag.
+fn tag_preview(tag: &Tag) -> String {
+    let mut preview = match tag.path() {
+        Some(path) => path.to_string(),
+        None if tag.secret.is_some() => "(secret)".to_string(),
+        None => "(no path or URL)".to_string(),
+    };
+    if let Some(about) = tag.about.as_deref().and_then(|a| a.lines().next()) {
+        preview.push('\n');
+        preview.push_str(about);
+    }
+    match tag.subtags.len() {
+        0 => {},
+        1 => preview.push_str("\n1 subtag"),
+        n => preview.push_str(&format!("\n{} subtags", n)),
+    }
+    preview
+}
+
+/// Prompts user to recursively select a tag, returning the indices of the
+/// selected tag and of its parents among their siblings, root first, as used
+/// by [`tag::tag_at_mut`].
+///
+/// Pinned tags are shown first, then the tags most recently opened or
+/// selected, with their subtags, as recorded in the usage file next to the
+/// tags file at `path`. The selected tag is recorded in it, and its ancestry
+/// is printed so that tags with the same name in different places are told
+/// apart.
+fn select_tag(
+    tags: &[Tag],
+    path: &Path,
     prompt: &str,
     rec_prompt: &str,
-) -> Result<Option<&'a mut Tag>> {
-    if let Some(i) = FuzzySelect::with_theme(&ColorfulTheme::default())
-        .with_prompt(prompt)
-        .items(
-            &tags
-                .iter()
-                .map(|t| t.names.first().expect("tag has no name"))
-                .collect::<Vec<_>>(),
-        )
+    config: &Config,
+) -> Result<Option<Vec<usize>>> {
+    let usage_path = usage::get_usage_path(path);
+    let usage = usage::get_usage(&usage_path).unwrap_or_else(|e| {
+        log::warn!("unable to read the usage data: {}", e);
+        usage::Usage::new()
+    });
+
+    let mut indices = Vec::new();
+    let mut names = Vec::new();
+    select_tag_in(
+        tags,
+        &mut indices,
+        &mut names,
+        prompt,
+        rec_prompt,
+        &usage,
+        config,
+    )?;
+    if let Some(tag) = tag::tag_at(tags, &indices) {
+        usage::record_select(&usage_path, &names.join("/"))?;
+        if !output::is_quiet() {
+            eprintln!("Selected {}", breadcrumb(&names, tag));
+        }
+    }
+    Ok(Some(indices).filter(|i| !i.is_empty()))
+}
+
+/// Returns the names of the tag and its parents, root first, separated by `>`,
+/// followed by the number of subtags of the tag if it has any, such as
+/// `work > projects > api (3 subtags)`.
+fn breadcrumb(names: &[String], tag: &Tag) -> String {
+    let mut breadcrumb = names.join(" > ");
+    match tag.subtags.len() {
+        0 => {},
+        1 => breadcrumb.push_str(" (1 subtag)"),
+        n => breadcrumb.push_str(&format!(" ({} subtags)", n)),
+    }
+    breadcrumb
+}
+
+/// Prompts user to recursively select a tag among the tags, whose parents
+/// have the primary `names`, pushing the indices and the names of the
+/// selected tags.
+fn select_tag_in(
+    tags: &[Tag],
+    indices: &mut Vec<usize>,
+    names: &mut Vec<String>,
+    prompt: &str,
+    rec_prompt: &str,
+    usage: &usage::Usage,
+    config: &Config,
+) -> Result<()> {
+    let last_used = tags
+        .iter()
+        .map(|t| {
+            names.push(t.names[0].clone());
+            let last_used = usage::last_used(usage, &names.join("/"));
+            names.pop();
+            last_used
+        })
+        .collect::<Vec<_>>();
+    let order = (0..tags.len())
+        .sorted_by_key(|&i| (!tags[i].pinned, Reverse(last_used[i])))
+        .collect::<Vec<_>>();
+    let display_names = order
+        .iter()
+        .map(|&i| tag::display_name(&tags[i]))
+        .collect::<Vec<_>>();
+
+    let previews = order
+        .iter()
+        .map(|&i| tag_preview(&tags[i]))
+        .collect::<Vec<_>>();
+
+    if let Some(i) = Picker::new(prompt, &display_names, &config.fuzzy)
+        .with_previews(&previews)
         .interact_opt()?
     {
-        let tag_ptr = tags.get_mut(i).expect("expected index in bounds") as *mut Tag;
-        // SAFETY: `tag_ptr` is not mutated in this function and is valid
-        let tag = unsafe { &mut *tag_ptr };
-        if !tag.subtags.is_empty() {
-            if let Some(t) = select_tag(&mut tag.subtags, rec_prompt, rec_prompt)? {
-                return Ok(Some(t));
-            }
+        let i = order[i];
+        indices.push(i);
+        names.push(tags[i].names[0].clone());
+        if !tags[i].subtags.is_empty() {
+            select_tag_in(
+                &tags[i].subtags,
+                indices,
+                names,
+                rec_prompt,
+                rec_prompt,
+                usage,
+                config,
+            )?;
         }
-        // SAFETY: `tag_ptr` is not mutated in this function and is valid
-        return Ok(Some(unsafe { &mut *tag_ptr }));
     }
 
-    Ok(None)
+    Ok(())
+}
+
+/// Returns the resolved path of the tag, or the first of its fallback paths
+/// that is available if the path is not, as with [`analysis::is_available`].
+///
+/// If none of them is available, the first one is returned so that opening it
+/// reports why.
+fn tag_target<'a>(tag: &'a Tag, root: Option<&str>) -> Option<Cow<'a, str>> {
+    let mut candidates = tag
+        .path()
+        .into_iter()
+        .chain(tag.fallback_paths.iter().map(String::as_str))
+        .map(|p| analysis::resolve_path(p, root));
+    let first = candidates.next()?;
+    if analysis::is_available(&first) {
+        return Some(first);
+    }
+
+    match candidates.find(|p| analysis::is_available(p)) {
+        Some(fallback) => {
+            log::info!("`{}` is not available, using `{}`", first, fallback);
+            Some(fallback)
+        },
+        None => Some(first),
+    }
+}
+
+/// Prints the resolved path of the tag, if it has one, and those of its
+/// subtags, one per line.
+///
+/// Only the direct subtags are printed unless `recursive` is `true`. Secret
+/// tags are skipped, as their paths are encrypted.
+fn print_paths(tag: &Tag, subtags: bool, recursive: bool, root: Option<&str>) {
+    if let Some(path) = tag.path() {
+        println!("{}", analysis::resolve_path(path, root));
+    }
+    if subtags {
+        for subtag in &tag.subtags {
+            print_paths(subtag, recursive, recursive, root);
+        }
+    }
 }
 
 /// Runs the add command.
-pub fn add(tags: &mut Tags) -> Result<()> {
+pub fn add(tags: &mut Tags, path: &Path, matches: &ArgMatches, config: &Config) -> Result<()> {
     let names: Vec<_> = Input::<String>::new()
         .with_prompt("Enter tag name and aliases (comma-separated; at least one)")
         .interact_text()?
@@ -97,12 +3164,16 @@ pub fn add(tags: &mut Tags) -> Result<()> {
         .map(|s| s.trim().to_string())
         .collect();
 
-    let subtags = if let Some(t) = select_tag(
+    let subtags = if let Some(indices) = select_tag(
         tags,
+        path,
         "Select the parent tag (press `esc` for no parent)",
         "Select a subtag of the parent (press `esc` to select the parent)",
+        config,
     )? {
-        &mut t.subtags
+        &mut tag::tag_at_mut(tags, &indices)
+            .expect("selected tag is in the tags")
+            .subtags
     } else {
         tags
     };
@@ -123,47 +3194,751 @@ pub fn add(tags: &mut Tags) -> Result<()> {
     };
 
     let path = get_optional("Enter path or url, press enter to skip")?;
+    if let Some(ref path) = path {
+        check_new_path(path, matches)?;
+    }
     let about = get_optional("Enter info about the tag, press enter to skip")?;
     let default_application =
         get_optional("Enter name of default app to open the tag, press enter to skip")?;
 
-    subtags.push(Tag {
+    let now = usage::now();
+    let mut tag = Tag {
         names,
-        path,
+        path: path.map(TagPath::from),
         about,
-        app: default_application,
+        app: default_application.as_deref().map(App::parse),
+        labels: new_labels(matches),
+        created: Some(now),
+        modified: Some(now),
+        ..Default::default()
+    };
+    if matches.contains_id("secret") {
+        secret::encrypt(&mut tag, &config.secrets)?;
+    }
+    subtags.push(tag);
+
+    Ok(())
+}
+
+/// Runs the apply command, returning `true` if the tags changed.
+///
+/// The operations are applied to the tags in memory and checked as a whole,
+/// so that the tags file is written once or not at all. With `--dry-run`, the
+/// changes are printed instead.
+pub fn apply(tags: &mut Tags, config: &Config, matches: &ArgMatches) -> Result<bool> {
+    let file = matches.value_of("file").expect("required");
+    let (text, toml) = if file == "-" {
+        (io::read_to_string(io::stdin())?, false)
+    } else {
+        let path = analysis::expand_path(file);
+        let extension = Path::new(path.as_ref())
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_ascii_lowercase);
+        if matches!(extension.as_deref(), Some("yaml" | "yml")) {
+            return Err(Error::new(
+                ErrorKind::Usage,
+                "YAML is not supported; write the operations as JSON or TOML",
+            ));
+        }
+        let text = fs::read_to_string(path.as_ref())
+            .map_err(|e| Error::new(ErrorKind::Io, format!("unable to read `{}`: {}", path, e)))?;
+        (text, extension.as_deref() == Some("toml"))
+    };
+
+    let ops = ops::parse(&text, toml)?;
+    let count = ops.len();
+    let old = tags.clone();
+    let mut on_conflict = OnConflict::from_matches(matches);
+    let (mut skipped, mut merged) = (0, 0);
+    ops::apply(tags, ops, |siblings, tag, parent| {
+        match add_resolving(siblings, tag, parent, &mut on_conflict)? {
+            Resolved::Added => {},
+            Resolved::Skipped => skipped += 1,
+            Resolved::Merged => merged += 1,
+        }
+        Ok(())
+    })?;
+    tag::validate_tags(tags)?;
+    check_unique_names(&old, tags, config)?;
+
+    if matches.contains_id("dry-run") {
+        print_changes(analysis::diff(&old, tags));
+        return Ok(false);
+    }
+
+    match count {
+        1 => output::status("Applied 1 operation."),
+        n => output::status(&format!("Applied {} operations.", n)),
+    }
+    print_resolved(skipped, merged);
+    Ok(count > 0)
+}
+
+/// Runs the audit-urls command.
+///
+/// The `http` and `https` URLs of the tags are checked, and those that are
+/// broken, redirect, time out, or cannot be reached are listed. The user is
+/// offered to update each redirected URL to where it leads. Returns `true` if
+/// any was updated.
+///
+/// Updating the tags that redirect is only offered if `update` is `true`.
+pub fn audit_urls(tags: &mut Tags, matches: &ArgMatches, update: bool) -> Result<bool> {
+    let parallel = *matches.get_one::<usize>("parallel").expect("default");
+
+    let mut targets = Vec::new();
+    analysis::walk(tags, |names, tag| {
+        if let Some(url) = tag
+            .path()
+            .filter(|p| p.starts_with("http://") || p.starts_with("https://"))
+        {
+            targets.push((names.to_vec(), url.to_string()));
+        }
+    });
+    if targets.is_empty() {
+        output::status("No URLs to check!");
+        return Ok(false);
+    }
+
+    output::status(&format!(
+        "Checking {} URL{}...",
+        targets.len(),
+        if targets.len() == 1 { "" } else { "s" }
+    ));
+    let urls = targets.iter().map(|(_, u)| u.as_str()).collect::<Vec<_>>();
+    let progress = Progress::new("Checking URLs", urls.len());
+    let statuses = links::check_all(&urls, parallel, || progress.inc());
+    let took = progress.took();
+    drop(progress);
+    let checked = targets.len();
+
+    let (error, warning) = (Style::new().red(), Style::new().yellow());
+    let (mut problems, mut failed) = (0, 0);
+    let mut redirects = Vec::new();
+    for ((names, url), status) in targets.into_iter().zip(statuses) {
+        let (label, details) = match status {
+            links::LinkStatus::Ok => continue,
+            links::LinkStatus::Redirect(to) => {
+                let line = (warning.apply_to("redirect"), format!("{} -> {}", url, to));
+                redirects.push((names.clone(), to));
+                line
+            },
+            links::LinkStatus::Broken(code) => {
+                failed += 1;
+                (error.apply_to("broken"), format!("{} ({})", url, code))
+            },
+            links::LinkStatus::Timeout => {
+                failed += 1;
+                (warning.apply_to("timeout"), url)
+            },
+            links::LinkStatus::Unreachable(e) => {
+                failed += 1;
+                (error.apply_to("error"), format!("{} ({})", url, e))
+            },
+        };
+        println!("{:<8}    {}    {}", label, names.join("/"), details);
+        problems += 1;
+    }
+    if problems == 0 {
+        output::status(&format!("All URLs work{}!", took));
+        return Ok(false);
+    }
+    output::status(&format!(
+        "Checked {} URL{}{}: {} working, {} redirected, {} failed.",
+        checked,
+        if checked == 1 { "" } else { "s" },
+        took,
+        checked - problems,
+        redirects.len(),
+        failed
+    ));
+
+    if !update {
+        return Ok(false);
+    }
+
+    let mut updated = 0;
+    for (names, to) in redirects {
+        if !output::confirm(&format!("Update `{}` to `{}`?", names.join("/"), to), false)? {
+            continue;
+        }
+
+        let tag = tag::find_tag_by_names_mut(tags, &names).expect("checked tag is in the tags");
+        match tag.path {
+            Some(TagPath::PerOs(ref mut paths)) => {
+                paths.insert(env::consts::OS.to_string(), to);
+            },
+            _ => tag.path = Some(to.into()),
+        }
+        tag.modified = Some(usage::now());
+        updated += 1;
+    }
+
+    if updated > 0 {
+        output::status(&format!(
+            "Updated {} URL{}.",
+            updated,
+            if updated == 1 { "" } else { "s" }
+        ));
+    }
+    Ok(updated > 0)
+}
+
+/// Runs the capture command.
+///
+/// A tag is added for the path or the URL in the clipboard, with the name
+/// given in the matches or typed by the user, under the parent given in the
+/// matches or selected by the user.
+pub fn capture(
+    tags: &mut Tags,
+    tags_path: &Path,
+    matches: &ArgMatches,
+    config: &Config,
+) -> Result<()> {
+    let text = clipboard::paste()?;
+    let path = text.trim();
+    if path.is_empty() {
+        return Err(Error::new(ErrorKind::Clipboard, "the clipboard is empty"));
+    }
+    if path.lines().count() > 1 {
+        return Err(Error::new(
+            ErrorKind::Clipboard,
+            "the clipboard has more than one line",
+        ));
+    }
+
+    output::status(&format!("Capturing `{}`", path));
+    if let Some(problem) = analysis::path_problem(path) {
+        output::warn(&problem);
+    }
+
+    let names = match matches.value_of("name") {
+        Some(names) => names.to_string(),
+        None => {
+            let mut input = Input::<String>::new();
+            input.with_prompt("Enter tag name and aliases (comma-separated; at least one)");
+            if let Some(name) = analysis::suggest_name(path) {
+                input.default(name);
+            }
+            input.interact_text()?
+        },
+    };
+    let names: Vec<_> = names
+        .split_terminator(',')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect();
+    if names.is_empty() {
+        return Err("there must be at least one name".into());
+    }
+
+    let subtags = match matches.value_of("parent") {
+        Some(parent) => tag::find_or_create_subtags_mut(tags, &tag::split_tag_path(parent)),
+        None => match select_tag(
+            tags,
+            tags_path,
+            "Select the parent tag (press `esc` for no parent)",
+            "Select a subtag of the parent (press `esc` to select the parent)",
+            config,
+        )? {
+            Some(indices) => {
+                &mut tag::tag_at_mut(tags, &indices)
+                    .expect("selected tag is in the tags")
+                    .subtags
+            },
+            None => tags,
+        },
+    };
+
+    for name in &names {
+        if subtags.iter().flat_map(|t| &t.names).contains(name) {
+            return Err(format!("a tag with name `{}` already exists", name).into());
+        }
+    }
+
+    let about: String = Input::new()
+        .with_prompt("Enter info about the tag, press enter to skip")
+        .allow_empty(true)
+        .interact_text()?;
+
+    let now = usage::now();
+    subtags.push(Tag {
+        names,
+        path: Some(path.to_string().into()),
+        about: (!about.is_empty()).then_some(about),
+        created: Some(now),
+        modified: Some(now),
         ..Default::default()
     });
 
     Ok(())
 }
 
+/// Runs the add command for every file in the directory given in the matches.
+///
+/// The tags are added under the parent given in the matches, which is added if
+/// it does not exist. Files that already have a tag under the parent are
+/// skipped. Returns the number of tags added, which is `0` if the user did not
+/// confirm.
+pub fn add_from_dir(tags: &mut Tags, matches: &ArgMatches) -> Result<usize> {
+    let dir = analysis::expand_path(matches.value_of("from-dir").expect("required"));
+    let extensions = matches
+        .values_of("ext")
+        .map(|e| {
+            e.map(|e| e.trim_start_matches('.').to_lowercase())
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    let parent = matches.value_of("parent").unwrap_or_default();
+    let parent_names = tag::split_tag_path(parent);
+
+    let mut files = fs::read_dir(dir.as_ref())
+        .map_err(|e| {
+            Error::new(
+                ErrorKind::Io,
+                format!("unable to read directory `{}`: {}", dir, e),
+            )
+        })?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.is_file())
+        .filter(|p| {
+            !p.file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.starts_with('.'))
+        })
+        .filter(|p| {
+            extensions.is_empty()
+                || p.extension()
+                    .and_then(|e| e.to_str())
+                    .is_some_and(|e| extensions.contains(&e.to_lowercase()))
+        })
+        .collect::<Vec<_>>();
+    files.sort();
+
+    let labels = new_labels(matches);
+    let siblings = tag::find_or_create_subtags_mut(tags, &parent_names);
+    let mut taken = siblings
+        .iter()
+        .flat_map(|t| t.names.clone())
+        .collect::<Vec<_>>();
+    let mut new_tags = Vec::new();
+    for file in files {
+        let path = file.to_string_lossy().into_owned();
+        if siblings.iter().any(|t| t.path() == Some(path.as_str())) {
+            continue;
+        }
+
+        let stem = file.file_stem().unwrap_or_default().to_string_lossy();
+        let name = stem.split_whitespace().join("-").to_lowercase();
+        let name = tag::unique_name(&name, |n| taken.iter().any(|t| t == n));
+        taken.push(name.clone());
+
+        let now = usage::now();
+        new_tags.push(Tag {
+            names: vec![name],
+            path: Some(path.into()),
+            labels: labels.clone(),
+            created: Some(now),
+            modified: Some(now),
+            ..Default::default()
+        });
+    }
+
+    if new_tags.is_empty() {
+        output::status("No new files!");
+        return Ok(0);
+    }
+
+    let width = new_tags
+        .iter()
+        .map(|t| t.names[0].chars().count())
+        .max()
+        .unwrap_or(0);
+    for tag in &new_tags {
+        println!(
+            "    {:width$}    {}",
+            tag.names[0],
+            tag.path().unwrap_or_default(),
+            width = width
+        );
+    }
+
+    let prompt = if parent_names.is_empty() {
+        format!("Add {} tags?", new_tags.len())
+    } else {
+        format!(
+            "Add {} tags under `{}`?",
+            new_tags.len(),
+            parent_names.join("/")
+        )
+    };
+    if !output::confirm(&prompt, true)? {
+        return Ok(0);
+    }
+
+    let added = new_tags.len();
+    siblings.extend(new_tags);
+
+    Ok(added)
+}
+
+/// Runs the add command for a tag from a template in the config.
+///
+/// The tag has the fields and the subtags of the template, with `{name}`
+/// replaced with its primary name, and is added under the parent given in the
+/// matches, which is added if it does not exist. Returns the number of tags
+/// added, counting the subtags.
+pub fn add_from_template(tags: &mut Tags, matches: &ArgMatches, config: &Config) -> Result<usize> {
+    let mut values = matches.values_of("template").expect("two values");
+    let (template_name, names) = (
+        values.next().expect("two values"),
+        values.next().expect("two values"),
+    );
+    let template = config.templates.get(template_name).ok_or_else(|| {
+        Error::new(
+            ErrorKind::NotFound,
+            format!("no template named `{}` in the config", template_name),
+        )
+    })?;
+
+    let names = names
+        .split(',')
+        .map(str::trim)
+        .filter(|n| !n.is_empty())
+        .map(tag::normalize_name)
+        .collect::<Vec<_>>();
+    if names.is_empty() {
+        return Err("there must be at least one name".into());
+    }
+
+    let now = usage::now();
+    let mut new_tag = Tag {
+        names,
+        path: template.path.clone(),
+        about: template.about.clone(),
+        notes: template.notes.clone(),
+        app: template.app.clone(),
+        labels: template.labels.clone(),
+        subtags: template.subtags.clone(),
+        ..Default::default()
+    };
+    let name = new_tag.names[0].clone();
+    fill_template(&mut new_tag, &name, now);
+
+    let parent_names = tag::split_tag_path(matches.value_of("parent").unwrap_or_default());
+    let siblings = tag::find_or_create_subtags_mut(tags, &parent_names);
+    for name in &new_tag.names {
+        if siblings.iter().any(|t| t.has_name(name)) {
+            return Err(format!("a tag with name `{}` already exists", name).into());
+        }
+    }
+
+    let added = analysis::stats(std::slice::from_ref(&new_tag)).tags;
+    siblings.push(new_tag);
+    Ok(added)
+}
+
+/// Replaces `{name}` with the name in the fields of the tag and its subtags,
+/// and sets when they were created and modified to `now`.
+fn fill_template(tag: &mut Tag, name: &str, now: u64) {
+    fn fill(text: &mut String, name: &str) {
+        if text.contains("{name}") {
+            *text = text.replace("{name}", name);
+        }
+    }
+
+    fn fill_app(app: &mut App, name: &str) {
+        match app {
+            App::Name(app) => fill(app, name),
+            App::Command(command) => command.iter_mut().for_each(|a| fill(a, name)),
+            App::PerOs(apps) => apps.values_mut().for_each(|a| fill_app(a, name)),
+        }
+    }
+
+    tag.names.iter_mut().for_each(|n| fill(n, name));
+    match &mut tag.path {
+        Some(TagPath::Any(path)) => fill(path, name),
+        Some(TagPath::PerOs(paths)) => paths.values_mut().for_each(|p| fill(p, name)),
+        None => {},
+    }
+    tag.fallback_paths.iter_mut().for_each(|p| fill(p, name));
+    tag.about.iter_mut().for_each(|a| fill(a, name));
+    tag.notes.iter_mut().for_each(|n| fill(n, name));
+    tag.cwd.iter_mut().for_each(|c| fill(c, name));
+    tag.env.values_mut().for_each(|v| fill(v, name));
+    tag.app.iter_mut().for_each(|a| fill_app(a, name));
+    tag.created = Some(now);
+    tag.modified = Some(now);
+
+    for subtag in &mut tag.subtags {
+        fill_template(subtag, name, now);
+    }
+}
+
+/// Runs the add command for the tags read from `stdin`.
+///
+/// Each line is either a tag as a JSON object, or a name and a path separated
+/// by a tab. Empty lines are skipped. The tags are added under the parent
+/// given in the matches, which is added if it does not exist. Returns the
+/// number of tags added.
+pub fn add_from_stdin(tags: &mut Tags, matches: &ArgMatches) -> Result<usize> {
+    let now = usage::now();
+    let labels = new_labels(matches);
+    let lines = io::stdin().lines().collect::<io::Result<Vec<_>>>()?;
+    let progress = Progress::new("Checking", lines.len());
+    let mut new_tags = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        progress.inc();
+        let line = line.trim_end_matches(['\r', '\n']);
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let mut tag = if line.trim_start().starts_with('{') {
+            serde_json::from_str::<Tag>(line).map_err(|e| {
+                Error::new(
+                    ErrorKind::Data,
+                    format!("json error on line {}: {}", i + 1, e),
+                )
+            })?
+        } else {
+            let (name, path) = line.split_once('\t').ok_or_else(|| {
+                Error::new(
+                    ErrorKind::Data,
+                    format!("expected a name and a path on line {}", i + 1),
+                )
+            })?;
+            Tag {
+                names: vec![name.trim().to_string()],
+                path: Some(path.trim().to_string())
+                    .filter(|p| !p.is_empty())
+                    .map(TagPath::from),
+                ..Default::default()
+            }
+        };
+        if let Some(path) = tag.path() {
+            progress.suspend(|| check_new_path(path, matches))?;
+        }
+        for label in &labels {
+            if !tag.labels.contains(label) {
+                tag.labels.push(label.clone());
+            }
+        }
+        tag.created.get_or_insert(now);
+        tag.modified.get_or_insert(now);
+        new_tags.push(tag);
+    }
+
+    if new_tags.is_empty() {
+        output::status("No tags!");
+        return Ok(0);
+    }
+
+    let parent_names = tag::split_tag_path(matches.value_of("parent").unwrap_or_default());
+    let siblings = tag::find_or_create_subtags_mut(tags, &parent_names);
+    let added = new_tags.len();
+    for tag in new_tags {
+        for name in &tag.names {
+            if siblings.iter().flat_map(|t| &t.names).contains(name) {
+                return Err(format!("a tag with name `{}` already exists", name).into());
+            }
+        }
+        siblings.push(tag);
+    }
+
+    Ok(added)
+}
+
 /// Runs the remove command.
-pub fn remove(tags: &mut Tags) -> Result<()> {
-    if let Some(tag) = select_tag(
+///
+/// The removed tag is moved to the trash, from where it can be restored.
+pub fn remove(tags: &mut Tags, path: &Path, config: &Config) -> Result<Option<trash::TrashEntry>> {
+    let selected = match select_tag(
         tags,
+        path,
         "Select the parent tag (press `esc` to quit)",
         "Select a subtag of the parent (press `esc` to select the parent)",
+        config,
     )? {
-        // we take advantage of our serialization mechanism: tags with no names
-        // are not written to the file.
-        tag.names.clear();
+        Some(indices) => indices,
+        None => return Ok(None),
     };
+    let mut names = tag::names_at(tags, &selected).expect("selected tag is in the tags");
+    let tag = tag::remove_tag_at(tags, &selected).expect("selected tag is in the tags");
+    names.pop();
+
+    Ok(Some(trash::TrashEntry {
+        parent: names,
+        deleted: usage::now(),
+        tag,
+    }))
+}
+
+/// Checks that the tags added, renamed, or given new aliases between the two
+/// versions of the tags have names that no other tag in the tree has.
+///
+/// Clashes are only checked if `unique` is configured to, and are errors with
+/// `global` and warnings with `warn`.
+pub fn check_unique_names(old: &[Tag], new: &[Tag], config: &Config) -> Result<()> {
+    if config.unique == Uniqueness::Siblings {
+        return Ok(());
+    }
+
+    for change in analysis::diff(old, new) {
+        let names = match change {
+            analysis::Change::Added { names, .. } => names,
+            analysis::Change::Moved { to, .. } => to,
+            analysis::Change::Changed { names, fields }
+                if fields.iter().any(|(f, _, _)| *f == "aliases") =>
+            {
+                names
+            },
+            _ => continue,
+        };
+
+        for (name, other) in analysis::name_clashes(new, &names) {
+            let message = format!("`{}` is also a name of the tag `{}`", name, other.join("/"));
+            match config.unique {
+                Uniqueness::Global => return Err(message.into()),
+                _ => output::warn(&message),
+            }
+        }
+    }
 
     Ok(())
 }
 
+/// Prints the changes between the tags as loaded and as they would be saved,
+/// and asks the user to confirm them. Returns `true` if they are confirmed or
+/// if nothing changed.
+///
+/// Added and removed tags are shown once, with the number of their subtags.
+pub fn confirm_changes(old: &[Tag], new: &[Tag]) -> Result<bool> {
+    let mut changes = analysis::diff(old, new);
+    if changes.is_empty() {
+        return Ok(true);
+    }
+
+    let (added, removed) = (Style::new().green(), Style::new().red());
+    let mut roots: Vec<(Vec<String>, bool)> = Vec::new();
+    changes.retain(|change| {
+        let (names, is_added) = match change {
+            analysis::Change::Added { names, .. } => (names, true),
+            analysis::Change::Removed { names, .. } => (names, false),
+            _ => return true,
+        };
+        if roots
+            .iter()
+            .any(|(root, a)| *a == is_added && names.starts_with(root))
+        {
+            return false;
+        }
+        roots.push((names.clone(), is_added));
+        true
+    });
+
+    println!();
+    for change in changes {
+        let (style, sign, names, path, tags) = match change {
+            analysis::Change::Added { names, path } => (&added, '+', names, path, new),
+            analysis::Change::Removed { names, path } => (&removed, '-', names, path, old),
+            change => {
+                print_changes(vec![change]);
+                continue;
+            },
+        };
+        let subtags = tag::find_tag_by_names(tags, &names).map_or(0, |t| {
+            let mut count = 0;
+            analysis::walk(&t.subtags, |_, _| count += 1);
+            count
+        });
+        let mut line = format!(
+            "{} {}    {}",
+            sign,
+            names.join("/"),
+            path.unwrap_or_default()
+        );
+        line = line.trim_end().to_string();
+        if subtags > 0 {
+            line += &format!(
+                " (and {} subtag{})",
+                subtags,
+                if subtags == 1 { "" } else { "s" }
+            );
+        }
+        println!("{}", style.apply_to(line));
+    }
+    println!();
+
+    output::confirm("Save the changes?", true)
+}
+
+/// Returns the primary names of the tag and its parents, root first, if the
+/// tag is one of the tags or their subtags.
+fn tag_names_of(tags: &[Tag], target: &Tag) -> Option<Vec<String>> {
+    tags.iter().find_map(|tag| {
+        if std::ptr::eq(tag, target) {
+            return Some(vec![tag.names[0].clone()]);
+        }
+        let mut names = tag_names_of(&tag.subtags, target)?;
+        names.insert(0, tag.names[0].clone());
+        Some(names)
+    })
+}
+
 /// Runs the update command.
-pub fn update(tags: &mut Tags) -> Result<()> {
+pub fn update(tags: &mut Tags, path: &Path, matches: &ArgMatches, config: &Config) -> Result<()> {
     let tag = match select_tag(
         tags,
+        path,
         "Select the parent tag (press `esc` to quit)",
         "Select a subtag of the parent (press `esc` to select the parent)",
+        config,
     )? {
-        Some(t) => t,
+        Some(indices) => tag::tag_at_mut(tags, &indices).expect("selected tag is in the tags"),
         None => return Ok(()),
     };
 
+    let last_modified = tag.modified.replace(usage::now());
+
+    if matches.contains_id("pin") || matches.contains_id("unpin") {
+        tag.pinned = matches.contains_id("pin");
+        return Ok(());
+    }
+
+    if matches.contains_id("label") {
+        tag.labels = new_labels(matches);
+        if !matches.contains_id("secret") {
+            return Ok(());
+        }
+    }
+
+    if matches.contains_id("edit-notes") {
+        // notes are kept as they are, including lines starting with '#'
+        if let Some(notes) = Editor::new().edit(tag.notes.as_deref().unwrap_or_default())? {
+            let notes = notes.trim_end();
+            tag.notes = (!notes.trim().is_empty()).then(|| notes.to_string());
+        }
+        return Ok(());
+    }
+
+    let fields = MultiSelect::with_theme(&ColorfulTheme::default())
+        .with_prompt(
+            "Press `space` to pick the fields to edit and `enter` to edit them (press `esc` to \
+             quit)",
+        )
+        .items(&["Names", "Path", "Description", "App"])
+        .interact_opt()?
+        .unwrap_or_default();
+    if fields.is_empty() && !matches.contains_id("secret") {
+        tag.modified = last_modified;
+        return Ok(());
+    }
+
+    // secret tags are edited decrypted and encrypted again afterwards
+    let is_secret = tag.secret.is_some() || matches.contains_id("secret");
+    secret::decrypt(tag, &config.secrets)?;
+
     let filter_text = |text: String| {
         text.lines()
             .filter(|l| {
@@ -175,19 +3950,21 @@ pub fn update(tags: &mut Tags) -> Result<()> {
 
     let ignored_str = "Lines starting with '#' will be ignored.";
 
-    let names_msg = format!(
-        "{}\n# Please enter/edit comma-separated list of names above.\n# {ignored_str}",
-        tag.names.join(", ")
-    );
-    if let Some(names) = Editor::new().edit(&names_msg)? {
-        let names = filter_text(names)
-            .split_terminator(',')
-            .map(|s| s.trim().to_string())
-            .collect::<Vec<_>>();
-        if names.is_empty() {
-            return Err("there must be at least one name".into());
+    if fields.contains(&0) {
+        let names_msg = format!(
+            "{}\n# Please enter/edit comma-separated list of names above.\n# {ignored_str}",
+            tag.names.join(", ")
+        );
+        if let Some(names) = Editor::new().edit(&names_msg)? {
+            let names = filter_text(names)
+                .split_terminator(',')
+                .map(|s| s.trim().to_string())
+                .collect::<Vec<_>>();
+            if names.is_empty() {
+                return Err("there must be at least one name".into());
+            }
+            tag.names = names;
         }
-        tag.names = names;
     }
 
     let update_field = |field: &mut Option<_>, msg: &str| -> Result<()> {
@@ -203,7 +3980,112 @@ pub fn update(tags: &mut Tags) -> Result<()> {
         Ok(())
     };
 
-    update_field(&mut tag.path, "Please edit/enter the path/url above.")?;
-    update_field(&mut tag.about, "Please edit/enter the description above.")?;
-    update_field(&mut tag.app, "Please edit/enter the default app above.")
+    if fields.contains(&1) {
+        let mut path = tag.path.as_ref().map(TagPath::to_string);
+        update_field(
+            &mut path,
+            "Please edit/enter the path/url above, or a JSON object of them by operating system.",
+        )?;
+        tag.path = path.as_deref().map(TagPath::parse);
+    }
+    if fields.contains(&2) {
+        update_field(&mut tag.about, "Please edit/enter the description above.")?;
+    }
+    if fields.contains(&3) {
+        let mut app = tag.app.as_ref().map(App::to_string);
+        update_field(
+            &mut app,
+            "Please edit/enter the default app above, or a JSON array of a program and its \
+             arguments.",
+        )?;
+        tag.app = app.as_deref().map(App::parse);
+    }
+
+    if is_secret {
+        secret::encrypt(tag, &config.secrets)?;
+    }
+
+    Ok(())
+}
+
+/// Runs the external command with the arguments and returns its exit code.
+///
+/// The paths of the tags file and the config file are passed to it in
+/// `OPENTAG_DATA` and `OPENTAG_CONFIG`.
+pub fn external(
+    program: &Path,
+    args: &[OsString],
+    tags_path: &Path,
+    config_path: &Path,
+) -> Result<i32> {
+    let status = Command::new(program)
+        .args(args)
+        .env("OPENTAG_DATA", tags_path)
+        .env("OPENTAG_CONFIG", config_path)
+        .status()
+        .map_err(|e| format!("unable to run `{}`: {}", program.display(), e))?;
+
+    Ok(status.code().unwrap_or(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Asserts that the tag is the same after going through the TOML that
+    /// `ot edit` opens.
+    fn assert_edit_round_trips(json: &str) {
+        let tags = tag::parse_tags(json).expect("valid tags");
+        let text = tag_to_toml(&tags[0]).expect("serializable tag");
+        let edited = toml::from_str::<Tag>(&text).expect("parsable TOML");
+
+        assert_eq!(
+            serde_json::to_value(&tags[0]).unwrap(),
+            serde_json::to_value(&edited).unwrap(),
+            "{}",
+            text
+        );
+    }
+
+    #[test]
+    fn edit_round_trips_tags_by_os() {
+        assert_edit_round_trips(
+            r#"[{
+                "names": ["docs", "d"],
+                "path": {"linux": "/usr/share/doc", "macos": "/Library/Documentation"},
+                "app": {"linux": "xdg-open", "macos": ["open", "-a", "Preview"]},
+                "about": "Local documentation",
+                "labels": ["reference"],
+                "pinned": true
+            }]"#,
+        );
+    }
+
+    #[test]
+    fn edit_round_trips_env_and_timestamps() {
+        assert_edit_round_trips(
+            r#"[{
+                "names": ["gh"],
+                "path": "https://github.com",
+                "cwd": "~/src",
+                "env": {"GH_HOST": "github.com", "NO_COLOR": "1"},
+                "review_after": "2030-01-01",
+                "created": 1700000000,
+                "modified": 1700000500
+            }]"#,
+        );
+    }
+
+    #[test]
+    fn edit_round_trips_unknown_keys() {
+        assert_edit_round_trips(
+            r#"[{
+                "names": ["notes"],
+                "path": "~/notes",
+                "icon": "📝",
+                "shortcut": "ctrl+n",
+                "future": {"weight": 3, "tags": ["a", "b"]}
+            }]"#,
+        );
+    }
 }
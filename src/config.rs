@@ -0,0 +1,334 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::{env, fs};
+
+use serde::Deserialize;
+
+use crate::clipboard::ClipboardKind;
+use crate::error::{Error, ErrorKind, Result};
+use crate::tag::{App, Tag, TagPath};
+
+/// User configuration.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Configuration of the fuzzy matcher used by the interactive pickers.
+    pub fuzzy: FuzzyConfig,
+    /// Command used to open paths when there is no graphical session.
+    pub headless_opener: Option<String>,
+    /// Whether to open tags without bringing the app to the foreground by
+    /// default.
+    pub background: bool,
+    /// The clipboard that paths are copied to.
+    pub clipboard: ClipboardKind,
+    /// Whether to copy with OSC 52 when the clipboard cannot be used, instead
+    /// of printing the text.
+    pub osc52_fallback: bool,
+    /// Whether tags can be invoked by a prefix of their name that no other tag
+    /// at the same level starts with.
+    pub prefix_matching: bool,
+    /// The apps to open paths with by default, by file extension or URL
+    /// scheme.
+    pub apps: HashMap<String, App>,
+    /// How secret tags are encrypted and decrypted.
+    pub secrets: SecretsConfig,
+    /// Named lists of tags, by path, that are opened together.
+    pub groups: HashMap<String, Vec<String>>,
+    /// Tags files, by `https://` URL or path, whose tags are merged into the
+    /// local tags for reading only.
+    pub extra_sources: Vec<String>,
+    /// Where the tags file is synced to with `ot sync`.
+    pub sync: Option<SyncConfig>,
+    /// The directory that relative tag paths are resolved against when they
+    /// are opened.
+    pub root: Option<String>,
+    /// Where the names of added and updated tags must be unique.
+    pub unique: Uniqueness,
+    /// Whether the aliases of nested tags can be invoked from the top level
+    /// if no other tag has them.
+    pub global_aliases: bool,
+    /// Templates that tags are added from with `--template`, by name.
+    pub templates: HashMap<String, Template>,
+    /// What invoking a tag without a path but with subtags does.
+    pub on_no_path: NoPath,
+    /// Options applied to every invocation unless given on the command line.
+    pub defaults: Defaults,
+}
+
+impl Config {
+    /// Returns the app configured for the scheme of the URL or the extension
+    /// of the file, ignoring case.
+    pub fn app_for(&self, path: &str) -> Option<&App> {
+        let key = match path.split_once("://") {
+            Some((scheme, _)) => scheme,
+            None => Path::new(path).extension()?.to_str()?,
+        };
+
+        self.apps
+            .iter()
+            .find(|(k, _)| k.trim_start_matches('.').eq_ignore_ascii_case(key))
+            .map(|(_, app)| app)
+    }
+}
+
+/// The fields and the subtags of the tags added from a template.
+///
+/// `{name}` in the fields and in the subtags is replaced with the primary name
+/// of the added tag.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Template {
+    /// The path of the tag.
+    #[serde(alias = "url", alias = "link")]
+    pub path: Option<TagPath>,
+    /// The description of the tag.
+    pub about: Option<String>,
+    /// The notes of the tag.
+    pub notes: Option<String>,
+    /// The app to open the tag with.
+    pub app: Option<App>,
+    /// The labels of the tag.
+    pub labels: Vec<String>,
+    /// The subtags of the tag.
+    pub subtags: Vec<Tag>,
+}
+
+/// Configuration of the encryption of secret tags with `age`.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct SecretsConfig {
+    /// The public keys to encrypt secret tags to. Secret tags are encrypted
+    /// with a passphrase if there are none.
+    pub recipients: Vec<String>,
+    /// The path to the identity file to decrypt secret tags with.
+    pub identity: Option<String>,
+}
+
+/// Configuration of the backend that the tags file is synced to.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "backend", rename_all = "lowercase")]
+pub enum SyncConfig {
+    /// A file on a WebDAV server.
+    #[serde(rename = "webdav")]
+    WebDav {
+        /// The URL of the file.
+        url: String,
+        /// The username to authenticate with, if any.
+        username: Option<String>,
+        /// The password to authenticate with. Read from
+        /// `$OPENTAG_SYNC_PASSWORD` if missing.
+        password: Option<String>,
+    },
+    /// An object in an S3-compatible bucket.
+    S3 {
+        /// The URL of the S3 service, such as
+        /// `https://s3.eu-west-1.amazonaws.com`.
+        endpoint: String,
+        /// The name of the bucket.
+        bucket: String,
+        /// The key of the object.
+        key: String,
+        /// The region of the bucket.
+        region: String,
+        /// The access key ID. Read from `$AWS_ACCESS_KEY_ID` if missing.
+        access_key_id: Option<String>,
+        /// The secret access key. Read from `$AWS_SECRET_ACCESS_KEY` if
+        /// missing.
+        secret_access_key: Option<String>,
+    },
+}
+
+/// Configuration of the fuzzy matcher.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct FuzzyConfig {
+    /// The matching algorithm.
+    pub algorithm: MatchAlgorithm,
+    /// How the case of the query and the candidates is compared.
+    pub case: CaseMatching,
+    /// Candidates scoring less than this are not shown.
+    pub threshold: i64,
+}
+
+impl Default for FuzzyConfig {
+    fn default() -> Self {
+        Self {
+            algorithm: MatchAlgorithm::Skim,
+            case: CaseMatching::Smart,
+            threshold: 0,
+        }
+    }
+}
+
+/// The algorithm used to match a query against the candidates.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MatchAlgorithm {
+    /// Skim-style fuzzy matching; the query characters must appear in order.
+    Skim,
+    /// The query must appear as a contiguous substring.
+    Substring,
+}
+
+/// How the case of the query and the candidates is compared.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CaseMatching {
+    /// Case-sensitive only if the query contains an uppercase character.
+    Smart,
+    /// Always case-insensitive.
+    Ignore,
+    /// Always case-sensitive.
+    Respect,
+}
+
+/// Where the names of a tag must be unique.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Uniqueness {
+    /// Among the tags at the same level only.
+    #[default]
+    Siblings,
+    /// Among the tags at the same level, with a warning for names used
+    /// anywhere else in the tree.
+    Warn,
+    /// Anywhere in the tree.
+    Global,
+}
+
+/// What invoking a tag without a path or a URL, but with subtags, does.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum NoPath {
+    /// Fail, as the tag cannot be opened.
+    #[default]
+    Error,
+    /// List the subtags, as with `--list`.
+    List,
+    /// Pick a subtag to invoke instead, or fail when not run in a terminal.
+    Select,
+}
+
+/// Options applied to every invocation, as if they were given on the command
+/// line, unless the command line gives them or options that conflict with
+/// them.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Defaults {
+    /// Copy the path of an invoked tag, as with `--copy`.
+    pub copy: bool,
+    /// Copy the path of an invoked tag without opening it, as with
+    /// `--silent-copy`.
+    pub silent_copy: bool,
+    /// Print the path of an invoked tag instead of opening it, as with
+    /// `--print`.
+    pub print: bool,
+    /// Wait for the app to exit, as with `--wait`.
+    pub wait: bool,
+    /// Options applied when listing tags.
+    pub list: ListDefaults,
+}
+
+/// Options applied when listing tags with `--list`.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct ListDefaults {
+    /// List the subtags of the listed tags as well, as with `--recursive`.
+    pub recursive: bool,
+    /// The number of levels of subtags to list, as with `--depth`.
+    pub depth: Option<usize>,
+    /// The order the tags are listed in, as with `--sort`.
+    pub sort: Option<SortOrder>,
+    /// What the tags are grouped by, as with `--group-by`.
+    pub group_by: Option<GroupBy>,
+    /// The template to list the tags with, as with `--format`.
+    pub format: Option<String>,
+    /// Print the listing directly instead of through the pager, as with
+    /// `--no-pager`.
+    pub no_pager: bool,
+}
+
+/// The order tags are listed in at each level.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    /// By primary name, ignoring case.
+    Name,
+    /// Most recently changed first.
+    Recent,
+    /// Most opened first.
+    Usage,
+    /// By kind, such as directories before files.
+    Kind,
+}
+
+impl SortOrder {
+    /// Returns the order as it is given to `--sort`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Name => "name",
+            Self::Recent => "recent",
+            Self::Usage => "usage",
+            Self::Kind => "kind",
+        }
+    }
+}
+
+/// What listed tags are grouped by.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum GroupBy {
+    /// By label.
+    Label,
+    /// By kind.
+    Kind,
+}
+
+impl GroupBy {
+    /// Returns the grouping as it is given to `--group-by`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Label => "label",
+            Self::Kind => "kind",
+        }
+    }
+}
+
+/// Returns the path to the config file.
+///
+/// Errors if unable to retrieve the config directory path (and
+/// `$OPENTAG_CONFIG` is not set).
+pub fn get_config_path() -> Result<PathBuf> {
+    env::var("OPENTAG_CONFIG").map_or_else(
+        |_| {
+            dirs_next::config_dir()
+                .map(|d| d.join("opentag/config.json"))
+                .ok_or_else(|| "unable to retrieve config directory path".into())
+        },
+        |p| Ok(PathBuf::from(p)),
+    )
+}
+
+/// Returns the config present at the given path.
+///
+/// The default config is returned if the file does not exist.
+pub fn get_config<P: AsRef<Path>>(path: P) -> Result<Config> {
+    let path = path.as_ref();
+    if !path.exists() {
+        return Ok(Config::default());
+    }
+
+    let contents = fs::read_to_string(path).map_err(|e| {
+        Error::new(
+            ErrorKind::Io,
+            format!("config file error at path `{}`: {}", path.display(), e),
+        )
+    })?;
+
+    serde_json::from_str(&contents).map_err(|e| {
+        Error::new(
+            ErrorKind::Data,
+            format!("json error at path `{}`: {}", path.display(), e),
+        )
+    })
+}
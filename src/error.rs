@@ -1,35 +1,143 @@
-use std::fmt::Display;
-use std::io::Write;
-
-use termcolor::{BufferWriter, Color, ColorChoice, ColorSpec, WriteColor};
+use std::fmt::{self, Display};
+use std::io;
 
 /// Result type used throughout the crate.
-pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// The category of an error, which determines the exit code.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// Any other error.
+    Other,
+    /// The arguments are invalid.
+    Usage,
+    /// The tag or another item given by the user does not exist.
+    NotFound,
+    /// The tags file, the config file, or other input is invalid.
+    Data,
+    /// Reading or writing a file failed.
+    Io,
+    /// Copying to the clipboard failed.
+    Clipboard,
+    /// Opening the path or the URL failed.
+    Open,
+    /// The user aborted the command.
+    Abort,
+}
+
+impl ErrorKind {
+    /// Returns the exit code of errors of this kind.
+    ///
+    /// `2` matches the exit code of `clap` for invalid arguments.
+    pub fn exit_code(self) -> i32 {
+        match self {
+            Self::Other => 1,
+            Self::Usage => 2,
+            Self::NotFound => 3,
+            Self::Data => 4,
+            Self::Io => 5,
+            Self::Clipboard => 6,
+            Self::Open => 7,
+            Self::Abort => 8,
+        }
+    }
+
+    /// Returns the name of the kind used in JSON errors.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Other => "other",
+            Self::Usage => "usage",
+            Self::NotFound => "not_found",
+            Self::Data => "data",
+            Self::Io => "io",
+            Self::Clipboard => "clipboard",
+            Self::Open => "open",
+            Self::Abort => "abort",
+        }
+    }
+}
+
+/// An error with the category it belongs to.
+#[derive(Debug)]
+pub struct Error {
+    kind: ErrorKind,
+    message: String,
+}
+
+impl Error {
+    /// Creates an error of the given kind.
+    pub fn new<S: Into<String>>(kind: ErrorKind, message: S) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+        }
+    }
+
+    /// Returns the kind of the error.
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+}
 
-/// Prints the error on the `stderr` and exits with the provided exit code.
-///
-/// "error: " is displayed before the error message. The "error" is displayed in
-/// red and bold if possible.
-pub fn exit<T: Display>(err: T, code: i32) -> ! {
-    print_error(&err).unwrap_or_else(|_| eprintln!("error: {}", err));
-    std::process::exit(code);
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
 }
 
-/// Prints error on the `stderr`.
-///
-/// "error: " is displayed before the error message. The "error" is displayed in
-/// red and bold if possible.
-fn print_error<T: Display>(err: &T) -> Result<()> {
-    let bufwtr = BufferWriter::stderr(ColorChoice::Auto);
-    let mut buffer = bufwtr.buffer();
+impl std::error::Error for Error {}
 
-    buffer.set_color(ColorSpec::new().set_fg(Some(Color::Red)).set_bold(true))?;
+impl From<String> for Error {
+    fn from(message: String) -> Self {
+        Self::new(ErrorKind::Other, message)
+    }
+}
+
+impl From<&str> for Error {
+    fn from(message: &str) -> Self {
+        Self::new(ErrorKind::Other, message)
+    }
+}
 
-    write!(&mut buffer, "error")?;
-    buffer.reset()?;
-    writeln!(&mut buffer, ": {}", err)?;
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Self::new(ErrorKind::Io, err.to_string())
+    }
+}
+
+impl From<fmt::Error> for Error {
+    fn from(err: fmt::Error) -> Self {
+        Self::new(ErrorKind::Other, err.to_string())
+    }
+}
 
-    bufwtr.print(&buffer)?;
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        Self::new(ErrorKind::Data, err.to_string())
+    }
+}
+
+impl From<toml::ser::Error> for Error {
+    fn from(err: toml::ser::Error) -> Self {
+        Self::new(ErrorKind::Data, err.to_string())
+    }
+}
+
+impl From<clap::Error> for Error {
+    fn from(err: clap::Error) -> Self {
+        // only the first line, without the usage and the "error: " prefix
+        let message = console::strip_ansi_codes(&err.to_string())
+            .lines()
+            .next()
+            .unwrap_or_default()
+            .trim_start_matches("error: ")
+            .to_string();
+        Self::new(ErrorKind::Usage, message)
+    }
+}
 
-    Ok(())
+impl From<arboard::Error> for Error {
+    fn from(err: arboard::Error) -> Self {
+        Self::new(ErrorKind::Clipboard, err.to_string())
+    }
 }
@@ -0,0 +1,85 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, ErrorKind, Result};
+use crate::usage;
+
+/// A tag that was opened, printed, or copied.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct HistoryEntry {
+    /// When the tag was opened, in seconds since the Unix epoch.
+    pub time: u64,
+    /// The tag path, such as `web/github`.
+    pub tag: String,
+    /// The path or the URL the tag resolved to, or none for secret tags, whose
+    /// targets are not written in the history.
+    pub target: Option<String>,
+    /// What was done with the tag, such as `open`, `print`, or `copy`.
+    pub action: String,
+}
+
+/// Returns the path to the history file kept next to the tags file.
+pub fn get_history_path(tags_path: &Path) -> PathBuf {
+    tags_path.with_extension("history.jsonl")
+}
+
+/// Returns the history present at the given path, oldest first.
+///
+/// Empty history is returned if the file does not exist.
+pub fn get_history<P: AsRef<Path>>(path: P) -> Result<Vec<HistoryEntry>> {
+    let path = path.as_ref();
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let contents = fs::read_to_string(path)
+        .map_err(|e| format!("history file error at path `{}`: {}", path.display(), e))?;
+
+    contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            serde_json::from_str(line).map_err(|e| {
+                Error::new(
+                    ErrorKind::Data,
+                    format!(
+                        "json error at path `{}`, line {}: {}",
+                        path.display(),
+                        i + 1,
+                        e
+                    ),
+                )
+            })
+        })
+        .collect()
+}
+
+/// Appends the entry to the history at the given path.
+///
+/// Nothing is written if opened tags are not being recorded, as with
+/// [`usage::set_recording`].
+pub fn record<P: AsRef<Path>>(path: P, entry: &HistoryEntry) -> Result<()> {
+    if !usage::is_recording() {
+        return Ok(());
+    }
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path.as_ref())?;
+    // one write per entry, so that entries of programs run at once are not
+    // interleaved
+    Ok(file.write_all(format!("{}\n", serde_json::to_string(entry)?).as_bytes())?)
+}
+
+/// Deletes the history at the given path, if any.
+pub fn clear<P: AsRef<Path>>(path: P) -> Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
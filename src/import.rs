@@ -0,0 +1,691 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+use std::{env, fs, io};
+
+use serde_json::Value;
+
+use crate::error::{Error, ErrorKind, Result};
+use crate::tag::{self, App, Tag, TagPath};
+use crate::{analysis, usage};
+
+/// The fields of a tag that can be imported from a CSV column.
+const CSV_FIELDS: &[&str] = &["name", "aliases", "path", "about", "app"];
+
+/// Parses the CSV text into records of fields.
+///
+/// Fields may be quoted with `"`, in which case they may contain commas,
+/// newlines, and quotes written as `""`.
+pub fn parse_csv(text: &str) -> Result<Vec<Vec<String>>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted => {
+                if chars.peek() == Some(&'"') {
+                    field.push('"');
+                    chars.next();
+                } else {
+                    quoted = false;
+                }
+            },
+            '"' if field.is_empty() => quoted = true,
+            ',' if !quoted => record.push(std::mem::take(&mut field)),
+            '\r' if !quoted && chars.peek() == Some(&'\n') => {},
+            '\n' if !quoted => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            },
+            c => field.push(c),
+        }
+    }
+
+    if quoted {
+        return Err(Error::new(
+            ErrorKind::Data,
+            "unterminated quoted field in csv",
+        ));
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+
+    Ok(records
+        .into_iter()
+        .filter(|r| !(r.len() == 1 && r[0].trim().is_empty()))
+        .collect())
+}
+
+/// Parses a column mapping such as `name=Service,path=URL` into a map from
+/// the field of a tag to the header of its column.
+pub fn parse_mapping(mapping: &str) -> Result<HashMap<String, String>> {
+    mapping
+        .split(',')
+        .filter(|m| !m.trim().is_empty())
+        .map(|m| {
+            let (field, header) = m
+                .split_once('=')
+                .ok_or_else(|| format!("expected `field=header` in mapping, found `{}`", m))?;
+            let field = field.trim().to_lowercase();
+            if !CSV_FIELDS.contains(&field.as_str()) {
+                return Err(format!(
+                    "unknown field `{}` in mapping, expected one of: {}",
+                    field,
+                    CSV_FIELDS.join(", ")
+                )
+                .into());
+            }
+            Ok((field, header.trim().to_string()))
+        })
+        .collect()
+}
+
+/// Creates tags from the CSV text.
+///
+/// The first record is the header. The columns of the fields of a tag are
+/// found by the header given in `mapping`, or else by the name of the field,
+/// ignoring case. `url` is accepted for the path. Aliases are separated by
+/// commas or semicolons.
+pub fn tags_from_csv(text: &str, mapping: &HashMap<String, String>) -> Result<Vec<Tag>> {
+    let mut records = parse_csv(text)?.into_iter();
+    let header = records
+        .next()
+        .ok_or_else(|| Error::new(ErrorKind::Data, "csv has no header"))?;
+
+    let column = |field: &str| {
+        let header_name = mapping.get(field).map_or(field, String::as_str);
+        header
+            .iter()
+            .position(|h| h.trim().eq_ignore_ascii_case(header_name))
+            .or_else(|| {
+                (field == "path" && !mapping.contains_key(field))
+                    .then(|| {
+                        header
+                            .iter()
+                            .position(|h| h.trim().eq_ignore_ascii_case("url"))
+                    })
+                    .flatten()
+            })
+    };
+    let name_column =
+        column("name").ok_or_else(|| Error::new(ErrorKind::Data, "csv has no name column"))?;
+    let (aliases_column, path_column, about_column, app_column) = (
+        column("aliases"),
+        column("path"),
+        column("about"),
+        column("app"),
+    );
+
+    let now = usage::now();
+    records
+        .enumerate()
+        .map(|(i, record)| {
+            let get = |column: Option<usize>| {
+                column
+                    .and_then(|c| record.get(c))
+                    .map(|f| f.trim().to_string())
+                    .filter(|f| !f.is_empty())
+            };
+
+            let name = get(Some(name_column)).ok_or_else(|| {
+                Error::new(
+                    ErrorKind::Data,
+                    format!("missing name on row {} of csv", i + 2),
+                )
+            })?;
+            let mut names = vec![name];
+            if let Some(aliases) = get(aliases_column) {
+                names.extend(
+                    aliases
+                        .split([',', ';'])
+                        .map(str::trim)
+                        .filter(|a| !a.is_empty())
+                        .map(String::from),
+                );
+            }
+
+            Ok(Tag {
+                names,
+                path: get(path_column).map(TagPath::from),
+                about: get(about_column),
+                app: get(app_column).as_deref().map(App::parse),
+                created: Some(now),
+                modified: Some(now),
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+/// Creates tags from the bookmarks of the browser, one of `firefox`, `chrome`,
+/// or `safari`.
+///
+/// Folders become tags without a path, with the bookmarks in them as subtags.
+/// The bookmarks are read from `file` if it is given, or else from the default
+/// profile of the browser.
+pub fn tags_from_browser(browser: &str, file: Option<&Path>) -> Result<Vec<Tag>> {
+    let default_file;
+    let file = match file {
+        Some(file) => file,
+        None => {
+            default_file = browser_bookmarks_path(browser)?;
+            default_file.as_path()
+        },
+    };
+    log::info!("reading {} bookmarks from `{}`", browser, file.display());
+    if !file.exists() {
+        return Err(Error::new(
+            ErrorKind::NotFound,
+            format!("no {} bookmarks at `{}`", browser, file.display()),
+        ));
+    }
+
+    let mut tags = match browser {
+        "firefox" => tags_from_firefox(file)?,
+        "chrome" => tags_from_chrome(file)?,
+        "safari" => tags_from_safari(file)?,
+        browser => unreachable!("unhandled browser `{}`", browser),
+    };
+    prune_empty_folders(&mut tags);
+    Ok(tags)
+}
+
+/// Creates tags for the hosts of the SSH config text, with `ssh://` URLs.
+///
+/// Each `Host` line becomes a tag named after its hosts, the first one also
+/// being its URL, and described with its `User`, `HostName`, and `Port`.
+/// Patterns with wildcards, such as `*` or `*.example.com`, are not hosts and
+/// are skipped, as are `Match` blocks.
+pub fn tags_from_ssh_config(text: &str) -> Vec<Tag> {
+    let now = usage::now();
+    let mut tags = Vec::new();
+    let mut current: Option<SshHost> = None;
+
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        // keywords are separated from their values by spaces or `=`
+        let is_separator = |c: char| c.is_whitespace() || c == '=';
+        let (key, value) = match line.split_once(is_separator) {
+            Some((key, value)) => (key, value.trim_start_matches(is_separator).trim()),
+            None => continue,
+        };
+
+        match key.to_ascii_lowercase().as_str() {
+            "host" | "match" => {
+                tags.extend(current.take().map(|h| h.into_tag(now)));
+                let hosts = value
+                    .split_whitespace()
+                    .filter(|h| !h.contains(['*', '?', '!']))
+                    .map(tag::normalize_name)
+                    .collect::<Vec<_>>();
+                if key.eq_ignore_ascii_case("host") && !hosts.is_empty() {
+                    current = Some(SshHost {
+                        hosts,
+                        ..Default::default()
+                    });
+                }
+            },
+            "user" => current.iter_mut().for_each(|h| h.user = Some(value)),
+            "hostname" => current.iter_mut().for_each(|h| h.hostname = Some(value)),
+            "port" => current.iter_mut().for_each(|h| h.port = Some(value)),
+            _ => {},
+        }
+    }
+    tags.extend(current.map(|h| h.into_tag(now)));
+
+    tags
+}
+
+/// A `Host` entry of an SSH config file.
+#[derive(Default)]
+struct SshHost<'a> {
+    /// The hosts the entry applies to, without patterns.
+    hosts: Vec<String>,
+    /// The user to log in as.
+    user: Option<&'a str>,
+    /// The real host name to connect to.
+    hostname: Option<&'a str>,
+    /// The port to connect to.
+    port: Option<&'a str>,
+}
+
+impl SshHost<'_> {
+    /// Returns the tag for the entry, created and modified at `now`.
+    fn into_tag(self, now: u64) -> Tag {
+        let about = self.hostname.map(|host| {
+            let user = self.user.map(|u| format!("{}@", u)).unwrap_or_default();
+            let port = self.port.map(|p| format!(":{}", p)).unwrap_or_default();
+            format!("{}{}{}", user, host, port)
+        });
+
+        Tag {
+            path: Some(format!("ssh://{}", self.hosts[0]).into()),
+            names: self.hosts,
+            about,
+            created: Some(now),
+            modified: Some(now),
+            ..Default::default()
+        }
+    }
+}
+
+/// Creates tags for the `count` directories visited most often according to
+/// zoxide, most visited first.
+///
+/// Each tag is named after its directory, made unique among the tags.
+pub fn tags_from_zoxide(count: usize) -> Result<Vec<Tag>> {
+    let output = Command::new("zoxide")
+        .args(["query", "--list", "--score"])
+        .output()
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => Error::new(
+                ErrorKind::NotFound,
+                "`zoxide` is required to import its directories",
+            ),
+            _ => Error::new(ErrorKind::Io, format!("unable to run `zoxide`: {}", e)),
+        })?;
+    if !output.status.success() {
+        return Err(format!(
+            "`zoxide` failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+        .into());
+    }
+
+    // each line is the score followed by the directory, highest score first
+    let now = usage::now();
+    let tags = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|l| l.trim_start().split_once(' '))
+        .map(|(_, dir)| dir.trim())
+        .filter(|dir| !dir.is_empty())
+        .take(count)
+        .map(|dir| {
+            let name = Path::new(dir)
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            Tag {
+                names: vec![bookmark_name(&name, None)],
+                path: Some(dir.to_string().into()),
+                created: Some(now),
+                modified: Some(now),
+                ..Default::default()
+            }
+        })
+        .collect();
+    Ok(unique_names(tags))
+}
+
+/// Returns the path to the bookmarks of the default profile of the browser.
+fn browser_bookmarks_path(browser: &str) -> Result<PathBuf> {
+    let not_found = || {
+        Error::new(
+            ErrorKind::NotFound,
+            format!(
+                "unable to find the {} profile; pass its bookmarks file",
+                browser
+            ),
+        )
+    };
+
+    match browser {
+        "firefox" => {
+            let profiles = if cfg!(target_os = "macos") {
+                dirs_next::config_dir().map(|d| d.join("Firefox/Profiles"))
+            } else if cfg!(windows) {
+                dirs_next::config_dir().map(|d| d.join("Mozilla/Firefox/Profiles"))
+            } else {
+                dirs_next::home_dir().map(|d| d.join(".mozilla/firefox"))
+            }
+            .ok_or_else(not_found)?;
+
+            // the profile used last has the most recently modified bookmarks
+            fs::read_dir(&profiles)
+                .map_err(|_| not_found())?
+                .filter_map(|e| e.ok().map(|e| e.path().join("places.sqlite")))
+                .filter_map(|p| Some((fs::metadata(&p).ok()?.modified().ok()?, p)))
+                .max()
+                .map(|(_, p)| p)
+                .ok_or_else(not_found)
+        },
+        "chrome" => {
+            let dir = if cfg!(windows) {
+                dirs_next::data_local_dir().map(|d| d.join("Google/Chrome/User Data"))
+            } else if cfg!(target_os = "macos") {
+                dirs_next::config_dir().map(|d| d.join("Google/Chrome"))
+            } else {
+                dirs_next::config_dir().map(|d| d.join("google-chrome"))
+            };
+            dir.map(|d| d.join("Default/Bookmarks"))
+                .ok_or_else(not_found)
+        },
+        "safari" => dirs_next::home_dir()
+            .map(|d| d.join("Library/Safari/Bookmarks.plist"))
+            .ok_or_else(not_found),
+        browser => unreachable!("unhandled browser `{}`", browser),
+    }
+}
+
+/// Returns a tag for a bookmark folder.
+fn folder_tag(name: &str, subtags: Vec<Tag>) -> Tag {
+    let now = usage::now();
+    Tag {
+        names: vec![bookmark_name(name, None)],
+        subtags: unique_names(subtags),
+        created: Some(now),
+        modified: Some(now),
+        ..Default::default()
+    }
+}
+
+/// Returns a tag for a bookmark.
+fn bookmark_tag(title: &str, url: &str) -> Tag {
+    let now = usage::now();
+    Tag {
+        names: vec![bookmark_name(title, Some(url))],
+        path: Some(url.to_string().into()),
+        created: Some(now),
+        modified: Some(now),
+        ..Default::default()
+    }
+}
+
+/// Returns the name of a tag for a bookmark or a folder with the title.
+///
+/// `/` separates the names of subtags, so it is replaced with `-`. Untitled
+/// bookmarks are named after their URL.
+fn bookmark_name(title: &str, url: Option<&str>) -> String {
+    let name = title.trim().replace('/', "-");
+    if !name.is_empty() {
+        return name;
+    }
+    url.and_then(analysis::suggest_name)
+        .unwrap_or_else(|| "untitled".to_string())
+}
+
+/// Renames the tags that share a name with an earlier sibling, as browsers
+/// allow bookmarks with the same title in a folder.
+fn unique_names(mut tags: Vec<Tag>) -> Vec<Tag> {
+    let mut taken: Vec<String> = Vec::new();
+    for tag in &mut tags {
+        let name = tag::unique_name(&tag.names[0], |n| taken.iter().any(|t| t == n));
+        taken.push(name.clone());
+        tag.names[0] = name;
+    }
+    tags
+}
+
+/// Removes the folders that have no bookmarks, however deep.
+fn prune_empty_folders(tags: &mut Vec<Tag>) {
+    for tag in tags.iter_mut() {
+        prune_empty_folders(&mut tag.subtags);
+    }
+    tags.retain(|t| t.path.is_some() || !t.subtags.is_empty());
+}
+
+/// Creates tags from the `Bookmarks` JSON file of Chrome or another Chromium
+/// browser.
+fn tags_from_chrome(file: &Path) -> Result<Vec<Tag>> {
+    fn convert(node: &Value) -> Option<Tag> {
+        let name = node["name"].as_str().unwrap_or_default();
+        match node["type"].as_str()? {
+            "url" => Some(bookmark_tag(name, node["url"].as_str()?)),
+            "folder" => Some(folder_tag(name, children(node))),
+            _ => None,
+        }
+    }
+    fn children(node: &Value) -> Vec<Tag> {
+        node["children"]
+            .as_array()
+            .map(|c| c.iter().filter_map(convert).collect())
+            .unwrap_or_default()
+    }
+
+    let contents = fs::read_to_string(file)?;
+    let json: Value = serde_json::from_str(&contents).map_err(|e| {
+        Error::new(
+            ErrorKind::Data,
+            format!("json error at path `{}`: {}", file.display(), e),
+        )
+    })?;
+
+    let roots = json["roots"]
+        .as_object()
+        .ok_or_else(|| Error::new(ErrorKind::Data, "chrome bookmarks have no roots"))?;
+    Ok(unique_names(
+        ["bookmark_bar", "other", "synced"]
+            .iter()
+            .filter_map(|r| roots.get(*r))
+            .filter_map(convert)
+            .collect(),
+    ))
+}
+
+/// Creates tags from the `places.sqlite` database of Firefox.
+///
+/// The database is read with the `sqlite3` program from a copy, as Firefox
+/// locks it while it runs.
+fn tags_from_firefox(file: &Path) -> Result<Vec<Tag>> {
+    const QUERY: &str = "SELECT b.id, b.parent, b.type, b.title, b.guid, p.url FROM moz_bookmarks \
+                         b LEFT JOIN moz_places p ON b.fk = p.id ORDER BY b.parent, b.position";
+    // the titles of the root folders are not meant to be shown
+    const ROOTS: &[(&str, &str)] = &[
+        ("toolbar_____", "Bookmarks Toolbar"),
+        ("menu________", "Bookmarks Menu"),
+        ("unfiled_____", "Other Bookmarks"),
+        ("mobile______", "Mobile Bookmarks"),
+    ];
+
+    let copy = env::temp_dir().join(format!("opentag-places-{}.sqlite", process::id()));
+    let wal = |p: &Path| PathBuf::from(format!("{}-wal", p.display()));
+    fs::copy(file, &copy)?;
+    if wal(file).exists() {
+        fs::copy(wal(file), wal(&copy))?;
+    }
+    let output = Command::new("sqlite3")
+        .args(["-json", "-readonly"])
+        .arg(&copy)
+        .arg(QUERY)
+        .output();
+    let _ = fs::remove_file(&copy);
+    let _ = fs::remove_file(wal(&copy));
+
+    let output = output.map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => "`sqlite3` is required to import Firefox bookmarks".into(),
+        _ => format!("unable to run `sqlite3`: {}", e),
+    })?;
+    if !output.status.success() {
+        return Err(Error::new(
+            ErrorKind::Data,
+            format!(
+                "unable to read `{}`: {}",
+                file.display(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+        ));
+    }
+
+    let rows: Vec<Value> = if output.stdout.iter().all(u8::is_ascii_whitespace) {
+        Vec::new()
+    } else {
+        serde_json::from_slice(&output.stdout)?
+    };
+    let mut children: HashMap<i64, Vec<&Value>> = HashMap::new();
+    for row in &rows {
+        if let Some(parent) = row["parent"].as_i64() {
+            children.entry(parent).or_default().push(row);
+        }
+    }
+
+    fn convert(row: &Value, children: &HashMap<i64, Vec<&Value>>) -> Option<Tag> {
+        let title = row["title"].as_str().unwrap_or_default();
+        match row["type"].as_i64()? {
+            1 => {
+                let url = row["url"].as_str()?;
+                // `place:` URLs are saved searches, not pages
+                (!url.starts_with("place:")).then(|| bookmark_tag(title, url))
+            },
+            2 => {
+                let subtags = children
+                    .get(&row["id"].as_i64()?)
+                    .map(|c| c.iter().filter_map(|r| convert(r, children)).collect())
+                    .unwrap_or_default();
+                Some(folder_tag(title, subtags))
+            },
+            _ => None,
+        }
+    }
+
+    Ok(ROOTS
+        .iter()
+        .filter_map(|(guid, name)| {
+            let row = rows.iter().find(|r| r["guid"].as_str() == Some(guid))?;
+            let mut tag = convert(row, &children)?;
+            tag.names = vec![name.to_string()];
+            Some(tag)
+        })
+        .collect())
+}
+
+/// Creates tags from the `Bookmarks.plist` file of Safari.
+///
+/// The file is converted to XML with the `plutil` program of macOS first.
+fn tags_from_safari(file: &Path) -> Result<Vec<Tag>> {
+    fn convert(node: &Value) -> Option<Tag> {
+        match node["WebBookmarkType"].as_str()? {
+            "WebBookmarkTypeLeaf" => {
+                let title = node["URIDictionary"]["title"].as_str().unwrap_or_default();
+                Some(bookmark_tag(title, node["URLString"].as_str()?))
+            },
+            "WebBookmarkTypeList" => {
+                let title = match node["Title"].as_str().unwrap_or_default() {
+                    "BookmarksBar" => "Favorites",
+                    "BookmarksMenu" => "Bookmarks Menu",
+                    "com.apple.ReadingList" => "Reading List",
+                    title => title,
+                };
+                let subtags = node["Children"]
+                    .as_array()
+                    .map(|c| c.iter().filter_map(convert).collect())
+                    .unwrap_or_default();
+                Some(folder_tag(title, subtags))
+            },
+            _ => None,
+        }
+    }
+
+    let output = Command::new("plutil")
+        .args(["-convert", "xml1", "-o", "-"])
+        .arg(file)
+        .output()
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => "`plutil` is required to import Safari bookmarks".into(),
+            _ => format!("unable to run `plutil`: {}", e),
+        })?;
+    if !output.status.success() {
+        return Err(Error::new(
+            ErrorKind::Data,
+            format!(
+                "unable to read `{}`: {}",
+                file.display(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+        ));
+    }
+
+    let plist = parse_plist(&String::from_utf8_lossy(&output.stdout))?;
+    Ok(convert(&plist).map(|t| t.subtags).unwrap_or_default())
+}
+
+/// Parses an XML property list into JSON values.
+///
+/// Data and dates are read as strings, and numbers as JSON numbers.
+fn parse_plist(xml: &str) -> Result<Value> {
+    let invalid = || Error::new(ErrorKind::Data, "invalid property list");
+
+    fn unescape(text: &str) -> String {
+        text.replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&quot;", "\"")
+            .replace("&apos;", "'")
+            .replace("&amp;", "&")
+    }
+
+    /// Parses the value starting at the first element of `xml`, returning it
+    /// along with the rest of the text.
+    fn value(xml: &str) -> Option<(Value, &str)> {
+        let start = xml.find('<')?;
+        let end = start + xml[start..].find('>')?;
+        let tag = &xml[start + 1..end];
+        let rest = &xml[end + 1..];
+
+        if let Some(tag) = tag.strip_suffix('/') {
+            return match tag.trim() {
+                "true" => Some((Value::Bool(true), rest)),
+                "false" => Some((Value::Bool(false), rest)),
+                "dict" => Some((Value::Object(Default::default()), rest)),
+                "array" => Some((Value::Array(Vec::new()), rest)),
+                "string" | "data" => Some((Value::String(String::new()), rest)),
+                _ => None,
+            };
+        }
+
+        match tag {
+            "dict" => {
+                let mut map = serde_json::Map::new();
+                let mut rest = rest;
+                loop {
+                    let trimmed = rest.trim_start();
+                    if let Some(after) = trimmed.strip_prefix("</dict>") {
+                        return Some((Value::Object(map), after));
+                    }
+                    let after_key = trimmed.strip_prefix("<key>")?;
+                    let key_end = after_key.find("</key>")?;
+                    let key = unescape(&after_key[..key_end]);
+                    let (v, after_value) = value(&after_key[key_end + "</key>".len()..])?;
+                    map.insert(key, v);
+                    rest = after_value;
+                }
+            },
+            "array" => {
+                let mut items = Vec::new();
+                let mut rest = rest;
+                loop {
+                    let trimmed = rest.trim_start();
+                    if let Some(after) = trimmed.strip_prefix("</array>") {
+                        return Some((Value::Array(items), after));
+                    }
+                    let (v, after_value) = value(trimmed)?;
+                    items.push(v);
+                    rest = after_value;
+                }
+            },
+            "string" | "data" | "date" | "integer" | "real" => {
+                let close = format!("</{}>", tag);
+                let text_end = rest.find(&close)?;
+                let text = unescape(&rest[..text_end]);
+                let after = &rest[text_end + close.len()..];
+                let v = match tag {
+                    "integer" | "real" => serde_json::from_str(text.trim()).ok()?,
+                    _ => Value::String(text),
+                };
+                Some((v, after))
+            },
+            // the XML declaration, the doctype, and the `plist` element
+            _ if tag.starts_with('?') || tag.starts_with('!') || tag.starts_with("plist") => {
+                value(rest)
+            },
+            _ => None,
+        }
+    }
+
+    value(xml).map(|(v, _)| v).ok_or_else(invalid)
+}
@@ -0,0 +1,18 @@
+//! The tags of `opentag` and the functions to load, search, change, and save
+//! them.
+//!
+//! The `ot` binary is a command-line interface over this library. Other
+//! frontends can use it to work with the same tags file without running `ot`.
+
+pub mod analysis;
+pub mod backup;
+mod cache;
+pub mod error;
+pub mod history;
+pub mod lock;
+pub mod tag;
+pub mod trash;
+pub mod usage;
+
+pub use error::{Error, ErrorKind, Result};
+pub use tag::{App, Tag, Tags};
@@ -0,0 +1,132 @@
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+/// The longest a URL is waited for, in seconds.
+const CHECK_TIMEOUT: &str = "10";
+
+/// Where the bodies of the responses are discarded.
+const NULL_DEVICE: &str = if cfg!(windows) { "NUL" } else { "/dev/null" };
+
+/// The health of a URL.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LinkStatus {
+    /// The URL responds successfully.
+    Ok,
+    /// The URL redirects to the final URL, which responds successfully.
+    Redirect(String),
+    /// The URL responds with an HTTP error status.
+    Broken(u32),
+    /// The URL did not respond in time.
+    Timeout,
+    /// The URL could not be reached, such as because its host does not exist.
+    Unreachable(String),
+}
+
+/// Checks the URLs with up to `parallel` requests at a time, returning their
+/// statuses in the order of the URLs.
+///
+/// `on_checked` is called from the checking threads after each URL.
+pub fn check_all<F: Fn() + Sync>(urls: &[&str], parallel: usize, on_checked: F) -> Vec<LinkStatus> {
+    let next = AtomicUsize::new(0);
+    let statuses = Mutex::new(vec![LinkStatus::Ok; urls.len()]);
+
+    thread::scope(|scope| {
+        for _ in 0..parallel.clamp(1, urls.len().max(1)) {
+            scope.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let url = match urls.get(i) {
+                    Some(url) => url,
+                    None => break,
+                };
+                let status = check(url);
+                statuses.lock().expect("no thread panics with the lock")[i] = status;
+                on_checked();
+            });
+        }
+    });
+
+    statuses
+        .into_inner()
+        .expect("no thread panics with the lock")
+}
+
+/// Checks the URL with `curl`, following redirects.
+///
+/// A `HEAD` request is made first, and a `GET` request if the server does not
+/// support `HEAD`.
+pub fn check(url: &str) -> LinkStatus {
+    log::info!("checking `{}`", url);
+    match request(url, true) {
+        Ok((405 | 403 | 501, _, _)) => match request(url, false) {
+            Ok(response) => status_of(url, response),
+            Err(status) => status,
+        },
+        Ok(response) => status_of(url, response),
+        Err(status) => status,
+    }
+}
+
+/// Returns the status of the URL from its final status code, its number of
+/// redirects, and its final URL.
+fn status_of(url: &str, (code, redirects, final_url): (u32, u32, String)) -> LinkStatus {
+    match code {
+        200..=299 if redirects > 0 && final_url != url => LinkStatus::Redirect(final_url),
+        200..=299 => LinkStatus::Ok,
+        code => LinkStatus::Broken(code),
+    }
+}
+
+/// Requests the URL with `curl`, returning its final status code, its number
+/// of redirects, and its final URL.
+fn request(url: &str, head: bool) -> Result<(u32, u32, String), LinkStatus> {
+    let mut cmd = Command::new("curl");
+    cmd.args(["--silent", "--show-error", "--location"])
+        .args(["--proto", "=http,https", "--max-time", CHECK_TIMEOUT])
+        .args(["--output", NULL_DEVICE])
+        .args([
+            "--write-out",
+            "%{http_code} %{num_redirects} %{url_effective}",
+        ]);
+    if head {
+        cmd.arg("--head");
+    }
+
+    let output = cmd.arg(url).output().map_err(|e| {
+        LinkStatus::Unreachable(match e.kind() {
+            std::io::ErrorKind::NotFound => "`curl` is required to check URLs".to_string(),
+            _ => format!("unable to run `curl`: {}", e),
+        })
+    })?;
+
+    // curl exits with 28 when the operation timed out
+    match output.status.code() {
+        Some(0) => {},
+        Some(28) => return Err(LinkStatus::Timeout),
+        _ => {
+            // errors look like `curl: (6) Could not resolve host: ...`
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let message = stderr.trim().trim_start_matches("curl: ");
+            let message = message
+                .split_once(") ")
+                .filter(|(code, _)| code.starts_with('('))
+                .map_or(message, |(_, m)| m);
+            return Err(LinkStatus::Unreachable(message.to_string()));
+        },
+    }
+
+    parse_write_out(&String::from_utf8_lossy(&output.stdout))
+        .ok_or_else(|| LinkStatus::Unreachable("invalid output from `curl`".to_string()))
+}
+
+/// Parses the status code, the number of redirects, and the final URL written
+/// by `curl --write-out`.
+fn parse_write_out(text: &str) -> Option<(u32, u32, String)> {
+    let mut parts = text.trim().splitn(3, ' ');
+    Some((
+        parts.next()?.parse().ok()?,
+        parts.next()?.parse().ok()?,
+        parts.next()?.to_string(),
+    ))
+}
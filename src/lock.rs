@@ -0,0 +1,105 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use std::{process, thread};
+
+use crate::error::{Error, ErrorKind, Result};
+
+/// How long to wait for another program to release the lock.
+pub const LOCK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How old a lock must be to be stale if it cannot be told whether the
+/// program holding it is still running.
+const STALE_AFTER: Duration = Duration::from_secs(10 * 60);
+
+/// Returns the path to the lock file kept next to the tags file.
+pub fn get_lock_path(tags_path: &Path) -> PathBuf {
+    tags_path.with_extension("lock")
+}
+
+/// An advisory lock on the tags file, held while its tags are read, changed,
+/// and written so that two programs changing them at once do not lose each
+/// other's changes.
+///
+/// The lock is a file next to the tags file holding the ID of the process,
+/// and is released when dropped.
+#[derive(Debug)]
+pub struct Lock {
+    path: PathBuf,
+}
+
+impl Lock {
+    /// Acquires the lock on the tags file, waiting up to [`LOCK_TIMEOUT`] for
+    /// another program to release it.
+    ///
+    /// A stale lock, left by a program that is no longer running, is taken
+    /// over.
+    pub fn acquire(tags_path: &Path) -> Result<Self> {
+        let path = get_lock_path(tags_path);
+        let start = Instant::now();
+
+        loop {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut file) => {
+                    let _ = write!(file, "{}", process::id());
+                    return Ok(Self { path });
+                },
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    if is_stale(&path) {
+                        let _ = fs::remove_file(&path);
+                        continue;
+                    }
+                    if start.elapsed() > LOCK_TIMEOUT {
+                        return Err(Error::new(
+                            ErrorKind::Io,
+                            format!(
+                                "the tags file is locked by another program; remove `{}` if no \
+                                 other `ot` is running",
+                                path.display()
+                            ),
+                        ));
+                    }
+                    thread::sleep(Duration::from_millis(50));
+                },
+                Err(e) => {
+                    return Err(Error::new(
+                        ErrorKind::Io,
+                        format!(
+                            "unable to lock the tags file at path `{}`: {}",
+                            path.display(),
+                            e
+                        ),
+                    ))
+                },
+            }
+        }
+    }
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Returns whether the lock at the path is stale.
+///
+/// On systems with `/proc`, a lock is stale if the process holding it is no
+/// longer running. Elsewhere, it is stale once it is older than
+/// [`STALE_AFTER`].
+fn is_stale(path: &Path) -> bool {
+    let proc = Path::new("/proc");
+    let pid = fs::read_to_string(path)
+        .ok()
+        .and_then(|pid| pid.trim().parse::<u32>().ok());
+    if let Some(pid) = pid.filter(|_| proc.is_dir()) {
+        return !proc.join(pid.to_string()).exists();
+    }
+
+    fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|modified| modified.elapsed().ok())
+        .is_some_and(|age| age > STALE_AFTER)
+}
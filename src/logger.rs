@@ -0,0 +1,47 @@
+use console::style;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+/// Logs diagnostics on the `stderr`.
+struct Logger;
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let level = match record.level() {
+            Level::Error => style("error").for_stderr().red(),
+            Level::Warn => style("warn").for_stderr().yellow(),
+            Level::Info => style("info").for_stderr().green(),
+            Level::Debug => style("debug").for_stderr().blue(),
+            Level::Trace => style("trace").for_stderr().magenta(),
+        };
+        eprintln!("{}: {}", level.bold(), record.args());
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: Logger = Logger;
+
+/// Enables the diagnostics for the number of times `--verbose` was given.
+///
+/// Once shows what `opentag` does, such as the tag that matched and how it is
+/// opened. Twice also shows the details, such as how the path was expanded.
+pub fn init(verbosity: u64) {
+    let level = match verbosity {
+        0 => LevelFilter::Off,
+        1 => LevelFilter::Info,
+        2 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    };
+
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(level);
+    }
+}
@@ -1,64 +1,549 @@
 mod app;
+mod clipboard;
+mod color;
 mod commands;
-mod error;
-mod tag;
+mod config;
+mod import;
+mod links;
+mod logger;
+mod opener;
+mod ops;
+mod output;
+mod picker;
+mod progress;
+mod report;
+mod secret;
+mod sources;
+mod sync;
+mod timings;
 
-use error::{exit, Result};
-use tag::Tag;
+use std::ffi::OsString;
+use std::path::Path;
+use std::time::Instant;
+use std::{env, fs, process};
+
+use clap::ArgMatches;
+use commands::MatchOptions;
+use config::{Config, NoPath};
+use opentag::error::{Error, ErrorKind, Result};
+use opentag::lock::Lock;
+use opentag::tag::{FileStamp, Tag, Tags};
+use opentag::{analysis, backup, error, history, tag, trash, usage};
+use report::exit;
 
 fn run_app() -> Result<()> {
-    let path = tag::get_tags_path()?;
+    timings::start();
+
+    // the tags are loaded before the arguments are parsed
+    let data_file = app::raw_option_value("data-file");
+    let path = tag::get_tags_path(data_file.as_deref().map(analysis::expand_path).as_deref())?;
     if !path.exists() {
         tag::create_tags_file(&path)?;
     }
-    let mut tags = tag::get_tags(&path)?;
-    let tags_clone = tags.clone();
+    let stamp = FileStamp::of(&path);
+    let tags = tag::get_tags(&path)?;
+    let config_path = config::get_config_path()?;
+    let config = config::get_config(&config_path)?;
+    let all_tags = sources::merge(tags.clone(), sources::load(&config.extra_sources));
+    timings::mark("load");
+
+    let app = app::create_base_app();
+    let mut args = app::normalize_tag_names(&app, env::args_os().collect());
+    if config.global_aliases {
+        args = app::expand_global_aliases(&app, &all_tags, args);
+    }
+    if let Some((program, external_args)) = app::external_command(&app, &all_tags, &args) {
+        let code = commands::external(&program, &external_args, &path, &config_path)?;
+        process::exit(code);
+    }
+    if let Some((i, mut found)) = app::nested_tags_named(&app, &all_tags, &args) {
+        let names = match found.len() {
+            1 => found.pop(),
+            _ if console::user_attended_stderr() => match commands::choose_tag(&found, &config)? {
+                Some(n) => Some(found.swap_remove(n)),
+                None => return Ok(()),
+            },
+            _ => None,
+        };
+        if let Some(names) = names {
+            log::info!("found nested tag `{}`", names.join("/"));
+            args.splice(i..=i, names.into_iter().map(OsString::from));
+        }
+    }
+    if config.prefix_matching {
+        args = app::expand_tag_prefixes(&app, &all_tags, args);
+    }
+    if console::user_attended_stderr() {
+        if let Some(pick_args) = app::unknown_tag_to_pick(&app, &all_tags, &args) {
+            args = pick_args;
+        }
+    }
+    let mut app = app::add_tags(app, &all_tags, &args);
+    let matches = match app.try_get_matches_from_mut(args) {
+        Ok(matches) => matches,
+        Err(e) if e.kind() == clap::ErrorKind::DisplayHelp => {
+            let no_pager = env::args_os().any(|a| a == "--no-pager");
+            return output::page(&e.to_string(), no_pager);
+        },
+        Err(e) if e.use_stderr() && report::is_json() => return Err(e.into()),
+        Err(e) => e.exit(),
+    };
+    timings::mark("build");
+    output::set_quiet(matches.contains_id("quiet"));
+    output::set_assume_yes(matches.contains_id("yes"));
+    logger::init(matches.occurrences_of("verbose"));
+    log::info!("tags file: {}", path.display());
+    log::info!("config file: {}", config_path.display());
+
+    let read_only = matches.contains_id("read-only") || !is_writable(&path);
+    if read_only {
+        log::info!("the tags file is read-only");
+        usage::set_recording(false);
+    }
+    if matches.contains_id("no-history") {
+        usage::set_recording(false);
+    }
+
+    let result = run_command(tags, &all_tags, &path, stamp, &config, &matches, read_only);
+
+    if matches.contains_id("timings") {
+        timings::print();
+    }
+
+    result
+}
+
+/// Returns whether the file can be written, such as not being on a read-only
+/// share or owned by another user.
+fn is_writable(path: &Path) -> bool {
+    fs::OpenOptions::new().append(true).open(path).is_ok()
+}
+
+/// Returns whether the built-in command changes the tags file.
+fn changes_tags(name: &str, sub_matches: &ArgMatches) -> bool {
+    match name {
+        "alias" | "apply" | "capture" | "copy" | "edit" | "import" | "init" | "merge"
+        | "reorder" | "sort" | "undo" => true,
+        "sync" => sub_matches.subcommand_name() == Some("pull"),
+        "trash" => matches!(sub_matches.subcommand_name(), Some("restore" | "empty")),
+        _ => false,
+    }
+}
+
+/// Locks the tags file for a command that changes it, reloading the tags if
+/// another program changed the file since they were loaded.
+fn lock_tags(tags: &mut Tags, path: &Path, stamp: &mut Option<FileStamp>) -> Result<Lock> {
+    let lock = Lock::acquire(path)?;
+    let current = FileStamp::of(path);
+    if current != *stamp {
+        log::info!("the tags file changed since it was loaded, reloading it");
+        *tags = tag::get_tags(path)?;
+        *stamp = current;
+    }
+    Ok(lock)
+}
 
-    let mut app = app::create_tags_app(&tags_clone);
-    let matches = app.get_matches_mut();
+/// Returns the error for a command that would change the tags file at the
+/// path while it is read-only.
+fn read_only_error(path: &Path) -> Error {
+    Error::new(
+        ErrorKind::Io,
+        format!(
+            "the tags file `{}` is read-only; this command would change it",
+            path.display()
+        ),
+    )
+}
 
+/// Runs the command matching the invocation.
+///
+/// `all_tags` are the local tags merged with the tags of the extra sources,
+/// which can be opened and searched but not changed. Commands that change the
+/// tags fail before doing anything if `read_only` is `true`, unless they are
+/// dry runs.
+fn run_command(
+    mut tags: Tags,
+    all_tags: &[Tag],
+    path: &Path,
+    mut stamp: Option<FileStamp>,
+    config: &Config,
+    matches: &ArgMatches,
+    read_only: bool,
+) -> Result<()> {
+    let dry_run = matches.contains_id("dry-run");
     if let Some((name, sub_matches)) = matches.subcommand() {
+        // `ot tag <NAME>` invokes the tag even if it is named like a command
+        let (name, sub_matches, namespaced) = match (name, sub_matches.subcommand()) {
+            ("tag", Some((name, tag_matches))) => (name, tag_matches, true),
+            _ => (name, sub_matches, false),
+        };
+        if app::is_builtin(name) && !namespaced {
+            // held until the changed tags are written
+            let mut _lock = None;
+            if !dry_run && changes_tags(name, sub_matches) {
+                if read_only {
+                    return Err(read_only_error(path));
+                }
+                _lock = Some(lock_tags(&mut tags, path, &mut stamp)?);
+            }
+
+            let modified = match name {
+                "__complete" => {
+                    commands::complete(all_tags, sub_matches);
+                    false
+                },
+                "alias" => {
+                    commands::alias(&mut tags, sub_matches)?;
+                    true
+                },
+                "aliases" => {
+                    commands::aliases(all_tags, sub_matches);
+                    false
+                },
+                "apply" => commands::apply(&mut tags, config, sub_matches)?,
+                "audit-urls" => commands::audit_urls(&mut tags, sub_matches, !read_only)?,
+                "capture" => {
+                    commands::capture(&mut tags, path, sub_matches, config)?;
+                    true
+                },
+                "copy" => {
+                    commands::copy(&mut tags, sub_matches)?;
+                    true
+                },
+                "diff" => {
+                    commands::diff(&tags, path, sub_matches)?;
+                    false
+                },
+                "dupes" => {
+                    commands::dupes(&tags, config);
+                    false
+                },
+                "edit" => commands::edit(&mut tags, sub_matches)?,
+                "exec" => {
+                    let code = commands::exec(all_tags, path, config, matches, sub_matches)?;
+                    if code != 0 {
+                        process::exit(code);
+                    }
+                    false
+                },
+                "export" => {
+                    commands::export(&tags, sub_matches)?;
+                    false
+                },
+                "grep" => {
+                    commands::grep(all_tags, sub_matches)?;
+                    false
+                },
+                "group" => {
+                    let code = commands::group(all_tags, path, config, matches, sub_matches)?;
+                    if code != 0 {
+                        process::exit(code);
+                    }
+                    false
+                },
+                "history" => {
+                    commands::history(path, matches, sub_matches)?;
+                    false
+                },
+                "import" => {
+                    commands::import(&mut tags, sub_matches)?;
+                    true
+                },
+                "info" => {
+                    commands::info(&tags, all_tags, path, config, sub_matches)?;
+                    false
+                },
+                "init" => {
+                    let count = commands::init(&mut tags, path, sub_matches)?;
+                    if save_tags(tags, path, stamp, matches)? {
+                        match count {
+                            1 => output::status(&format!(
+                                "Initialized `{}` with 1 tag.",
+                                path.display()
+                            )),
+                            n => output::status(&format!(
+                                "Initialized `{}` with {} tags.",
+                                path.display(),
+                                n
+                            )),
+                        }
+                    }
+                    return Ok(());
+                },
+                "lookup" => {
+                    commands::lookup(all_tags, config, sub_matches)?;
+                    false
+                },
+                "merge" => {
+                    commands::merge(&mut tags, sub_matches)?;
+                    true
+                },
+                "pick" => {
+                    commands::pick(all_tags, path, config, matches, sub_matches)?;
+                    false
+                },
+                "recent" => {
+                    commands::recent(&tags, sub_matches);
+                    false
+                },
+                "reorder" => {
+                    commands::reorder(&mut tags, sub_matches)?;
+                    true
+                },
+                "shell-init" => {
+                    commands::shell_init(sub_matches);
+                    false
+                },
+                "sort" => {
+                    commands::sort(&mut tags, sub_matches)?;
+                    true
+                },
+                "stale" => {
+                    commands::stale(&tags);
+                    false
+                },
+                "stats" => {
+                    commands::stats(&tags, path)?;
+                    false
+                },
+                "sync" => {
+                    commands::sync(&tags, path, stamp, config, matches, sub_matches)?;
+                    false
+                },
+                "top" => {
+                    commands::top(&tags, path, sub_matches)?;
+                    false
+                },
+                "trash" => match commands::trash(&mut tags, path, sub_matches)? {
+                    Some(removed) => {
+                        if save_tags(tags, path, stamp, matches)? {
+                            trash::write_trash(trash::get_trash_path(path), &removed)?;
+                        }
+                        return Ok(());
+                    },
+                    None => false,
+                },
+                "undo" => {
+                    commands::undo(&tags, path, stamp, matches)?;
+                    false
+                },
+                "unused" => {
+                    commands::unused(&tags, path, sub_matches)?;
+                    false
+                },
+                "version" => {
+                    commands::version(&tags, path, sub_matches)?;
+                    false
+                },
+                _ => unreachable!("unhandled built-in command `{}`", name),
+            };
+
+            if modified {
+                save_tags(tags, path, stamp, matches)?;
+            }
+            return Ok(());
+        }
+
         if matches.contains_id("cmd-conflict") && !matches.contains_id("list") {
             return Err("this argument cannot be used with a tag".into());
         }
+        if matches.contains_id("label") && !matches.contains_id("list") {
+            return Err("`--label` requires `--list` with a tag".into());
+        }
+        if matches.contains_id("recursive")
+            && !matches.contains_id("list")
+            && !matches.contains_id("paths")
+        {
+            return Err("`--recursive` requires `--list` or `--paths`".into());
+        }
 
-        if let Some(tag) = tag::find_tag(&tags, name, sub_matches) {
-            commands::run_tag(tag, &matches)?;
-        } else {
-            return Err("no tag found".into());
+        let found = app::find_tag(all_tags, name, sub_matches);
+        timings::mark("resolve");
+
+        let (mut tag, mut names) =
+            found.ok_or_else(|| Error::new(ErrorKind::NotFound, "no tag found"))?;
+        log::info!("matched tag `{}`", names.join("/"));
+
+        let mut options = MatchOptions::from_matches(matches, &config.defaults);
+        options.query = app::query_words(sub_matches);
+        options.load_usage(path);
+        if options.random && !options.list {
+            tag = tag::random_subtag(tag).ok_or("tag has no subtags with a path or url")?;
+            names.push(tag.names[0].clone());
+            log::info!("picked random subtag `{}`", names.join("/"));
         }
-    } else if matches.contains_id("list") {
-        if app.has_subcommands() {
-            app = app.help_template("TAGS\n{subcommands}");
-            for subcmd in app.get_subcommands_mut() {
-                *subcmd = subcmd.clone().hide(false);
+
+        // tags without a path are often folders of their subtags
+        let opens_path = !options.list
+            && !options.paths
+            && !options.notes
+            && !options.copy_about
+            && !options.info;
+        let has_no_path =
+            |t: &Tag| t.path().is_none() && t.fallback_paths.is_empty() && t.secret.is_none();
+        if opens_path && has_no_path(tag) && !tag.subtags.is_empty() {
+            match config.on_no_path {
+                NoPath::List => return commands::list(&tag.subtags, &names, &options, config),
+                NoPath::Select if console::user_attended_stderr() => {
+                    while has_no_path(tag) && !tag.subtags.is_empty() {
+                        tag = match commands::select_subtag(tag, &names, config)? {
+                            Some(subtag) => subtag,
+                            None => return Ok(()),
+                        };
+                        names.push(tag.names[0].clone());
+                    }
+                },
+                NoPath::Select | NoPath::Error => {},
             }
+        }
 
-            app.print_help()?;
-        } else {
-            println!("No tags!");
+        if options.info {
+            let ancestor_app = tag::ancestor_app(all_tags, &names);
+            return commands::tag_info(tag, &names, ancestor_app, path, &options, config);
+        }
+
+        let revealed;
+        if tag.secret.is_some() && !options.list && !options.notes && !options.paths {
+            revealed = match secret::reveal(tag, &names.join("/"), &config.secrets)? {
+                Some(tag) => tag,
+                None => return Ok(()),
+            };
+            tag = &revealed;
+        }
+
+        let ancestor_app = tag::ancestor_app(all_tags, &names);
+        let code = commands::run_tag(tag, &names, ancestor_app, &options, config)?;
+        if !options.list && !options.paths && analysis::is_stale(tag, usage::now()) {
+            output::note(&format!(
+                "`{}` was due for review on {}; update its `review_after` date once checked",
+                names.join("/"),
+                tag.review_after.as_deref().unwrap_or_default()
+            ));
+        }
+
+        if !options.list && !options.dry_run {
+            commands::record_open(all_tags, path, &names.join("/"), options.action(), config)?;
         }
+        if code != 0 {
+            process::exit(code);
+        }
+    } else if matches.contains_id("list") {
+        let mut options = MatchOptions::from_matches(matches, &config.defaults);
+        options.load_usage(path);
+        commands::list(all_tags, &[], &options, config)?;
     } else {
-        let action = if matches.contains_id("add") {
-            commands::add(&mut tags)?;
-            "Added"
-        } else if matches.contains_id("remove") {
-            commands::remove(&mut tags)?;
-            "Removed"
-        } else if matches.contains_id("update") {
-            commands::update(&mut tags)?;
-            "Updated"
+        if matches.contains_id("recursive") {
+            return Err("`--recursive` requires `--list` or `--paths`".into());
+        }
+        if matches.contains_id("label")
+            && (matches.contains_id("remove")
+                || ["pin", "unpin", "edit-notes"]
+                    .iter()
+                    .any(|id| matches.contains_id(id)))
+        {
+            return Err("`--label` cannot be used with this argument".into());
+        }
+        let mut _lock = None;
+        if !dry_run {
+            if read_only {
+                return Err(read_only_error(path));
+            }
+            _lock = Some(lock_tags(&mut tags, path, &mut stamp)?);
+        }
+
+        let old = tags.clone();
+        let start = Instant::now();
+        let added = if matches.contains_id("from-dir") {
+            Some(commands::add_from_dir(&mut tags, matches)?)
+        } else if matches.contains_id("stdin") {
+            Some(commands::add_from_stdin(&mut tags, matches)?)
+        } else if matches.contains_id("template") {
+            Some(commands::add_from_template(&mut tags, matches, config)?)
         } else {
-            return Err("invalid invocation".into());
+            None
+        };
+
+        let mut removed = None;
+        let message = if let Some(added) = added {
+            commands::check_unique_names(&old, &tags, config)?;
+            // the time spent confirming the files to add is not counted
+            let took = if matches.contains_id("from-dir") {
+                String::new()
+            } else {
+                progress::took(start)
+            };
+            match added {
+                0 => return Ok(()),
+                1 => format!("Added 1 tag{}.", took),
+                n => format!("Added {} tags{}.", n, took),
+            }
+        } else {
+            let message = if matches.contains_id("add") {
+                commands::add(&mut tags, path, matches, config)?;
+                "Added tag."
+            } else if matches.contains_id("remove") {
+                removed = commands::remove(&mut tags, path, config)?;
+                "Removed tag."
+            } else if matches.contains_id("update") {
+                commands::update(&mut tags, path, matches, config)?;
+                "Updated tag."
+            } else {
+                return Err("invalid invocation".into());
+            };
+
+            commands::check_unique_names(&old, &tags, config)?;
+            // the tags picked interactively are reviewed before they are saved
+            if !dry_run && !commands::confirm_changes(&old, &tags)? {
+                return Err(Error::new(ErrorKind::Abort, "the changes were discarded"));
+            }
+            message.to_string()
         };
 
-        tag::write_tags(tags, &path)?;
-        println!("\n{} tag.", action);
+        if save_tags(tags, path, stamp, matches)? {
+            if let Some(entry) = removed {
+                let trash_path = trash::get_trash_path(path);
+                let mut trash = trash::get_trash(&trash_path)?;
+                trash.push(entry);
+                trash::write_trash(&trash_path, &trash)?;
+            }
+            output::status(&format!("\n{}", message));
+        }
     }
 
     Ok(())
 }
 
+/// Validates the tags and writes them at the path, returning `true` if they
+/// were written.
+///
+/// Nothing is written if the file changed since it was loaded with `stamp`. In
+/// a dry run, the contents that would be written are printed instead.
+fn save_tags(
+    tags: Tags,
+    path: &Path,
+    stamp: Option<FileStamp>,
+    matches: &ArgMatches,
+) -> Result<bool> {
+    tag::validate_tags(&tags)?;
+    timings::mark("validate");
+
+    if !matches.contains_id("dry-run") {
+        tag::write_tags(tags, path, stamp)?;
+        timings::mark("write");
+        return Ok(true);
+    }
+
+    let contents = tag::serialize_tags(&tags)?;
+    if fs::read_to_string(path).is_ok_and(|c| c == contents) {
+        println!("Would leave `{}` unchanged.", path.display());
+    } else {
+        println!("Would write to `{}`:\n{}", path.display(), contents);
+    }
+    Ok(false)
+}
+
 fn main() {
-    run_app().unwrap_or_else(|e| exit(e, 1));
+    color::init();
+    report::init();
+    run_app().unwrap_or_else(|e| exit(e));
 }
@@ -0,0 +1,499 @@
+use std::path::Path;
+use std::process::Command;
+use std::{env, io};
+
+use crate::config::Config;
+use crate::error::Result;
+use crate::tag::App;
+use crate::{analysis, output};
+
+/// Options for opening a path.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct OpenOptions<'a> {
+    /// The app to open the path with, instead of the system program.
+    pub app: Option<&'a App>,
+    /// Whether to open the path without bringing the app to the foreground.
+    ///
+    /// Only supported on macOS and Windows, and not for apps given as a
+    /// program with arguments.
+    pub background: bool,
+    /// The working directory of the app.
+    pub cwd: Option<&'a Path>,
+    /// The environment variables set for the app.
+    pub env: &'a [(String, String)],
+    /// Whether to wait for the app to exit.
+    ///
+    /// Without an app, this is not supported by every system program on
+    /// Linux and BSD.
+    pub wait: bool,
+    /// The line to open the file at, in editors that are known to take one.
+    pub line: Option<u32>,
+    /// Print how the path would be opened instead of opening it.
+    pub dry_run: bool,
+}
+
+/// Opens the path with the given app, or with the configured system program.
+///
+/// If no app is given, `$OPENTAG_OPENER` is used for every path, and
+/// `$BROWSER` for `http` and `https` URLs, before falling back to the system
+/// program.
+///
+/// If there is no graphical session, the configured headless opener is used
+/// instead of the system program, or the path is printed if there is none.
+///
+/// With `options.line`, the file is opened with the app if it is a known
+/// editor, or else with `$VISUAL` or `$EDITOR`, which are told the line.
+///
+/// Returns the exit code of the app if `options.wait` is set, or `0`
+/// otherwise. In a dry run, the command that would open the path is printed
+/// instead.
+pub fn open(path: &str, options: OpenOptions, config: &Config) -> Result<i32> {
+    let absolute;
+    let path = match options.cwd {
+        Some(cwd) => {
+            // relative paths are relative to where `ot` was run, not to the
+            // working directory of the app
+            let path = if !analysis::is_url(path) && Path::new(path).is_relative() {
+                absolute = env::current_dir()?
+                    .join(path)
+                    .to_string_lossy()
+                    .into_owned();
+                absolute.as_str()
+            } else {
+                path
+            };
+
+            log::info!("running the app in `{}`", cwd.display());
+            if options.dry_run {
+                println!("Would run in `{}`.", cwd.display());
+            } else if !cwd.is_dir() {
+                return Err(format!(
+                    "unable to change directory to `{}`: not a directory",
+                    cwd.display()
+                )
+                .into());
+            }
+            path
+        },
+        None => path,
+    };
+
+    for (key, value) in options.env {
+        log::info!("setting `{}` to `{}` for the app", key, value);
+        if options.dry_run {
+            println!("Would set `{}` to `{}`.", key, value);
+        }
+    }
+
+    if let Some(line) = options.line {
+        let editor = match options.app.and_then(App::current) {
+            Some(App::Command(command)) => command.clone(),
+            Some(App::Name(name)) => vec![name.clone()],
+            _ => env_var("VISUAL")
+                .or_else(|| env_var("EDITOR"))
+                .map(|e| e.split_whitespace().map(String::from).collect())
+                .unwrap_or_default(),
+        };
+        match editor.first() {
+            Some(program) if line_args(program, path, line).is_some() => {
+                log::info!("opening `{}` at line {} with {:?}", path, line, editor);
+                return run_command(editor.iter().map(String::as_str), path, options);
+            },
+            Some(program) => output::warn(&format!(
+                "`{}` is not a known editor, so the file is opened without going to line {}",
+                program, line
+            )),
+            None => output::warn(&format!(
+                "there is no editor to open the file at line {}; set the tag's app or `$EDITOR`",
+                line
+            )),
+        }
+    }
+
+    let app_name = match options.app.and_then(App::current) {
+        Some(App::Name(name)) => Some(name.as_str()),
+        Some(App::Command(command)) => {
+            log::info!("opening `{}` with {:?}", path, command);
+            if options.background && !options.wait {
+                output::warn(
+                    "apps given as a program with arguments cannot be opened in the background, \
+                     so the app is opened in the foreground",
+                );
+            }
+            return start_command(command.iter().map(String::as_str), path, options);
+        },
+        // apps by operating system are never nested
+        Some(App::PerOs(_)) | None => None,
+    };
+
+    if options.background && !options.wait {
+        if let Some(mut cmd) = background_command(path, app_name) {
+            log::info!("opening `{}` in the background", path);
+            return run(&mut cmd, options);
+        }
+    }
+
+    if let Some(app) = app_name {
+        log::info!("opening `{}` with `{}`", path, app);
+        if options.wait {
+            return run(&mut wait_command(path, Some(app)), options);
+        }
+        if options.dry_run {
+            println!("Would open `{}` with `{}`.", path, app);
+            return Ok(0);
+        }
+        in_app_context(options, || open::with(path, app))?;
+        return Ok(0);
+    }
+
+    if let Some(opener) = env_var("OPENTAG_OPENER") {
+        log::info!("opening `{}` with `$OPENTAG_OPENER`", path);
+        return start_command(opener.split_whitespace(), path, options);
+    }
+
+    if is_web_url(path) {
+        if let Some(browsers) = env_var("BROWSER") {
+            // `$BROWSER` is a colon-separated list of browsers to try in order
+            let mut last_err = None;
+            for browser in browsers.split(':').filter(|b| !b.trim().is_empty()) {
+                log::info!("opening `{}` with `$BROWSER` command `{}`", path, browser);
+                match start_command(browser.split_whitespace(), path, options) {
+                    Ok(code) => return Ok(code),
+                    Err(e) => {
+                        log::debug!("`{}` failed: {}", browser, e);
+                        last_err = Some(e);
+                    },
+                }
+            }
+            if let Some(e) = last_err {
+                return Err(e);
+            }
+        }
+    }
+
+    if is_headless() {
+        return if let Some(ref opener) = config.headless_opener {
+            log::info!("opening `{}` with the headless opener", path);
+            run_command_line(&analysis::expand_path(opener), path, options)
+        } else {
+            if !output::is_quiet() {
+                eprintln!("No graphical session found, printing the path instead.");
+            }
+            println!("{}", path);
+            Ok(0)
+        };
+    }
+
+    log::info!("opening `{}` with the system program", path);
+    if options.wait {
+        return run(&mut wait_command(path, None), options);
+    }
+    if options.dry_run {
+        println!("Would open `{}` with the system program.", path);
+        return Ok(0);
+    }
+    in_app_context(options, || open::that(path))?;
+    Ok(0)
+}
+
+/// Runs `f` in the working directory and with the environment of the app, if
+/// they are given, and changes them back afterwards.
+///
+/// The system program is started by `open`, which takes neither, so the app
+/// inherits those of `ot` instead. Commands built here are given them with
+/// [`set_app_context`].
+fn in_app_context<T, F: FnOnce() -> io::Result<T>>(options: OpenOptions, f: F) -> Result<T> {
+    let previous_dir = match options.cwd {
+        Some(cwd) => {
+            let previous = env::current_dir()?;
+            env::set_current_dir(cwd)
+                .map_err(|e| format!("unable to change directory to `{}`: {}", cwd.display(), e))?;
+            Some(previous)
+        },
+        None => None,
+    };
+    let previous_env = options
+        .env
+        .iter()
+        .map(|(key, value)| {
+            let previous = env::var_os(key);
+            env::set_var(key, value);
+            (key, previous)
+        })
+        .collect::<Vec<_>>();
+
+    let result = f();
+
+    for (key, previous) in previous_env.into_iter().rev() {
+        match previous {
+            Some(value) => env::set_var(key, value),
+            None => env::remove_var(key),
+        }
+    }
+    if let Some(previous) = previous_dir {
+        env::set_current_dir(&previous).map_err(|e| {
+            format!(
+                "unable to change directory back to `{}`: {}",
+                previous.display(),
+                e
+            )
+        })?;
+    }
+    Ok(result?)
+}
+
+/// Sets the working directory and the environment of the app on the command,
+/// if they are given.
+fn set_app_context(cmd: &mut Command, options: OpenOptions) {
+    if let Some(cwd) = options.cwd {
+        cmd.current_dir(cwd);
+    }
+    cmd.envs(options.env.iter().map(|(k, v)| (k, v)));
+}
+
+/// Returns `true` if the path is an `http` or `https` URL.
+///
+/// URLs with other schemes, such as `mailto:` or app-specific ones, are left
+/// to the system program, which knows the app registered for them.
+fn is_web_url(path: &str) -> bool {
+    path.split_once("://").is_some_and(|(scheme, _)| {
+        scheme.eq_ignore_ascii_case("http") || scheme.eq_ignore_ascii_case("https")
+    })
+}
+
+/// Returns `true` if there is no graphical session to open paths in.
+///
+/// Always `false` on Windows and macOS.
+fn is_headless() -> bool {
+    cfg!(all(unix, not(target_os = "macos")))
+        && env_var("DISPLAY").is_none()
+        && env_var("WAYLAND_DISPLAY").is_none()
+}
+
+/// Returns the value of the environment variable if it is set and not empty.
+fn env_var(key: &str) -> Option<String> {
+    env::var(key).ok().filter(|v| !v.trim().is_empty())
+}
+
+/// Runs the command line with the path and waits for it to exit.
+///
+/// Every `%s` in the command line is replaced with the path, and `%%` with a
+/// literal `%`. The path is appended as the last argument if there is no `%s`.
+fn run_command_line(command_line: &str, path: &str, options: OpenOptions) -> Result<i32> {
+    run_command(command_line.split_whitespace(), path, options)
+}
+
+/// Runs the program followed by its arguments with the path and waits for it
+/// to exit, substituting the path as [`run_command_line`] does.
+///
+/// With `options.line`, the path is passed with the line as the program takes
+/// it, if it is a known editor, where it is a whole argument or appended.
+fn run_command<'a, I: IntoIterator<Item = &'a str>>(
+    command: I,
+    path: &str,
+    options: OpenOptions,
+) -> Result<i32> {
+    run(&mut path_command(command, path, options)?, options)
+}
+
+/// Starts the program followed by its arguments with the path, substituting
+/// the path as [`run_command_line`] does, and returns without waiting for it
+/// to exit, as the system program does.
+///
+/// It is waited for if `options.wait` is set, or if there is no graphical
+/// session, where the program runs in the terminal `ot` was run in.
+fn start_command<'a, I: IntoIterator<Item = &'a str>>(
+    command: I,
+    path: &str,
+    options: OpenOptions,
+) -> Result<i32> {
+    let mut cmd = path_command(command, path, options)?;
+    if options.wait || is_headless() {
+        run(&mut cmd, options)
+    } else {
+        spawn(&mut cmd, options)
+    }
+}
+
+/// Returns the command that runs the program followed by its arguments with
+/// the path, substituting the path as [`run_command_line`] does.
+fn path_command<'a, I: IntoIterator<Item = &'a str>>(
+    command: I,
+    path: &str,
+    options: OpenOptions,
+) -> Result<Command> {
+    let mut parts = command.into_iter();
+    let program = parts.next().ok_or("empty command")?;
+    let path_args = options
+        .line
+        .and_then(|line| line_args(program, path, line))
+        .unwrap_or_else(|| vec![path.to_string()]);
+
+    let mut substituted = false;
+    let mut args = Vec::new();
+    for arg in parts {
+        if arg == "%s" {
+            substituted = true;
+            args.extend(path_args.iter().cloned());
+        } else {
+            let (arg, has_path) = substitute(arg, path);
+            substituted |= has_path;
+            args.push(arg);
+        }
+    }
+    if !substituted {
+        args.extend(path_args);
+    }
+
+    let mut cmd = Command::new(program);
+    cmd.args(&args);
+    Ok(cmd)
+}
+
+/// Replaces every `%s` in the argument with the path and `%%` with a literal
+/// `%`, in one pass so that neither is read in the path or in the other.
+///
+/// Returns the argument with whether it had a `%s`.
+fn substitute(arg: &str, path: &str) -> (String, bool) {
+    let mut substituted = String::with_capacity(arg.len());
+    let mut has_path = false;
+    let mut chars = arg.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            substituted.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('s') => {
+                substituted.push_str(path);
+                has_path = true;
+            },
+            Some('%') => substituted.push('%'),
+            Some(other) => {
+                substituted.push('%');
+                substituted.push(other);
+            },
+            None => substituted.push('%'),
+        }
+    }
+    (substituted, has_path)
+}
+
+/// Returns the arguments that open the file at the line in the program, in
+/// place of the path, if it is a known editor.
+fn line_args(program: &str, path: &str, line: u32) -> Option<Vec<String>> {
+    let name = Path::new(program)
+        .file_stem()?
+        .to_string_lossy()
+        .to_lowercase();
+    let args = match name.as_str() {
+        "vi" | "vim" | "nvim" | "gvim" | "mvim" | "nano" | "pico" | "emacs" | "emacsclient"
+        | "kak" | "micro" | "gedit" | "mg" | "joe" => vec![format!("+{}", line), path.to_string()],
+        "hx" | "helix" | "subl" | "sublime_text" | "zed" | "atom" => {
+            vec![format!("{}:{}", path, line)]
+        },
+        "code" | "code-insiders" | "codium" | "vscodium" | "cursor" | "windsurf" => {
+            vec!["--goto".to_string(), format!("{}:{}", path, line)]
+        },
+        "idea" | "pycharm" | "webstorm" | "clion" | "goland" | "rubymine" | "phpstorm"
+        | "rider" | "studio" | "kate" | "mate" => {
+            vec!["--line".to_string(), line.to_string(), path.to_string()]
+        },
+        _ => return None,
+    };
+    Some(args)
+}
+
+/// Returns the command that opens the path without bringing the app to the
+/// foreground, if the platform supports it.
+fn background_command(path: &str, app: Option<&str>) -> Option<Command> {
+    if cfg!(target_os = "macos") {
+        let mut cmd = Command::new("open");
+        cmd.arg("-g");
+        if let Some(app) = app {
+            cmd.args(["-a", app]);
+        }
+        cmd.arg(path);
+        Some(cmd)
+    } else if cfg!(windows) {
+        let mut cmd = Command::new("cmd");
+        cmd.args(["/C", "start", "/MIN", ""]);
+        if let Some(app) = app {
+            cmd.arg(app);
+        }
+        cmd.arg(path);
+        Some(cmd)
+    } else {
+        None
+    }
+}
+
+/// Returns the command that opens the path and exits when the app exits.
+///
+/// On Linux and BSD, the app is run directly if it is given. Otherwise,
+/// `xdg-open` is used, which may exit before the app does.
+fn wait_command(path: &str, app: Option<&str>) -> Command {
+    if cfg!(target_os = "macos") {
+        let mut cmd = Command::new("open");
+        cmd.arg("-W");
+        if let Some(app) = app {
+            cmd.args(["-a", app]);
+        }
+        cmd.arg(path);
+        cmd
+    } else if cfg!(windows) {
+        let mut cmd = Command::new("cmd");
+        cmd.args(["/C", "start", "/WAIT", ""]);
+        if let Some(app) = app {
+            cmd.arg(app);
+        }
+        cmd.arg(path);
+        cmd
+    } else {
+        let mut cmd = Command::new(app.unwrap_or("xdg-open"));
+        cmd.arg(path);
+        cmd
+    }
+}
+
+/// Starts the command without waiting for it to exit.
+///
+/// Returns `0` if it started. The command is printed instead in a dry run.
+fn spawn(cmd: &mut Command, options: OpenOptions) -> Result<i32> {
+    set_app_context(cmd, options);
+    let program = cmd.get_program().to_string_lossy().into_owned();
+    log::info!("starting {:?}", cmd);
+    if options.dry_run {
+        println!("Would run {:?}.", cmd);
+        return Ok(0);
+    }
+    cmd.spawn()
+        .map_err(|e| format!("unable to run `{}`: {}", program, e))?;
+    Ok(0)
+}
+
+/// Runs the command and waits for it to exit.
+///
+/// Returns the exit code of the command if `options.wait` is set, or `0` if
+/// it exited successfully. The command is printed instead in a dry run.
+fn run(cmd: &mut Command, options: OpenOptions) -> Result<i32> {
+    set_app_context(cmd, options);
+    let program = cmd.get_program().to_string_lossy().into_owned();
+    log::info!("running {:?}", cmd);
+    if options.dry_run {
+        println!("Would run {:?}.", cmd);
+        return Ok(0);
+    }
+    let status = cmd
+        .status()
+        .map_err(|e| format!("unable to run `{}`: {}", program, e))?;
+
+    if options.wait {
+        // killed by a signal if there is no code
+        Ok(status.code().unwrap_or(1))
+    } else if status.success() {
+        Ok(0)
+    } else {
+        Err(format!("`{}` exited with {}", program, status).into())
+    }
+}
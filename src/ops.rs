@@ -0,0 +1,257 @@
+use serde::Deserialize;
+
+use crate::error::{Error, ErrorKind, Result};
+use crate::tag::{self, App, Tag, TagPath, Tags};
+use crate::usage;
+
+/// A file of operations applied to the tags at once with `ot apply`.
+#[derive(Debug, Deserialize)]
+pub struct OpsFile {
+    /// The operations, applied in order.
+    pub ops: Vec<Op>,
+}
+
+/// An operation on the tags, naming tags by their paths such as `web/github`.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum Op {
+    /// Adds the tag, with its subtags, under the parent, which is added if it
+    /// does not exist.
+    Add {
+        /// The path of the parent, or none for the global tags.
+        #[serde(default)]
+        parent: String,
+        /// The tag to add.
+        tag: Tag,
+    },
+    /// Sets fields of the tag.
+    Update {
+        /// The path of the tag.
+        tag: String,
+        /// The fields to set.
+        set: Fields,
+    },
+    /// Removes the tag and its subtags.
+    Remove {
+        /// The path of the tag.
+        tag: String,
+    },
+    /// Moves the tag and its subtags under another tag, which is added if it
+    /// does not exist.
+    Move {
+        /// The path of the tag.
+        tag: String,
+        /// The path of the new parent, or `/` for the global tags.
+        to: String,
+    },
+}
+
+impl Op {
+    /// Returns the name of the operation, as written in the file.
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Add { .. } => "add",
+            Self::Update { .. } => "update",
+            Self::Remove { .. } => "remove",
+            Self::Move { .. } => "move",
+        }
+    }
+}
+
+/// The fields of a tag set by an update. Fields that are not given are kept.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Fields {
+    /// The names of the tag.
+    names: Option<Vec<String>>,
+    /// The path of the tag.
+    #[serde(alias = "url", alias = "link")]
+    path: Option<TagPath>,
+    /// The description of the tag.
+    about: Option<String>,
+    /// The notes of the tag.
+    notes: Option<String>,
+    /// The app to open the tag with.
+    app: Option<App>,
+    /// The labels of the tag.
+    labels: Option<Vec<String>>,
+    /// The icon of the tag.
+    icon: Option<String>,
+    /// The color of the name of the tag.
+    color: Option<String>,
+    /// The date after which the tag should be reviewed.
+    review_after: Option<String>,
+    /// Whether the tag is pinned.
+    pinned: Option<bool>,
+}
+
+/// Parses the operations from the text of a file, as TOML if `toml` is `true`
+/// and as JSON otherwise.
+pub fn parse(text: &str, toml: bool) -> Result<Vec<Op>> {
+    let file: OpsFile = if toml {
+        toml::from_str(text)
+            .map_err(|e| Error::new(ErrorKind::Data, format!("toml error: {}", e)))?
+    } else {
+        serde_json::from_str(text)?
+    };
+    Ok(file.ops)
+}
+
+/// Applies the operations to the tags in order.
+///
+/// Added tags are passed to `add` with the subtags of their parent, which is
+/// added if it does not exist, and the tag path of the parent, to add them
+/// there and resolve any conflict with the names of the other subtags.
+///
+/// The first operation that fails stops the others, with an error naming it,
+/// so the tags must only be saved if all of them succeed.
+pub fn apply<F>(tags: &mut Tags, ops: Vec<Op>, mut add: F) -> Result<()>
+where
+    F: FnMut(&mut Tags, Tag, &str) -> Result<()>,
+{
+    let now = usage::now();
+    for (i, op) in ops.into_iter().enumerate() {
+        let name = op.name();
+        apply_one(tags, op, now, &mut add).map_err(|e| {
+            Error::new(
+                e.kind(),
+                format!("operation {} ({}) failed: {}", i + 1, name, e),
+            )
+        })?;
+    }
+    Ok(())
+}
+
+/// Applies one operation to the tags, adding tags with `add`.
+fn apply_one<F>(tags: &mut Tags, op: Op, now: u64, add: &mut F) -> Result<()>
+where
+    F: FnMut(&mut Tags, Tag, &str) -> Result<()>,
+{
+    match op {
+        Op::Add { parent, mut tag } => {
+            stamp(&mut tag, now);
+            tag.names = tag.names.iter().map(|n| tag::normalize_name(n)).collect();
+            check_names(&[], &tag.names, None)?;
+            let siblings = tag::find_or_create_subtags_mut(tags, &tag::split_tag_path(&parent));
+            add(siblings, tag, parent.trim_matches('/'))
+        },
+        Op::Update { tag: tag_path, set } => {
+            let names = tag::split_tag_path(&tag_path);
+            let (last, parent_names) = names.split_last().ok_or_else(|| not_found(&tag_path))?;
+            let siblings = tag::find_subtags_by_names_mut(tags, parent_names)
+                .ok_or_else(|| not_found(&tag_path))?;
+            let i = siblings
+                .iter()
+                .position(|t| t.has_name(last))
+                .ok_or_else(|| not_found(&tag_path))?;
+
+            if let Some(new_names) = set.names {
+                let new_names = new_names
+                    .iter()
+                    .map(|n| tag::normalize_name(n))
+                    .collect::<Vec<_>>();
+                check_names(siblings, &new_names, Some(i))?;
+                siblings[i].names = new_names;
+            }
+            let tag = &mut siblings[i];
+            if let Some(path) = set.path {
+                tag.path = Some(path);
+            }
+            if let Some(about) = set.about {
+                tag.about = Some(about);
+            }
+            if let Some(notes) = set.notes {
+                tag.notes = Some(notes);
+            }
+            if let Some(app) = set.app {
+                tag.app = Some(app);
+            }
+            if let Some(labels) = set.labels {
+                tag.labels = labels;
+            }
+            if let Some(icon) = set.icon {
+                tag.icon = Some(icon);
+            }
+            if let Some(color) = set.color {
+                tag.color = Some(color);
+            }
+            if let Some(review_after) = set.review_after {
+                tag.review_after = Some(review_after);
+            }
+            if let Some(pinned) = set.pinned {
+                tag.pinned = pinned;
+            }
+            tag.modified = Some(now);
+            Ok(())
+        },
+        Op::Remove { tag: tag_path } => {
+            tag::remove_tag_by_names(tags, &tag::split_tag_path(&tag_path))
+                .map(|_| ())
+                .ok_or_else(|| not_found(&tag_path))
+        },
+        Op::Move { tag: tag_path, to } => {
+            let names = tag::split_tag_path(&tag_path);
+            let to_names = tag::split_tag_path(&to);
+            let moved = tag::find_tag_by_names(tags, &names).ok_or_else(|| not_found(&tag_path))?;
+            let under_itself = (1..=to_names.len()).any(|n| {
+                tag::find_tag_by_names(tags, &to_names[..n]).is_some_and(|t| std::ptr::eq(t, moved))
+            });
+            if under_itself {
+                return Err(format!("`{}` cannot be moved under itself", tag_path).into());
+            }
+
+            let mut tag = tag::remove_tag_by_names(tags, &names).expect("tag was found");
+            tag.modified = Some(now);
+            insert(tags, &to_names, tag)
+        },
+    }
+}
+
+/// Adds the tag under the parent with the names, which is added if it does
+/// not exist.
+fn insert(tags: &mut Tags, parent_names: &[&str], mut tag: Tag) -> Result<()> {
+    tag.names = tag.names.iter().map(|n| tag::normalize_name(n)).collect();
+    let siblings = tag::find_or_create_subtags_mut(tags, parent_names);
+    check_names(siblings, &tag.names, None)?;
+    siblings.push(tag);
+    Ok(())
+}
+
+/// Checks that none of the names is taken by one of the siblings other than
+/// the one at `skip`.
+fn check_names(siblings: &[Tag], names: &[String], skip: Option<usize>) -> Result<()> {
+    if names.is_empty() {
+        return Err(Error::new(
+            ErrorKind::Data,
+            "there must be at least one name",
+        ));
+    }
+
+    for (i, sibling) in siblings.iter().enumerate() {
+        if Some(i) == skip {
+            continue;
+        }
+        if let Some(name) = names.iter().find(|n| sibling.has_name(n)) {
+            return Err(format!("a tag with name `{}` already exists", name).into());
+        }
+    }
+    Ok(())
+}
+
+/// Sets when the tag and its subtags were created and modified to `now`,
+/// unless they have those times already.
+fn stamp(tag: &mut Tag, now: u64) {
+    tag.created.get_or_insert(now);
+    tag.modified.get_or_insert(now);
+    for subtag in &mut tag.subtags {
+        stamp(subtag, now);
+    }
+}
+
+/// Returns the error for a tag path that matches no tag.
+fn not_found(tag_path: &str) -> Error {
+    Error::new(
+        ErrorKind::NotFound,
+        format!("no tag found at `{}`", tag_path),
+    )
+}
@@ -0,0 +1,434 @@
+use std::env;
+use std::fmt::Write as _;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use console::{style, Term};
+use dialoguer::Confirm;
+use itertools::Itertools;
+
+use crate::analysis;
+use crate::error::Result;
+use crate::tag::{self, Tag};
+
+/// Whether status messages are suppressed.
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// Suppresses status messages if `quiet` is `true`.
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+/// Returns `true` if status messages are suppressed.
+pub fn is_quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+/// Whether confirmation prompts are answered with yes without asking.
+static ASSUME_YES: AtomicBool = AtomicBool::new(false);
+
+/// Answers confirmation prompts with yes without asking if `yes` is `true`.
+pub fn set_assume_yes(yes: bool) {
+    ASSUME_YES.store(yes, Ordering::Relaxed);
+}
+
+/// Returns whether confirmation prompts are answered with yes without asking.
+pub fn assume_yes() -> bool {
+    ASSUME_YES.load(Ordering::Relaxed)
+}
+
+/// Asks the user to confirm the prompt, returning `true` if they do.
+///
+/// Returns `true` without asking if prompts are answered with `--yes`.
+pub fn confirm(prompt: &str, default: bool) -> Result<bool> {
+    if assume_yes() {
+        return Ok(true);
+    }
+
+    Ok(Confirm::new()
+        .with_prompt(prompt)
+        .default(default)
+        .interact()?)
+}
+
+/// Prints a status message, such as the result of a command, unless status
+/// messages are suppressed with `--quiet`.
+pub fn status(message: &str) {
+    if !is_quiet() {
+        println!("{}", message);
+    }
+}
+
+/// Prints a note on the `stderr`, such as a reminder, unless status messages
+/// are suppressed with `--quiet`.
+///
+/// "note: " is displayed before the message, in cyan if colors are enabled.
+pub fn note(message: &str) {
+    if !is_quiet() {
+        eprintln!("{}: {}", style("note").for_stderr().cyan(), message);
+    }
+}
+
+/// Prints a warning on the `stderr`.
+///
+/// "warning: " is displayed before the message, in yellow and bold if colors
+/// are enabled.
+pub fn warn(message: &str) {
+    eprintln!(
+        "{}: {}",
+        style("warning").for_stderr().yellow().bold(),
+        message
+    );
+}
+
+/// Formats the tag using the template.
+///
+/// The following placeholders are replaced:
+///
+/// - `{name}`: the primary name
+/// - `{aliases}`: the aliases, comma-separated
+/// - `{icon}`: the icon
+/// - `{path}`: the path or the URL, with the tilde expanded
+/// - `{about}`: the first line of the description
+/// - `{app}`: the default app
+/// - `{labels}`: the labels, comma-separated
+///
+/// Placeholders for missing fields are replaced with nothing. The escapes `\t`,
+/// `\n`, and `\\` are replaced with a tab, a newline, and a backslash.
+pub fn format_tag(template: &str, tag: &Tag) -> String {
+    let mut output = String::with_capacity(template.len());
+    let mut chars = template.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.peek() {
+                Some('t') => {
+                    output.push('\t');
+                    chars.next();
+                },
+                Some('n') => {
+                    output.push('\n');
+                    chars.next();
+                },
+                Some('\\') => {
+                    output.push('\\');
+                    chars.next();
+                },
+                _ => output.push('\\'),
+            },
+            '{' => {
+                let rest = chars.clone().collect::<String>();
+                match rest
+                    .find('}')
+                    .and_then(|end| placeholder(&rest[..end], tag))
+                {
+                    Some((value, len)) => {
+                        output.push_str(&value);
+                        chars.nth(len);
+                    },
+                    None => output.push('{'),
+                }
+            },
+            c => output.push(c),
+        }
+    }
+
+    output
+}
+
+/// Returns the value of the placeholder along with the length of its key, or
+/// `None` if the key is unknown.
+fn placeholder(key: &str, tag: &Tag) -> Option<(String, usize)> {
+    let value = match key {
+        "name" => tag.names.first().cloned().unwrap_or_default(),
+        "aliases" => tag
+            .names
+            .iter()
+            .skip(1)
+            .cloned()
+            .collect::<Vec<_>>()
+            .join(","),
+        "icon" => tag.icon.clone().unwrap_or_default(),
+        "path" => tag
+            .path()
+            .map(|p| shellexpand::tilde(p).into_owned())
+            .unwrap_or_default(),
+        "about" => tag
+            .about
+            .as_deref()
+            .and_then(|a| a.lines().next())
+            .unwrap_or_default()
+            .to_string(),
+        "app" => tag
+            .app
+            .as_ref()
+            .map(ToString::to_string)
+            .unwrap_or_default(),
+        "labels" => tag.labels.join(","),
+        _ => return None,
+    };
+
+    Some((value, key.len()))
+}
+
+/// Quotes the text so that a POSIX shell reads it as a single word.
+pub fn shell_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+/// Formats a link to the path with the name as its text.
+///
+/// The format is one of `markdown`, `html`, or `org`.
+pub fn format_link(format: &str, name: &str, path: &str) -> String {
+    match format {
+        "markdown" => format!("[{}]({})", name, path),
+        "html" => format!(
+            "<a href=\"{}\">{}</a>",
+            escape_html(path),
+            escape_html(name)
+        ),
+        "org" => format!("[[{}][{}]]", path, name),
+        _ => unreachable!("unhandled link format `{}`", format),
+    }
+}
+
+/// Escapes the characters with a special meaning in HTML.
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Renders the tags and their subtags as nested Markdown lists.
+///
+/// URLs are rendered as links and file paths as code. Descriptions and
+/// aliases are listed under the tag, before its subtags.
+pub fn markdown(tags: &[Tag]) -> String {
+    fn render(tags: &[Tag], level: usize, output: &mut String) {
+        let indent = "  ".repeat(level);
+        for tag in tags {
+            let name = match tag.names.first() {
+                Some(n) => n,
+                None => continue,
+            };
+
+            let _ = match tag.path() {
+                Some(p) if analysis::is_url(p) => {
+                    writeln!(output, "{}- [{}]({})", indent, name, p)
+                },
+                Some(p) => writeln!(output, "{}- {}: `{}`", indent, name, p),
+                None => writeln!(output, "{}- **{}**", indent, name),
+            };
+
+            if let Some(ref about) = tag.about {
+                let about = about.lines().map(str::trim).join(" ");
+                let _ = writeln!(output, "{}  - {}", indent, about);
+            }
+            if tag.names.len() > 1 {
+                let aliases = tag.names[1..].iter().map(|n| format!("`{}`", n)).join(", ");
+                let _ = writeln!(output, "{}  - Aliases: {}", indent, aliases);
+            }
+
+            render(&tag.subtags, level + 1, output);
+        }
+    }
+
+    let mut output = String::new();
+    render(tags, 0, &mut output);
+    output
+}
+
+/// Returns the tags and their subtags that can be opened, with the names of
+/// the tag and its parents, starting with `parent`.
+///
+/// Tags without a path, fallback paths, or a secret are left out, as they only
+/// group their subtags.
+fn openable_tags<'a>(tags: &'a [Tag], parent: &[String]) -> Vec<(Vec<String>, &'a Tag)> {
+    fn collect<'a>(
+        tags: &'a [Tag],
+        names: &mut Vec<String>,
+        found: &mut Vec<(Vec<String>, &'a Tag)>,
+    ) {
+        for tag in tags.iter().filter(|t| !t.names.is_empty()) {
+            names.push(tag.names[0].clone());
+            if tag.path().is_some() || !tag.fallback_paths.is_empty() || tag.secret.is_some() {
+                found.push((names.clone(), tag));
+            }
+            collect(&tag.subtags, names, found);
+            names.pop();
+        }
+    }
+
+    let mut found = Vec::new();
+    collect(tags, &mut parent.to_vec(), &mut found);
+    found
+}
+
+/// Returns the first line of the description of the tag, or else its path
+/// unless it is secret.
+fn launcher_comment(tag: &Tag) -> Option<&str> {
+    match tag.about.as_deref().and_then(|a| a.lines().next()) {
+        Some(about) => Some(about),
+        None if tag.secret.is_none() => tag.path(),
+        None => None,
+    }
+}
+
+/// Renders a desktop entry for every tag that can be opened, returned with
+/// the name of its file, such as `opentag-web-github.desktop`.
+///
+/// Each entry runs `program tag` followed by the names of the tag and its
+/// parents, starting with `parent`, so that launchers such as rofi or the
+/// GNOME search open tags. Secret tags are opened in a terminal, where their
+/// passphrase can be entered.
+pub fn desktop_entries(tags: &[Tag], parent: &[String], program: &str) -> Vec<(String, String)> {
+    openable_tags(tags, parent)
+        .into_iter()
+        .map(|(names, tag)| {
+            let file_name = names
+                .iter()
+                .map(|n| {
+                    n.chars()
+                        .map(|c| {
+                            if c.is_ascii_alphanumeric() || c == '_' || c == '.' {
+                                c
+                            } else {
+                                '-'
+                            }
+                        })
+                        .collect::<String>()
+                })
+                .join("-");
+            let kind = analysis::tag_kind(tag, tag.path().map(analysis::expand_path).as_deref());
+            let icon = match kind {
+                "url" => "text-html",
+                "directory" => "folder",
+                "secret" => "dialog-password",
+                _ => "text-x-generic",
+            };
+            let keywords = tag.names[1..]
+                .iter()
+                .chain(&names[..names.len() - 1])
+                .chain(&tag.labels)
+                .map(|k| format!("{};", escape_desktop(k).replace(';', "\\;")))
+                .collect::<String>();
+            let exec = [program, "tag"]
+                .into_iter()
+                .chain(names.iter().map(String::as_str))
+                .map(quote_exec_arg)
+                .join(" ");
+
+            let mut entry = String::from("[Desktop Entry]\nType=Application\n");
+            let _ = writeln!(entry, "Name={}", escape_desktop(&tag.names[0]));
+            let _ = writeln!(entry, "GenericName={}", escape_desktop(&names.join("/")));
+            if let Some(comment) = launcher_comment(tag) {
+                let _ = writeln!(entry, "Comment={}", escape_desktop(comment));
+            }
+            if !keywords.is_empty() {
+                let _ = writeln!(entry, "Keywords={}", keywords);
+            }
+            let _ = writeln!(entry, "Icon={}", icon);
+            let _ = writeln!(entry, "Exec={}", exec);
+            let _ = writeln!(entry, "Terminal={}", kind == "secret");
+
+            (format!("opentag-{}.desktop", file_name), entry)
+        })
+        .collect()
+}
+
+/// Escapes the text for a string value of a desktop entry.
+fn escape_desktop(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('\n', "\\n")
+        .replace('\t', "\\t")
+        .replace('\r', "\\r")
+}
+
+/// Quotes the argument for the `Exec` key of a desktop entry, where `%`
+/// starts a field code and quoted arguments escape `"`, `` ` ``, `$`, and `\`.
+fn quote_exec_arg(arg: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in arg.chars() {
+        match c {
+            '"' | '`' | '$' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            },
+            '%' => quoted.push_str("%%"),
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    // the escapes of string values apply before the quoting
+    escape_desktop(&quoted)
+}
+
+/// Renders the tags that can be opened as the items of an Alfred script
+/// filter, in JSON.
+///
+/// The argument of each item is `tag` followed by the names of the tag and
+/// its parents, starting with `parent`, so that a workflow opens the tag by
+/// passing the argument to `ot`.
+pub fn alfred(tags: &[Tag], parent: &[String]) -> Result<String> {
+    let items = openable_tags(tags, parent)
+        .into_iter()
+        .map(|(names, tag)| {
+            let keywords = names.iter().chain(&tag.names[1..]).chain(&tag.labels).join(" ");
+            serde_json::json!({
+                "uid": names.join("/"),
+                "title": tag::display_name(tag),
+                "subtitle": launcher_comment(tag).unwrap_or_default(),
+                "arg": std::iter::once("tag").chain(names.iter().map(String::as_str)).collect::<Vec<_>>(),
+                "autocomplete": names.join("/"),
+                "match": keywords,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    Ok(serde_json::to_string_pretty(&serde_json::json!({ "items": items }))? + "\n")
+}
+
+/// Prints the text, through the pager if it is taller than the terminal.
+///
+/// The pager is the command in `$PAGER`, or `less` if it is not set. `LESS`
+/// is set to `FRX` for the pager unless it is already set. The text is printed
+/// directly if `disabled` is `true`, if the output is not a terminal, or if the
+/// pager cannot be started.
+pub fn page(text: &str, disabled: bool) -> Result<()> {
+    let term = Term::stdout();
+    let fits = term
+        .size_checked()
+        .is_none_or(|(rows, _)| text.lines().count() < rows as usize);
+    if disabled || fits || !term.is_term() {
+        print!("{}", text);
+        return Ok(());
+    }
+
+    let pager = env::var("PAGER")
+        .ok()
+        .filter(|p| !p.trim().is_empty())
+        .unwrap_or_else(|| "less".to_string());
+    let mut parts = pager.split_whitespace();
+    let mut command = Command::new(parts.next().expect("expected non-empty pager"));
+    command.args(parts).stdin(Stdio::piped());
+    if env::var_os("LESS").is_none() {
+        command.env("LESS", "FRX");
+    }
+
+    let mut child = match command.spawn() {
+        Ok(child) => child,
+        Err(_) => {
+            print!("{}", text);
+            return Ok(());
+        },
+    };
+    if let Some(mut stdin) = child.stdin.take() {
+        // the pager closes its input if it is quit before reading everything
+        let _ = stdin.write_all(text.as_bytes());
+    }
+    child.wait()?;
+
+    Ok(())
+}
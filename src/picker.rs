@@ -0,0 +1,237 @@
+use std::io;
+
+use console::{style, truncate_str, Key, Term};
+use fuzzy_matcher::skim::SkimMatcherV2;
+use fuzzy_matcher::FuzzyMatcher;
+
+use crate::config::{CaseMatching, FuzzyConfig, MatchAlgorithm};
+
+/// Matches a query against candidates as configured.
+pub struct Matcher {
+    config: FuzzyConfig,
+    skim: SkimMatcherV2,
+}
+
+impl Matcher {
+    /// Creates a matcher from the config.
+    pub fn new(config: &FuzzyConfig) -> Self {
+        let skim = match config.case {
+            CaseMatching::Smart => SkimMatcherV2::default().smart_case(),
+            CaseMatching::Ignore => SkimMatcherV2::default().ignore_case(),
+            CaseMatching::Respect => SkimMatcherV2::default().respect_case(),
+        };
+
+        Self {
+            config: config.clone(),
+            skim,
+        }
+    }
+
+    /// Returns the score of the candidate and the indices of the matched
+    /// characters, or `None` if the candidate does not match or scores below
+    /// the threshold.
+    pub fn indices(&self, candidate: &str, query: &str) -> Option<(i64, Vec<usize>)> {
+        let (score, indices) = match self.config.algorithm {
+            MatchAlgorithm::Skim => self.skim.fuzzy_indices(candidate, query)?,
+            MatchAlgorithm::Substring => self.substring_indices(candidate, query)?,
+        };
+
+        (query.is_empty() || score >= self.config.threshold).then_some((score, indices))
+    }
+
+    /// Matches the query as a substring of the candidate.
+    ///
+    /// Earlier matches score higher.
+    fn substring_indices(&self, candidate: &str, query: &str) -> Option<(i64, Vec<usize>)> {
+        let ignore_case = match self.config.case {
+            CaseMatching::Smart => !query.chars().any(char::is_uppercase),
+            CaseMatching::Ignore => true,
+            CaseMatching::Respect => false,
+        };
+
+        let (candidate_chars, query_chars): (Vec<_>, Vec<_>) = if ignore_case {
+            (
+                candidate.chars().flat_map(char::to_lowercase).collect(),
+                query.chars().flat_map(char::to_lowercase).collect(),
+            )
+        } else {
+            (candidate.chars().collect(), query.chars().collect())
+        };
+
+        if query_chars.is_empty() {
+            return Some((0, Vec::new()));
+        }
+
+        let start = candidate_chars
+            .windows(query_chars.len())
+            .position(|w| w == query_chars.as_slice())?;
+        let score = 100 * query_chars.len() as i64 - start as i64;
+
+        Some((score, (start..start + query_chars.len()).collect()))
+    }
+}
+
+/// The maximum number of lines of the preview of an item.
+pub const PREVIEW_LINES: usize = 3;
+
+/// An interactive prompt to select an item by typing a query.
+pub struct Picker<'a> {
+    prompt: &'a str,
+    items: &'a [String],
+    query: &'a str,
+    previews: Option<&'a [String]>,
+    matcher: Matcher,
+}
+
+impl<'a> Picker<'a> {
+    /// Creates a picker for the items.
+    pub fn new(prompt: &'a str, items: &'a [String], config: &FuzzyConfig) -> Self {
+        Self {
+            prompt,
+            items,
+            query: "",
+            previews: None,
+            matcher: Matcher::new(config),
+        }
+    }
+
+    /// Sets the query the picker starts with.
+    pub fn with_query(mut self, query: &'a str) -> Self {
+        self.query = query;
+        self
+    }
+
+    /// Sets the previews of the items, shown below the items for the
+    /// highlighted item.
+    ///
+    /// There must be one preview for every item. Only the first
+    /// [`PREVIEW_LINES`] lines of each preview are shown.
+    pub fn with_previews(mut self, previews: &'a [String]) -> Self {
+        self.previews = Some(previews);
+        self
+    }
+
+    /// Shows the picker on the `stderr` and returns the index of the selected
+    /// item, or `None` if the user pressed `esc`.
+    pub fn interact_opt(&self) -> io::Result<Option<usize>> {
+        let term = Term::stderr();
+        let (height, width) = term.size();
+        let preview_rows = self.previews.map_or(0, |previews| {
+            previews
+                .iter()
+                .map(|p| p.lines().count().min(PREVIEW_LINES))
+                .max()
+                .map_or(0, |lines| lines + 1)
+        });
+        let visible_rows = (height as usize).max(preview_rows + 3) - preview_rows - 2;
+
+        let mut query = self.query.to_string();
+        let mut selected = 0;
+        let mut first_row = 0;
+
+        term.hide_cursor()?;
+
+        let result = loop {
+            let mut matches = self
+                .items
+                .iter()
+                .enumerate()
+                .filter_map(|(i, item)| {
+                    self.matcher
+                        .indices(item, &query)
+                        .map(|(score, indices)| (i, score, indices))
+                })
+                .collect::<Vec<_>>();
+            matches.sort_by(|(i1, s1, _), (i2, s2, _)| s2.cmp(s1).then(i1.cmp(i2)));
+
+            term.write_line(&format!(
+                "{} {} {} {}|",
+                style("?").for_stderr().yellow(),
+                style(self.prompt).for_stderr().bold(),
+                style("›").for_stderr().black().bright(),
+                query,
+            ))?;
+
+            let shown = matches
+                .iter()
+                .enumerate()
+                .skip(first_row)
+                .take(visible_rows);
+            let lines = shown.len();
+            for (row, (i, _, indices)) in shown {
+                let text = self.items[*i]
+                    .chars()
+                    .enumerate()
+                    .map(|(ci, c)| {
+                        if indices.contains(&ci) {
+                            style(c).for_stderr().bold().to_string()
+                        } else {
+                            c.to_string()
+                        }
+                    })
+                    .collect::<String>();
+
+                if row == selected {
+                    term.write_line(&format!("{} {}", style("❯").for_stderr().green(), text))?;
+                } else {
+                    term.write_line(&format!("  {}", text))?;
+                }
+            }
+
+            let mut preview_lines = 0;
+            if let (Some(previews), Some((i, ..))) = (self.previews, matches.get(selected)) {
+                term.write_line("")?;
+                preview_lines += 1;
+                for line in previews[*i].lines().take(PREVIEW_LINES) {
+                    let line = truncate_str(line, (width as usize).saturating_sub(2), "…");
+                    term.write_line(&format!("  {}", style(line).for_stderr().dim()))?;
+                    preview_lines += 1;
+                }
+            }
+            term.flush()?;
+
+            let key = term.read_key()?;
+            term.clear_last_lines(lines + preview_lines + 1)?;
+
+            match key {
+                Key::Escape => break None,
+                Key::Enter if !matches.is_empty() => break Some(matches[selected].0),
+                Key::ArrowUp | Key::BackTab if !matches.is_empty() => {
+                    selected = selected.checked_sub(1).unwrap_or(matches.len() - 1);
+                },
+                Key::ArrowDown | Key::Tab if !matches.is_empty() => {
+                    selected = (selected + 1) % matches.len();
+                },
+                Key::Backspace => {
+                    query.pop();
+                    selected = 0;
+                },
+                Key::Char(c) if !c.is_ascii_control() => {
+                    query.push(c);
+                    selected = 0;
+                },
+                _ => {},
+            }
+
+            if selected < first_row {
+                first_row = selected;
+            } else if selected >= first_row + visible_rows {
+                first_row = selected + 1 - visible_rows;
+            }
+        };
+
+        term.show_cursor()?;
+
+        if let Some(i) = result {
+            term.write_line(&format!(
+                "{} {} {} {}",
+                style("✔").for_stderr().green(),
+                style(self.prompt).for_stderr().bold(),
+                style("·").for_stderr().black().bright(),
+                style(&self.items[i]).for_stderr().green(),
+            ))?;
+        }
+
+        Ok(result)
+    }
+}
@@ -0,0 +1,116 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use console::Term;
+
+use crate::output;
+
+/// How long work runs before its progress is shown, so that quick work does
+/// not flash a bar.
+const DELAY: Duration = Duration::from_millis(300);
+
+/// How often the bar is redrawn at most.
+const REDRAW: Duration = Duration::from_millis(100);
+
+/// The width of the bar, in characters.
+const WIDTH: usize = 30;
+
+/// A progress bar on the `stderr` for work done in steps, such as checking
+/// many URLs.
+///
+/// The bar is only shown once the work has taken a moment, and never if the
+/// `stderr` is not a terminal or if status messages are suppressed with
+/// `--quiet`. Steps may be counted from many threads at once.
+pub struct Progress {
+    /// What is being done, such as `Checking URLs`.
+    label: String,
+    /// The number of steps.
+    total: usize,
+    /// The number of steps done.
+    done: AtomicUsize,
+    /// When the work started.
+    start: Instant,
+    /// When the bar was last drawn, if it is shown.
+    drawn: Mutex<Option<Instant>>,
+    /// Whether the bar can be shown at all.
+    visible: bool,
+}
+
+impl Progress {
+    /// Starts the progress of work with `total` steps.
+    pub fn new(label: &str, total: usize) -> Self {
+        Self {
+            label: label.to_string(),
+            total,
+            done: AtomicUsize::new(0),
+            start: Instant::now(),
+            drawn: Mutex::new(None),
+            visible: !output::is_quiet() && console::user_attended_stderr(),
+        }
+    }
+
+    /// Counts one more step as done, redrawing the bar if it is due.
+    pub fn inc(&self) {
+        let done = self.done.fetch_add(1, Ordering::Relaxed) + 1;
+        if !self.visible || self.start.elapsed() < DELAY {
+            return;
+        }
+
+        let mut drawn = self.drawn.lock().expect("no thread panics with the lock");
+        if drawn.is_some_and(|d| d.elapsed() < REDRAW) && done < self.total {
+            return;
+        }
+        let filled = (done * WIDTH)
+            .checked_div(self.total)
+            .unwrap_or(WIDTH)
+            .min(WIDTH);
+        let _ = Term::stderr().clear_line();
+        eprint!(
+            "{} [{}{}] {}/{}",
+            self.label,
+            "#".repeat(filled),
+            "-".repeat(WIDTH - filled),
+            done,
+            self.total
+        );
+        *drawn = Some(Instant::now());
+    }
+
+    /// Hides the bar while `f` runs, such as to print a warning or to prompt
+    /// the user. It is drawn again with the next step.
+    pub fn suspend<T, F: FnOnce() -> T>(&self, f: F) -> T {
+        self.clear();
+        f()
+    }
+
+    /// Returns how long the work has taken so far, as with [`took`].
+    pub fn took(&self) -> String {
+        took(self.start)
+    }
+
+    /// Removes the bar from the terminal, if it is shown.
+    fn clear(&self) {
+        let mut drawn = self.drawn.lock().expect("no thread panics with the lock");
+        if drawn.take().is_some() {
+            let _ = Term::stderr().clear_line();
+        }
+    }
+}
+
+/// Returns how long it has been since `start`, such as ` in 2.4s`, to end a
+/// summary with, or nothing if it was too quick to mention.
+pub fn took(start: Instant) -> String {
+    let elapsed = start.elapsed();
+    match elapsed.as_secs() {
+        0 => String::new(),
+        1..=59 => format!(" in {:.1}s", elapsed.as_secs_f64()),
+        secs => format!(" in {}m {:02}s", secs / 60, secs % 60),
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
@@ -0,0 +1,71 @@
+use std::fmt::Display;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use termcolor::{BufferWriter, Color, ColorChoice, ColorSpec, WriteColor};
+
+use crate::error::Error;
+use crate::{app, color};
+
+/// Whether errors are printed as JSON.
+static JSON: AtomicBool = AtomicBool::new(false);
+
+/// Sets the format errors are printed in.
+///
+/// The format is read from the `--error-format` argument directly, so that it
+/// also applies to errors raised before the arguments are parsed.
+pub fn init() {
+    let json = match app::raw_option_value("error-format").as_deref() {
+        Some("json") => true,
+        Some("auto") => !console::user_attended_stderr(),
+        _ => false,
+    };
+    JSON.store(json, Ordering::Relaxed);
+}
+
+/// Returns `true` if errors are printed as JSON.
+pub fn is_json() -> bool {
+    JSON.load(Ordering::Relaxed)
+}
+
+/// Prints the error on the `stderr` and exits with the exit code of its kind.
+///
+/// "error: " is displayed before the error message. The "error" is displayed in
+/// red and bold if possible. With `--error-format json`, the error is printed
+/// as a JSON object with the `code`, the `kind`, and the `message` instead.
+pub fn exit(err: Error) -> ! {
+    if is_json() {
+        let json = serde_json::json!({
+            "code": err.kind().exit_code(),
+            "kind": err.kind().name(),
+            "message": err.to_string(),
+        });
+        eprintln!("{}", json);
+    } else {
+        print_error(&err).unwrap_or_else(|_| eprintln!("error: {}", err));
+    }
+    std::process::exit(err.kind().exit_code());
+}
+
+/// Prints error on the `stderr`.
+///
+/// "error: " is displayed before the error message. The "error" is displayed in
+/// red and bold if colors are enabled.
+fn print_error<T: Display>(err: &T) -> io::Result<()> {
+    let bufwtr = BufferWriter::stderr(if color::enabled(console::user_attended_stderr()) {
+        ColorChoice::Always
+    } else {
+        ColorChoice::Never
+    });
+    let mut buffer = bufwtr.buffer();
+
+    buffer.set_color(ColorSpec::new().set_fg(Some(Color::Red)).set_bold(true))?;
+
+    write!(&mut buffer, "error")?;
+    buffer.reset()?;
+    writeln!(&mut buffer, ": {}", err)?;
+
+    bufwtr.print(&buffer)?;
+
+    Ok(())
+}
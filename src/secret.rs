@@ -0,0 +1,123 @@
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::SecretsConfig;
+use crate::error::{Error, ErrorKind, Result};
+use crate::tag::TagPath;
+use crate::{analysis, output, Tag};
+
+/// The fields of a secret tag that are encrypted.
+#[derive(Deserialize, Serialize)]
+struct Secret {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path: Option<TagPath>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    about: Option<String>,
+}
+
+/// Encrypts the path and the description of the tag with `age`, moving them to
+/// its `secret` field.
+///
+/// The tag is encrypted to the configured recipients, or with a passphrase
+/// that `age` prompts for if there are none.
+pub fn encrypt(tag: &mut Tag, config: &SecretsConfig) -> Result<()> {
+    if tag.secret.is_some() {
+        return Err("tag is already secret".into());
+    }
+
+    let plaintext = serde_json::to_vec(&Secret {
+        path: tag.path.clone(),
+        about: tag.about.clone(),
+    })?;
+
+    let mut args = vec!["--encrypt".to_string(), "--armor".to_string()];
+    if config.recipients.is_empty() {
+        args.push("--passphrase".to_string());
+    }
+    for recipient in &config.recipients {
+        args.push("--recipient".to_string());
+        args.push(recipient.clone());
+    }
+
+    let ciphertext = run_age(&args, &plaintext)?;
+    tag.secret = Some(String::from_utf8_lossy(&ciphertext).trim_end().to_string());
+    tag.path = None;
+    tag.about = None;
+
+    Ok(())
+}
+
+/// Decrypts the `secret` field of the tag with `age`, restoring its path and
+/// its description.
+///
+/// The configured identity is used, or `age` prompts for the passphrase if
+/// there is none.
+pub fn decrypt(tag: &mut Tag, config: &SecretsConfig) -> Result<()> {
+    let ciphertext = match tag.secret {
+        Some(ref secret) => secret,
+        None => return Ok(()),
+    };
+
+    let mut args = vec!["--decrypt".to_string()];
+    if let Some(ref identity) = config.identity {
+        args.push("--identity".to_string());
+        args.push(analysis::expand_path(identity).into_owned());
+    }
+
+    let plaintext = run_age(&args, ciphertext.as_bytes())?;
+    let secret: Secret = serde_json::from_slice(&plaintext).map_err(|e| {
+        Error::new(
+            ErrorKind::Data,
+            format!("unable to read the decrypted tag: {}", e),
+        )
+    })?;
+    tag.path = secret.path;
+    tag.about = secret.about;
+    tag.secret = None;
+
+    Ok(())
+}
+
+/// Returns a decrypted copy of the secret tag if the user confirms it.
+pub fn reveal(tag: &Tag, name: &str, config: &SecretsConfig) -> Result<Option<Tag>> {
+    if !output::confirm(&format!("Decrypt the secret tag `{}`?", name), true)? {
+        return Ok(None);
+    }
+
+    let mut tag = tag.clone();
+    decrypt(&mut tag, config)?;
+    Ok(Some(tag))
+}
+
+/// Runs `age` with the arguments, writing the input to its `stdin`, and
+/// returns its output.
+///
+/// `age` prompts for passphrases on the terminal directly.
+fn run_age(args: &[String], input: &[u8]) -> Result<Vec<u8>> {
+    log::info!("running `age` with {:?}", args);
+    let mut child = Command::new("age")
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => {
+                "`age` is required for secret tags; see https://age-encryption.org".into()
+            },
+            _ => format!("unable to run `age`: {}", e),
+        })?;
+
+    child
+        .stdin
+        .take()
+        .expect("stdin is piped")
+        .write_all(input)?;
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(format!("`age` exited with {}", output.status).into());
+    }
+
+    Ok(output.stdout)
+}
@@ -0,0 +1,158 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::{Duration, SystemTime};
+
+use crate::error::{Error, ErrorKind, Result};
+use crate::tag::{self, Tags};
+use crate::{analysis, output};
+
+/// How long a fetched source is used before it is fetched again.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// The longest a source is waited for, in seconds.
+const FETCH_TIMEOUT: &str = "10";
+
+/// Returns the tags of the extra sources, in order.
+///
+/// A source is an `https://` URL or the path to a tags file. URLs are fetched
+/// with `curl` and cached, and are only fetched again once the cached copy is
+/// older than [`REFRESH_INTERVAL`]. If fetching fails, the cached copy is used
+/// with a warning. A source that cannot be read at all is skipped with a
+/// warning so that the local tags can still be used.
+pub fn load(sources: &[String]) -> Vec<Tags> {
+    sources
+        .iter()
+        .filter_map(|source| match load_source(source) {
+            Ok(tags) => Some(tags),
+            Err(e) => {
+                output::warn(&format!("skipping source `{}`: {}", source, e));
+                None
+            },
+        })
+        .collect()
+}
+
+/// Returns the tags of the source.
+fn load_source(source: &str) -> Result<Tags> {
+    let contents = if source.starts_with("https://") {
+        fetch(source)?
+    } else if source.contains("://") {
+        return Err("only `https://` URLs are supported".into());
+    } else {
+        let path = analysis::expand_path(source);
+        fs::read_to_string(path.as_ref())?
+    };
+
+    tag::parse_tags(&contents)
+        .map_err(|e| Error::new(ErrorKind::Data, format!("json error: {}", e)))
+}
+
+/// Returns the contents at the URL, from the cache if it is recent enough.
+fn fetch(url: &str) -> Result<String> {
+    let cache_path = get_source_cache_path(url);
+    let cached = cache_path
+        .as_ref()
+        .and_then(|p| Some((fs::read_to_string(p).ok()?, fs::metadata(p).ok()?)));
+
+    if let Some((ref contents, ref metadata)) = cached {
+        let age = metadata
+            .modified()
+            .ok()
+            .and_then(|m| SystemTime::now().duration_since(m).ok());
+        if age.is_some_and(|a| a < REFRESH_INTERVAL) {
+            log::info!("using cached copy of `{}`", url);
+            return Ok(contents.clone());
+        }
+    }
+
+    match run_curl(url) {
+        Ok(contents) => {
+            if let Some(ref path) = cache_path {
+                // the cache only avoids fetching the source again, so failing
+                // to write it is not an error
+                let _ = path
+                    .parent()
+                    .map_or(Ok(()), fs::create_dir_all)
+                    .and_then(|_| fs::write(path, &contents));
+            }
+            Ok(contents)
+        },
+        Err(e) => match cached {
+            Some((contents, _)) => {
+                output::warn(&format!("{}; using the cached copy of `{}`", e, url));
+                Ok(contents)
+            },
+            None => Err(e),
+        },
+    }
+}
+
+/// Returns the path to the cached copy of the source at the URL, if there is
+/// a cache directory.
+fn get_source_cache_path(url: &str) -> Option<PathBuf> {
+    let name = url
+        .trim_start_matches("https://")
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+
+    dirs_next::cache_dir().map(|d| d.join("opentag/sources").join(name + ".json"))
+}
+
+/// Fetches the URL with `curl`, returning the body of the response.
+fn run_curl(url: &str) -> Result<String> {
+    log::info!("fetching `{}`", url);
+    let output = Command::new("curl")
+        .args(["--silent", "--show-error", "--fail", "--location"])
+        .args(["--proto", "=https", "--max-time", FETCH_TIMEOUT])
+        .arg(url)
+        .output()
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => "`curl` is required for remote sources".to_string(),
+            _ => format!("unable to run `curl`: {}", e),
+        })?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("unable to fetch `{}`: {}", url, stderr.trim()).into());
+    }
+
+    String::from_utf8(output.stdout)
+        .map_err(|_| Error::new(ErrorKind::Data, "the source is not valid UTF-8"))
+}
+
+/// Merges the tags of the sources into the local tags, for reading only.
+///
+/// A source tag named like a local tag at the same level is merged into it:
+/// its subtags are merged in turn and its other fields are ignored. The local
+/// tags are never changed by a source, so only the local tags are written.
+pub fn merge(mut tags: Tags, sources: Vec<Tags>) -> Tags {
+    for source in sources {
+        merge_into(&mut tags, source);
+    }
+    tags
+}
+
+/// Merges the source tags into the tags at the same level.
+fn merge_into(tags: &mut Tags, source: Tags) {
+    for mut source_tag in source {
+        let existing = source_tag
+            .names
+            .iter()
+            .find_map(|n| tags.iter().position(|t| t.has_name(n)));
+        match existing {
+            Some(i) => {
+                let subtags = std::mem::take(&mut source_tag.subtags);
+                merge_into(&mut tags[i].subtags, subtags);
+            },
+            None => tags.push(source_tag),
+        }
+    }
+}
@@ -0,0 +1,308 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::SyncConfig;
+use crate::error::{Error, ErrorKind, Result};
+
+/// Where the tags file is synced to.
+///
+/// Backends are reached over HTTP with `curl`; they only differ in where the
+/// file is and how requests are authenticated.
+pub trait Backend {
+    /// Returns the URL of the synced file.
+    fn url(&self) -> String;
+
+    /// Returns the `curl` config lines that authenticate the requests.
+    ///
+    /// They are passed on the `stdin` so that credentials are not visible in
+    /// the process list.
+    fn auth(&self) -> Vec<String>;
+}
+
+/// A file on a WebDAV server, such as Nextcloud.
+pub struct WebDav {
+    url: String,
+    username: Option<String>,
+    password: Option<String>,
+}
+
+impl Backend for WebDav {
+    fn url(&self) -> String {
+        self.url.clone()
+    }
+
+    fn auth(&self) -> Vec<String> {
+        match self.username {
+            Some(ref username) => vec![format!(
+                "user = {}",
+                quote(&format!(
+                    "{}:{}",
+                    username,
+                    self.password.as_deref().unwrap_or_default()
+                ))
+            )],
+            None => Vec::new(),
+        }
+    }
+}
+
+/// An object in an S3-compatible bucket, such as on AWS or MinIO.
+pub struct S3 {
+    endpoint: String,
+    bucket: String,
+    key: String,
+    region: String,
+    access_key_id: String,
+    secret_access_key: String,
+}
+
+impl Backend for S3 {
+    fn url(&self) -> String {
+        format!(
+            "{}/{}/{}",
+            self.endpoint.trim_end_matches('/'),
+            self.bucket,
+            self.key.trim_start_matches('/')
+        )
+    }
+
+    fn auth(&self) -> Vec<String> {
+        vec![
+            format!(
+                "aws-sigv4 = {}",
+                quote(&format!("aws:amz:{}:s3", self.region))
+            ),
+            format!(
+                "user = {}",
+                quote(&format!(
+                    "{}:{}",
+                    self.access_key_id, self.secret_access_key
+                ))
+            ),
+        ]
+    }
+}
+
+/// Returns the backend configured by the user.
+///
+/// Credentials missing from the config are read from the environment:
+/// `OPENTAG_SYNC_PASSWORD` for WebDAV, and `AWS_ACCESS_KEY_ID` and
+/// `AWS_SECRET_ACCESS_KEY` for S3.
+pub fn backend(config: &SyncConfig) -> Result<Box<dyn Backend>> {
+    let env = |name: &str| std::env::var(name).ok();
+
+    Ok(match config.clone() {
+        SyncConfig::WebDav {
+            url,
+            username,
+            password,
+        } => Box::new(WebDav {
+            url,
+            username,
+            password: password.or_else(|| env("OPENTAG_SYNC_PASSWORD")),
+        }),
+        SyncConfig::S3 {
+            endpoint,
+            bucket,
+            key,
+            region,
+            access_key_id,
+            secret_access_key,
+        } => {
+            let missing = |name: &str| format!("`{}` is required to sync with S3", name);
+            Box::new(S3 {
+                endpoint,
+                bucket,
+                key,
+                region,
+                access_key_id: access_key_id
+                    .or_else(|| env("AWS_ACCESS_KEY_ID"))
+                    .ok_or_else(|| missing("access_key_id"))?,
+                secret_access_key: secret_access_key
+                    .or_else(|| env("AWS_SECRET_ACCESS_KEY"))
+                    .ok_or_else(|| missing("secret_access_key"))?,
+            })
+        },
+    })
+}
+
+/// The synced file as last seen by `ot`.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct SyncState {
+    /// The ETag of the remote file after the last push or pull.
+    pub etag: Option<String>,
+    /// The contents of the local tags file after the last push or pull.
+    pub contents: String,
+}
+
+/// Returns the path to the sync state kept next to the tags file.
+pub fn get_state_path(tags_path: &Path) -> PathBuf {
+    tags_path.with_extension("sync.json")
+}
+
+/// Returns the sync state at the given path, or `None` if the tags were never
+/// synced.
+pub fn get_state(path: &Path) -> Result<Option<SyncState>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let contents = fs::read_to_string(path)?;
+    serde_json::from_str(&contents).map(Some).map_err(|e| {
+        Error::new(
+            ErrorKind::Data,
+            format!("json error at path `{}`: {}", path.display(), e),
+        )
+    })
+}
+
+/// Writes the sync state at the given path.
+pub fn write_state(path: &Path, state: &SyncState) -> Result<()> {
+    Ok(fs::write(path, serde_json::to_string_pretty(state)?)?)
+}
+
+/// The response to a request made to a backend.
+#[derive(Debug)]
+pub struct Response {
+    /// The HTTP status code.
+    pub status: u32,
+    /// The value of the `ETag` header, including its quotes.
+    pub etag: Option<String>,
+    /// The body of the response.
+    pub body: Vec<u8>,
+}
+
+/// Returns the ETag of the remote file, or `None` if it does not exist.
+pub fn head(backend: &dyn Backend) -> Result<Option<String>> {
+    let response = request(backend, &["--head"])?;
+    match response.status {
+        404 => Ok(None),
+        200..=299 => Ok(Some(response.etag.unwrap_or_default())),
+        status => Err(status_error(backend, status)),
+    }
+}
+
+/// Returns the remote file and its ETag, or `None` if it does not exist.
+pub fn get(backend: &dyn Backend) -> Result<Option<(String, String)>> {
+    let response = request(backend, &[])?;
+    match response.status {
+        404 => Ok(None),
+        200..=299 => {
+            let contents = String::from_utf8(response.body).map_err(|_| {
+                Error::new(ErrorKind::Data, "the remote tags file is not valid UTF-8")
+            })?;
+            Ok(Some((contents, response.etag.unwrap_or_default())))
+        },
+        status => Err(status_error(backend, status)),
+    }
+}
+
+/// Uploads the file at the path, returning the new ETag of the remote file.
+///
+/// Unless `etag` is `None`, the remote file is only replaced if its ETag is
+/// still `etag`. An `etag` of `Some("")` means the remote file must not exist
+/// yet.
+pub fn put(backend: &dyn Backend, path: &Path, etag: Option<&str>) -> Result<String> {
+    let path = path.display().to_string();
+    let condition = match etag {
+        Some("") => Some("If-None-Match: *".to_string()),
+        Some(etag) => Some(format!("If-Match: {}", etag)),
+        None => None,
+    };
+    let mut args = vec!["--upload-file", &path];
+    if let Some(ref condition) = condition {
+        args.extend(["--header", condition]);
+    }
+
+    let response = request(backend, &args)?;
+    match response.status {
+        200..=299 => match response.etag {
+            Some(etag) => Ok(etag),
+            None => Ok(head(backend)?.unwrap_or_default()),
+        },
+        412 => Err(conflict_error()),
+        status => Err(status_error(backend, status)),
+    }
+}
+
+/// Returns the error for a remote file that changed since it was last synced.
+pub fn conflict_error() -> Error {
+    "the remote tags changed since the last sync; pull them first, or use `--force` to \
+     overwrite them"
+        .into()
+}
+
+/// Returns the error for an unexpected HTTP status.
+fn status_error(backend: &dyn Backend, status: u32) -> Error {
+    format!("`{}` responded with HTTP status {}", backend.url(), status).into()
+}
+
+/// Makes a request to the backend with `curl`, passing it the arguments.
+fn request(backend: &dyn Backend, args: &[&str]) -> Result<Response> {
+    let url = backend.url();
+    log::info!("requesting `{}` with {:?}", url, args);
+
+    let mut child = Command::new("curl")
+        .args(["--silent", "--show-error", "--include", "--config", "-"])
+        .args(args)
+        .arg(&url)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => "`curl` is required to sync the tags".to_string(),
+            _ => format!("unable to run `curl`: {}", e),
+        })?;
+
+    child
+        .stdin
+        .take()
+        .expect("stdin is piped")
+        .write_all(backend.auth().join("\n").as_bytes())?;
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("unable to reach `{}`: {}", url, stderr.trim()).into());
+    }
+
+    parse_response(output.stdout)
+        .ok_or_else(|| Error::new(ErrorKind::Data, format!("invalid response from `{}`", url)))
+}
+
+/// Parses the output of `curl --include` into the status, the ETag, and the
+/// body of the final response.
+fn parse_response(mut output: Vec<u8>) -> Option<Response> {
+    let (mut status, mut etag) = (None, None);
+
+    // informational responses, such as `100 Continue`, come before the final
+    // headers
+    while output.starts_with(b"HTTP/") {
+        let end = output.windows(4).position(|w| w == b"\r\n\r\n")?;
+        let headers = String::from_utf8_lossy(&output[..end]).into_owned();
+        output.drain(..end + 4);
+
+        let mut lines = headers.lines();
+        status = lines.next()?.split_whitespace().nth(1)?.parse().ok();
+        etag = lines.find_map(|l| {
+            let (name, value) = l.split_once(':')?;
+            name.eq_ignore_ascii_case("etag")
+                .then(|| value.trim().to_string())
+        });
+    }
+
+    Some(Response {
+        status: status?,
+        etag,
+        body: output,
+    })
+}
+
+/// Quotes the value for a `curl` config file.
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
@@ -1,11 +1,14 @@
+use std::collections::{BTreeMap, HashMap};
 use std::path::{Path, PathBuf};
-use std::{env, fs};
+use std::time::SystemTime;
+use std::{env, fmt, fs, io};
 
-use clap::{ArgMatches, Command};
 use serde::ser::SerializeSeq;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use unicode_normalization::{is_nfc, UnicodeNormalization};
 
-use crate::error::Result;
+use crate::error::{Error, ErrorKind, Result};
+use crate::{analysis, backup, cache, usage};
 
 /// Represents a tag.
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
@@ -19,17 +22,72 @@ pub struct Tag {
     pub names: Vec<String>,
     /// The path to open, if any.
     #[serde(alias = "url", alias = "link", skip_serializing_if = "Option::is_none")]
-    pub path: Option<String>,
+    pub path: Option<TagPath>,
+    /// Paths to open instead of the path if it is not available, in order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fallback_paths: Vec<String>,
+    /// The query parameter of the URL that words given after the tag are
+    /// searched for with, such as `q` for `https://duckduckgo.com/?q=words`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub query_param: Option<String>,
+    /// An icon or emoji shown before the name of the tag in listings.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
+    /// The color the name of the tag is shown in, either the name of an ANSI
+    /// color or a hex color such as `#ff8800`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
     /// Short info about the tag.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub about: Option<String>,
+    /// Longer notes about the tag, which may have many lines.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+    /// The path and the description encrypted with `age`, if the tag is
+    /// secret.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
     /// Default application to open the tag with.
     #[serde(
         alias = "default_app",
         alias = "default_application",
         skip_serializing_if = "Option::is_none"
     )]
-    pub app: Option<String>,
+    pub app: Option<App>,
+    /// Whether to open the tag without bringing the app to the foreground.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub background: Option<bool>,
+    /// The working directory of the app the tag is opened with.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+    /// Environment variables set for the app the tag is opened with.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    /// Labels grouping the tag with others, wherever they are.
+    #[serde(
+        alias = "label",
+        default,
+        deserialize_with = "deserialize_one_or_many",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub labels: Vec<String>,
+    /// The date, as `YYYY-MM-DD`, after which the tag should be checked
+    /// again, such as whether its link still works.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub review_after: Option<String>,
+    /// Whether the tag is shown before the others.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub pinned: bool,
+    /// When the tag was added, in seconds since the Unix epoch.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created: Option<u64>,
+    /// When the tag was last modified, in seconds since the Unix epoch.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub modified: Option<u64>,
+    /// Keys this version of `ot` does not know, such as those written by a
+    /// newer version, kept so that they are written back untouched.
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
     /// Subtags associated with the tag.
     #[serde(
         default,
@@ -39,18 +97,191 @@ pub struct Tag {
     pub subtags: Vec<Tag>,
 }
 
+impl Tag {
+    /// Returns when the tag was last modified, or added if it was never
+    /// modified.
+    pub fn last_changed(&self) -> Option<u64> {
+        self.modified.or(self.created)
+    }
+
+    /// Returns the path to open on the current operating system, if any.
+    pub fn path(&self) -> Option<&str> {
+        self.path.as_ref()?.current()
+    }
+
+    /// Returns `true` if the name is the primary name or an alias of the tag.
+    ///
+    /// The name is normalized as the names of loaded tags are, so that it
+    /// matches however it was typed.
+    pub fn has_name(&self, name: &str) -> bool {
+        if is_nfc(name) {
+            self.names.iter().any(|n| n == name)
+        } else {
+            let name = normalize_name(name);
+            self.names.contains(&name)
+        }
+    }
+}
+
+/// Returns the name in Unicode Normalization Form C.
+///
+/// Names are normalized when tags are loaded and saved so that a name typed
+/// on one system, such as "café" on macOS, matches the same name typed on
+/// another.
+pub fn normalize_name(name: &str) -> String {
+    name.nfc().collect()
+}
+
+/// Normalizes the names of the tags and of their subtags.
+fn normalize_names(tags: &mut [Tag]) {
+    for tag in tags {
+        for name in &mut tag.names {
+            if !is_nfc(name) {
+                *name = normalize_name(name);
+            }
+        }
+        normalize_names(&mut tag.subtags);
+    }
+}
+
 /// A collection of tags.
 pub type Tags = Vec<Tag>;
 
+/// The path of a tag.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum TagPath {
+    /// The path or the URL, the same on every operating system.
+    Any(String),
+    /// The paths or the URLs by operating system, such as `macos`, `linux`,
+    /// or `windows`, as named by [`std::env::consts::OS`].
+    PerOs(BTreeMap<String, String>),
+}
+
+impl TagPath {
+    /// Returns the path to open on the current operating system, if any.
+    pub fn current(&self) -> Option<&str> {
+        match self {
+            Self::Any(path) => Some(path),
+            Self::PerOs(paths) => paths.get(env::consts::OS).map(String::as_str),
+        }
+    }
+
+    /// Parses the path from text: a JSON object of the paths by operating
+    /// system, or else the path.
+    ///
+    /// This is the inverse of the `Display` implementation.
+    pub fn parse(text: &str) -> Self {
+        if text.trim_start().starts_with('{') {
+            if let Ok(paths) = serde_json::from_str::<BTreeMap<String, String>>(text) {
+                return Self::PerOs(paths);
+            }
+        }
+
+        Self::Any(text.to_string())
+    }
+}
+
+impl From<String> for TagPath {
+    fn from(path: String) -> Self {
+        Self::Any(path)
+    }
+}
+
+impl fmt::Display for TagPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Any(path) => f.write_str(path),
+            Self::PerOs(paths) => {
+                f.write_str(&serde_json::to_string(paths).map_err(|_| fmt::Error)?)
+            },
+        }
+    }
+}
+
+/// An app to open a tag with.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum App {
+    /// The name or the path of the app, opened with the system program.
+    Name(String),
+    /// The program to run followed by its arguments, such as
+    /// `["code", "--new-window"]`.
+    ///
+    /// Every `%s` in the arguments is replaced with the path. The path is
+    /// appended as the last argument if there is no `%s`.
+    Command(Vec<String>),
+    /// The apps by operating system, such as `macos`, `linux`, or `windows`,
+    /// as named by [`std::env::consts::OS`].
+    PerOs(BTreeMap<String, App>),
+}
+
+impl App {
+    /// Parses the app from text: a JSON array of the program and its
+    /// arguments, a JSON object of the apps by operating system, or else the
+    /// name of the app.
+    ///
+    /// This is the inverse of the `Display` implementation.
+    pub fn parse(text: &str) -> Self {
+        let trimmed = text.trim_start();
+        if trimmed.starts_with('[') {
+            if let Ok(command) = serde_json::from_str::<Vec<String>>(text) {
+                if !command.is_empty() {
+                    return Self::Command(command);
+                }
+            }
+        } else if trimmed.starts_with('{') {
+            if let Ok(apps) = serde_json::from_str::<BTreeMap<String, App>>(text) {
+                return Self::PerOs(apps);
+            }
+        }
+
+        Self::Name(text.to_string())
+    }
+
+    /// Returns the app to use on the current operating system, if any.
+    ///
+    /// Apps by operating system cannot be nested.
+    pub fn current(&self) -> Option<&App> {
+        match self {
+            Self::PerOs(apps) => apps
+                .get(env::consts::OS)
+                .filter(|a| !matches!(a, Self::PerOs(_))),
+            app => Some(app),
+        }
+    }
+}
+
+impl fmt::Display for App {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Name(name) => f.write_str(name),
+            Self::Command(command) => {
+                f.write_str(&serde_json::to_string(command).map_err(|_| fmt::Error)?)
+            },
+            Self::PerOs(apps) => f.write_str(&serde_json::to_string(apps).map_err(|_| fmt::Error)?),
+        }
+    }
+}
+
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 #[serde(transparent)]
 struct TagsSerde(#[serde(serialize_with = "skip_no_names")] Tags);
 
+#[derive(Serialize)]
+#[serde(transparent)]
+struct TagsRef<'a>(#[serde(serialize_with = "skip_no_names")] &'a [Tag]);
+
 /// Returns the path to the tags file.
 ///
-/// Errors if unable to retrieve the home directory path (and
-/// `$OPENTAG_DATA` is not set).
-pub fn get_tags_path() -> Result<PathBuf> {
+/// `data_file` takes precedence over `$OPENTAG_DATA`, which takes precedence
+/// over the default location. Errors if unable to retrieve the home
+/// directory path (and neither is set).
+pub fn get_tags_path(data_file: Option<&str>) -> Result<PathBuf> {
+    if let Some(data_file) = data_file {
+        return Ok(PathBuf::from(data_file));
+    }
+
     env::var("OPENTAG_DATA").map_or_else(
         |_| {
             dirs_next::data_dir()
@@ -62,78 +293,503 @@ pub fn get_tags_path() -> Result<PathBuf> {
 }
 
 /// Returns the serialized tags present at the given path.
+///
+/// The tags are read from the cache if the file did not change since it was
+/// cached. Otherwise, the cache is updated after the file is parsed.
 pub fn get_tags<P: AsRef<Path>>(path: P) -> Result<Tags> {
     let path = path.as_ref();
-    let contents = fs::read_to_string(path)
-        .map_err(|e| format!("tags file error at path `{}`: {}", path.display(), e))?;
+    if let Some(tags) = cache::read(path) {
+        return Ok(tags);
+    }
+
+    if path.is_dir() {
+        return Err(Error::new(
+            ErrorKind::Io,
+            format!(
+                "the tags file at path `{}` is a directory; use `--data-file` or \
+                 `$OPENTAG_DATA` to give the path of a file",
+                path.display()
+            ),
+        ));
+    }
+    let contents = fs::read_to_string(path).map_err(|e| tags_file_error(path, "read", &e))?;
+    let tags = parse_tags(&contents).map_err(|e| {
+        Error::new(
+            ErrorKind::Data,
+            format!("json error at path `{}`: {}", path.display(), e),
+        )
+    })?;
+    // names that clash or cannot be invoked are only caught here if the file
+    // was edited by hand
+    validate_tags(&tags).map_err(|e| {
+        Error::new(
+            ErrorKind::Data,
+            format!("tags file error at path `{}`: {}", path.display(), e),
+        )
+    })?;
+
+    // the cache only makes later invocations faster, so failing to write it
+    // is not an error
+    let _ = cache::write(path, &tags);
+
+    Ok(tags)
+}
+
+/// Parses the tags from the contents of a tags file.
+pub fn parse_tags(contents: &str) -> serde_json::Result<Tags> {
+    let mut tags = serde_json::from_str::<TagsSerde>(contents)?.0;
+    normalize_names(&mut tags);
+    Ok(tags)
+}
 
-    serde_json::from_str::<TagsSerde>(&contents)
-        .map(|t| t.0)
-        .map_err(|e| format!("json error at path `{}`: {}", path.display(), e).into())
+/// Serializes the tags as the contents of a tags file.
+pub fn serialize_tags(tags: &[Tag]) -> serde_json::Result<String> {
+    serde_json::to_string_pretty(&TagsRef(tags))
+}
+
+/// The size and the modification time of a tags file, used to detect changes
+/// made by other programs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FileStamp {
+    len: u64,
+    modified: Option<SystemTime>,
+}
+
+impl FileStamp {
+    /// Returns the stamp of the file at the path, or `None` if it does not
+    /// exist.
+    pub fn of<P: AsRef<Path>>(path: P) -> Option<Self> {
+        let metadata = fs::metadata(path).ok()?;
+        Some(Self {
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+        })
+    }
 }
 
 /// Writes the tags at the given path, creating the file if it does not exist.
-pub fn write_tags<P: AsRef<Path>>(tags: Tags, path: P) -> Result<()> {
-    Ok(fs::write(
-        path,
-        serde_json::to_string_pretty(&TagsSerde(tags))?,
-    )?)
+///
+/// The file is left untouched if it already has the same contents. Otherwise,
+/// the cache is updated after the file is written.
+///
+/// `loaded` is the stamp of the file when the tags were loaded. Nothing is
+/// written if the file changed since then, so that changes made in the
+/// meantime by another program are not lost. The previous contents are backed
+/// up with [`backup::create`].
+pub fn write_tags<P: AsRef<Path>>(
+    mut tags: Tags,
+    path: P,
+    loaded: Option<FileStamp>,
+) -> Result<()> {
+    let path = path.as_ref();
+    normalize_names(&mut tags);
+    let contents = serialize_tags(&tags)?;
+    if fs::read_to_string(path).is_ok_and(|c| c == contents) {
+        return Ok(());
+    }
+
+    if FileStamp::of(path) != loaded {
+        return Err(changed_error(path));
+    }
+
+    backup::create(path)?;
+    fs::write(path, contents)?;
+    let _ = cache::write(path, &tags);
+
+    Ok(())
+}
+
+/// Returns the error for a tags file that was changed by another program since
+/// it was loaded.
+pub(crate) fn changed_error(path: &Path) -> Error {
+    Error::new(
+        ErrorKind::Io,
+        format!(
+            "the tags file at path `{}` was changed by another program while `ot` was running; \
+             run the command again to apply it to the new tags",
+            path.display()
+        ),
+    )
+}
+
+/// Validates the tags and writes them at the given path.
+///
+/// Nothing is written if the tags are invalid or if the file changed since
+/// `loaded`, as with [`write_tags`].
+pub fn validate_and_write_tags<P: AsRef<Path>>(
+    tags: Tags,
+    path: P,
+    loaded: Option<FileStamp>,
+) -> Result<()> {
+    validate_tags(&tags)?;
+    write_tags(tags, path, loaded)
+}
+
+/// Checks that no two sibling tags share a name or an alias and that every
+/// name can be invoked, as with [`check_name`].
+///
+/// Tags with no names are ignored as they are not written to the file.
+pub fn validate_tags(tags: &[Tag]) -> Result<()> {
+    let mut seen: Vec<(String, &str)> = Vec::new();
+    for tag in tags.iter().filter(|t| !t.names.is_empty()) {
+        for name in &tag.names {
+            check_name(name)?;
+            let normalized = normalize_name(name);
+            match seen.iter().find(|(n, _)| *n == normalized) {
+                Some((_, other)) if *other == tag.names[0] => {
+                    return Err(format!("the tag `{}` has the name `{}` twice", other, name).into())
+                },
+                Some((_, other)) => {
+                    return Err(format!(
+                        "`{}` is a name of both the tag `{}` and the tag `{}` at the same level",
+                        name, other, tag.names[0]
+                    )
+                    .into())
+                },
+                None => seen.push((normalized, &tag.names[0])),
+            }
+        }
+        if let Some(ref date) = tag.review_after {
+            if analysis::parse_date(date).is_none() {
+                return Err(format!(
+                    "the `review_after` date of the tag `{}` is not a date such as `2024-12-31`",
+                    tag.names[0]
+                )
+                .into());
+            }
+        }
+        validate_tags(&tag.subtags)?;
+    }
+
+    Ok(())
+}
+
+/// Checks that the name or alias of a tag can be invoked: it is not empty,
+/// does not start with `-`, which would be read as an option, and has no `/`,
+/// which separates the names in tag paths such as `web/github`.
+pub fn check_name(name: &str) -> Result<()> {
+    if name.trim().is_empty() {
+        Err("tag names cannot be empty".into())
+    } else if name.starts_with('-') {
+        Err(format!("the tag name `{}` cannot start with `-`", name).into())
+    } else if name.contains('/') {
+        Err(format!("the tag name `{}` cannot contain `/`", name).into())
+    } else {
+        Ok(())
+    }
 }
 
 /// Recursively creates the tags file and all of its parent directories
 /// if they are missing.
 ///
-/// `path` must be the path to the tags FILE.
+/// `path` must be the path to the tags FILE. Errors clearly if one of its
+/// parents is a file, or if permission to create it is denied.
 pub fn create_tags_file<P: AsRef<Path>>(path: P) -> Result<()> {
     let path = path.as_ref();
 
-    if let Some(parent) = path.parent() {
-        if !parent.exists() {
-            fs::create_dir_all(parent)?;
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        // the nearest existing parent must be a directory to create the rest in
+        if let Some(file) = parent
+            .ancestors()
+            .find(|a| a.exists())
+            .filter(|a| !a.is_dir())
+        {
+            return Err(Error::new(
+                ErrorKind::Io,
+                format!(
+                    "unable to create the tags file at path `{}`: `{}` is a file, not a \
+                     directory",
+                    path.display(),
+                    file.display()
+                ),
+            ));
         }
-    };
+        fs::create_dir_all(parent).map_err(|e| tags_file_error(path, "create", &e))?;
+    }
 
-    fs::write(path, "[]")?;
+    fs::write(path, "[]").map_err(|e| tags_file_error(path, "create", &e))?;
 
     Ok(())
 }
 
-/// Creates a `clap` subcommand for the given tag.
-pub fn command_from_tag(tag: &Tag) -> Command {
-    let mut cmd = Command::new(tag.names.first().expect("expected at least one name"))
-        .disable_help_subcommand(true);
+/// Returns the error for failing to `action` the tags file, such as `read`,
+/// telling permission problems apart from other errors.
+fn tags_file_error(path: &Path, action: &str, err: &io::Error) -> Error {
+    let message = if err.kind() == io::ErrorKind::PermissionDenied {
+        format!(
+            "permission denied to {} the tags file at path `{}`; use `--data-file` or \
+             `$OPENTAG_DATA` to give a path you can write to",
+            action,
+            path.display()
+        )
+    } else {
+        format!("tags file error at path `{}`: {}", path.display(), err)
+    };
+    Error::new(ErrorKind::Io, message)
+}
 
-    if let Some(ref long_about) = tag.about {
-        cmd = cmd.about(long_about.lines().next());
-        cmd = cmd.long_about(long_about.as_str());
+/// Returns the primary name of the tag, after its icon if it has one, and
+/// marked with `*` if the tag is pinned.
+pub fn display_name(tag: &Tag) -> String {
+    let mut name = tag
+        .names
+        .first()
+        .expect("expected at least one name")
+        .clone();
+    if let Some(icon) = tag.icon.as_deref().filter(|i| !i.trim().is_empty()) {
+        name = format!("{} {}", icon.trim(), name);
     }
+    if tag.pinned {
+        name.push_str(" *");
+    }
+    name
+}
+
+/// Returns a random subtag of the tag that has a path, if any.
+pub fn random_subtag(tag: &Tag) -> Option<&Tag> {
+    let candidates = tag
+        .subtags
+        .iter()
+        .filter(|t| !t.names.is_empty() && t.path().is_some())
+        .collect::<Vec<_>>();
 
-    for alias in tag.names.iter().skip(1) {
-        cmd = cmd.visible_alias(alias.as_str());
+    if candidates.is_empty() {
+        None
+    } else {
+        Some(candidates[fastrand::usize(..candidates.len())])
     }
+}
 
-    cmd.subcommands(tag.subtags.iter().map(command_from_tag))
+/// Splits a tag path such as `web/github` into the chain of names.
+pub fn split_tag_path(path: &str) -> Vec<&str> {
+    path.split('/').filter(|n| !n.is_empty()).collect()
 }
 
-/// Find the tag matching the command invocation.
-pub fn find_tag<'a>(tags: &'a Tags, cmd: &str, matches: &ArgMatches) -> Option<&'a Tag> {
-    for tag in tags {
-        if tag.names.contains(&cmd.to_string()) {
-            if let Some((subcmd, sub_matches)) = matches.subcommand() {
-                return find_tag(&tag.subtags, subcmd, sub_matches);
-            } else {
-                return Some(tag);
+/// Returns the primary names of the nested tags, root first, by each of their
+/// aliases, and also by their primary names if `primary` is `true`.
+///
+/// Top-level tags are left out, as their names already resolve from the root.
+/// A name of several nested tags maps to all of them.
+pub fn name_index(tags: &[Tag], primary: bool) -> HashMap<String, Vec<Vec<String>>> {
+    fn index_inner(
+        tags: &[Tag],
+        primary: bool,
+        names: &mut Vec<String>,
+        index: &mut HashMap<String, Vec<Vec<String>>>,
+    ) {
+        for tag in tags.iter().filter(|t| !t.names.is_empty()) {
+            names.push(tag.names[0].clone());
+            if names.len() > 1 {
+                let skip = if primary { 0 } else { 1 };
+                for name in &tag.names[skip..] {
+                    index.entry(name.clone()).or_default().push(names.clone());
+                }
             }
+            index_inner(&tag.subtags, primary, names, index);
+            names.pop();
         }
     }
 
-    None
+    let mut index = HashMap::new();
+    index_inner(tags, primary, &mut Vec::new(), &mut index);
+    index
+}
+
+/// Finds the tag reached by following the chain of `names` from the root.
+///
+/// Each name may be the primary name or an alias of the tag at its level.
+pub fn find_tag_by_names<'a, S: AsRef<str>>(tags: &'a [Tag], names: &[S]) -> Option<&'a Tag> {
+    let (first, rest) = names.split_first()?;
+    let tag = tags.iter().find(|t| t.has_name(first.as_ref()))?;
+
+    if rest.is_empty() {
+        Some(tag)
+    } else {
+        find_tag_by_names(&tag.subtags, rest)
+    }
+}
+
+/// Returns the app of the nearest ancestor of the tag reached by following
+/// the chain of `names` that has one, which opens the tag if it has no app of
+/// its own.
+pub fn ancestor_app<'a, S: AsRef<str>>(tags: &'a [Tag], names: &[S]) -> Option<&'a App> {
+    let mut level = tags;
+    let mut app = None;
+    for name in names.iter().take(names.len().saturating_sub(1)) {
+        let tag = level.iter().find(|t| t.has_name(name.as_ref()))?;
+        app = tag.app.as_ref().or(app);
+        level = &tag.subtags;
+    }
+    app
+}
+
+/// Finds the tag reached by following the chain of `names` from the root.
+///
+/// Each name may be the primary name or an alias of the tag at its level.
+pub fn find_tag_by_names_mut<'a, S: AsRef<str>>(
+    tags: &'a mut Tags,
+    names: &[S],
+) -> Option<&'a mut Tag> {
+    let (first, rest) = names.split_first()?;
+    let tag = tags.iter_mut().find(|t| t.has_name(first.as_ref()))?;
+
+    if rest.is_empty() {
+        Some(tag)
+    } else {
+        find_tag_by_names_mut(&mut tag.subtags, rest)
+    }
+}
+
+/// Returns the tag reached by following the `indices` from the root, each
+/// being the index of a tag among its siblings.
+pub fn tag_at<'a>(tags: &'a [Tag], indices: &[usize]) -> Option<&'a Tag> {
+    let (first, rest) = indices.split_first()?;
+    let tag = tags.get(*first)?;
+
+    if rest.is_empty() {
+        Some(tag)
+    } else {
+        tag_at(&tag.subtags, rest)
+    }
+}
+
+/// Returns the tag reached by following the `indices` from the root, each
+/// being the index of a tag among its siblings.
+pub fn tag_at_mut<'a>(tags: &'a mut Tags, indices: &[usize]) -> Option<&'a mut Tag> {
+    let (first, rest) = indices.split_first()?;
+    let tag = tags.get_mut(*first)?;
+
+    if rest.is_empty() {
+        Some(tag)
+    } else {
+        tag_at_mut(&mut tag.subtags, rest)
+    }
+}
+
+/// Returns the primary names of the tag reached by following the `indices`
+/// and of its parents, root first.
+pub fn names_at(tags: &[Tag], indices: &[usize]) -> Option<Vec<String>> {
+    let mut level = tags;
+    let mut names = Vec::with_capacity(indices.len());
+    for &i in indices {
+        let tag = level.get(i)?;
+        names.push(tag.names[0].clone());
+        level = &tag.subtags;
+    }
+    Some(names)
+}
+
+/// Removes the tag reached by following the `indices` and returns it.
+pub fn remove_tag_at(tags: &mut Tags, indices: &[usize]) -> Option<Tag> {
+    let (last, parents) = indices.split_last()?;
+    let siblings = if parents.is_empty() {
+        tags
+    } else {
+        &mut tag_at_mut(tags, parents)?.subtags
+    };
+
+    (*last < siblings.len()).then(|| siblings.remove(*last))
+}
+
+/// Returns the subtags of the tag reached by following the chain of `names`,
+/// or the root tags if `names` is empty.
+pub fn find_subtags_by_names_mut<'a, S: AsRef<str>>(
+    tags: &'a mut Tags,
+    names: &[S],
+) -> Option<&'a mut Tags> {
+    if names.is_empty() {
+        Some(tags)
+    } else {
+        find_tag_by_names_mut(tags, names).map(|t| &mut t.subtags)
+    }
+}
+
+/// Returns the subtags of the tag reached by following the chain of `names`,
+/// or the root tags if `names` is empty.
+///
+/// Tags in the chain that do not exist are added.
+pub fn find_or_create_subtags_mut<'a, S: AsRef<str>>(
+    tags: &'a mut Tags,
+    names: &[S],
+) -> &'a mut Tags {
+    let (first, rest) = match names.split_first() {
+        Some(split) => split,
+        None => return tags,
+    };
+
+    let i = match tags.iter().position(|t| t.has_name(first.as_ref())) {
+        Some(i) => i,
+        None => {
+            let now = usage::now();
+            tags.push(Tag {
+                names: vec![normalize_name(first.as_ref())],
+                created: Some(now),
+                modified: Some(now),
+                ..Default::default()
+            });
+            tags.len() - 1
+        },
+    };
+
+    find_or_create_subtags_mut(&mut tags[i].subtags, rest)
+}
+
+/// Removes the tag reached by following the chain of `names` and returns it.
+pub fn remove_tag_by_names<S: AsRef<str>>(tags: &mut Tags, names: &[S]) -> Option<Tag> {
+    let (last, parents) = names.split_last()?;
+    let siblings = find_subtags_by_names_mut(tags, parents)?;
+    let i = siblings.iter().position(|t| t.has_name(last.as_ref()))?;
+
+    Some(siblings.remove(i))
+}
+
+/// Returns `name`, or `name` followed by the smallest number (starting from
+/// `2`) that makes it unique, if `name` is taken.
+pub fn unique_name<F: Fn(&str) -> bool>(name: &str, is_taken: F) -> String {
+    if !is_taken(name) {
+        return name.to_string();
+    }
+
+    (2..)
+        .map(|i| format!("{}-{}", name, i))
+        .find(|n| !is_taken(n))
+        .expect("expected an unused name")
+}
+
+/// Sorts the tags alphabetically by their primary name, ignoring case.
+///
+/// The subtags of each tag are sorted as well if `recursive` is `true`.
+pub fn sort_tags(tags: &mut Tags, recursive: bool) {
+    tags.sort_by_cached_key(|t| t.names.first().map(|n| n.to_lowercase()));
+
+    if recursive {
+        for tag in tags {
+            sort_tags(&mut tag.subtags, true);
+        }
+    }
 }
 
 /// Deserializes a string or a list of strings into a `Vec<String>`.
 ///
 /// Returns an error if an empty list is provided.
 fn deserialize_one_or_more<'de, D, T>(deserializer: D) -> std::result::Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    let v = deserialize_one_or_many(deserializer)?;
+    if v.is_empty() {
+        Err(serde::de::Error::custom(
+            "expected at least one item, found empty array",
+        ))
+    } else {
+        Ok(v)
+    }
+}
+
+/// Deserializes a string or a list of strings, which may be empty, into a
+/// `Vec<String>`.
+fn deserialize_one_or_many<'de, D, T>(deserializer: D) -> std::result::Result<Vec<T>, D::Error>
 where
     D: Deserializer<'de>,
     T: Deserialize<'de>,
@@ -147,18 +803,15 @@ where
 
     Ok(match Helper::deserialize(deserializer)? {
         Helper::One(s) => vec![s],
-        Helper::Many(v) => {
-            if v.is_empty() {
-                return Err(serde::de::Error::custom(
-                    "expected at least one item, found empty array",
-                ));
-            } else {
-                v
-            }
-        },
+        Helper::Many(v) => v,
     })
 }
 
+/// Returns `true` if the value is `false`.
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
 /// Skips serializing tags with no names.
 fn skip_no_names<S>(tags: &[Tag], serializer: S) -> std::result::Result<S::Ok, S::Error>
 where
@@ -0,0 +1,75 @@
+use std::cell::RefCell;
+use std::time::{Duration, Instant};
+
+/// Durations of the phases of an invocation.
+struct Timings {
+    /// When the invocation started.
+    start: Instant,
+    /// When the last phase ended.
+    last: Instant,
+    /// The recorded phases, in order.
+    phases: Vec<(&'static str, Duration)>,
+}
+
+thread_local! {
+    static TIMINGS: RefCell<Timings> = RefCell::new(Timings {
+        start: Instant::now(),
+        last: Instant::now(),
+        phases: Vec::new(),
+    });
+}
+
+/// Starts timing the invocation.
+///
+/// Anything done before this call is not counted.
+pub fn start() {
+    TIMINGS.with(|t| {
+        let mut t = t.borrow_mut();
+        t.start = Instant::now();
+        t.last = t.start;
+        t.phases.clear();
+    });
+}
+
+/// Records the end of a phase that began when the previous phase ended.
+///
+/// Phases with the same name are added together.
+pub fn mark(phase: &'static str) {
+    TIMINGS.with(|t| {
+        let mut t = t.borrow_mut();
+        let now = Instant::now();
+        let elapsed = now - t.last;
+        t.last = now;
+
+        if let Some((_, d)) = t.phases.iter_mut().find(|(p, _)| *p == phase) {
+            *d += elapsed;
+        } else {
+            t.phases.push((phase, elapsed));
+        }
+    });
+}
+
+/// Prints the recorded phases and the total time on the `stderr`.
+pub fn print() {
+    TIMINGS.with(|t| {
+        let t = t.borrow();
+        let width = t
+            .phases
+            .iter()
+            .map(|(p, _)| p.len())
+            .max()
+            .unwrap_or(0)
+            .max(5);
+
+        eprintln!("timings:");
+        for (phase, duration) in &t.phases {
+            eprintln!("    {:width$}    {:?}", phase, duration, width = width);
+        }
+        eprintln!(
+            "    {:width$}    {:?}",
+            "total",
+            t.start.elapsed(),
+            width = width
+        );
+    });
+}
@@ -0,0 +1,61 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, ErrorKind, Result};
+use crate::tag::Tag;
+
+/// A removed tag, along with where it was and when it was removed.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct TrashEntry {
+    /// The primary names of the parents of the tag, root first.
+    #[serde(default)]
+    pub parent: Vec<String>,
+    /// When the tag was removed, in seconds since the Unix epoch.
+    pub deleted: u64,
+    /// The removed tag and its subtags.
+    pub tag: Tag,
+}
+
+/// The removed tags, oldest first.
+pub type Trash = Vec<TrashEntry>;
+
+/// Returns the path to the trash file kept next to the tags file.
+pub fn get_trash_path(tags_path: &Path) -> PathBuf {
+    tags_path.with_extension("trash.json")
+}
+
+/// Returns the trash present at the given path.
+///
+/// An empty trash is returned if the file does not exist.
+pub fn get_trash<P: AsRef<Path>>(path: P) -> Result<Trash> {
+    let path = path.as_ref();
+    if !path.exists() {
+        return Ok(Trash::new());
+    }
+
+    let contents = fs::read_to_string(path)
+        .map_err(|e| format!("trash file error at path `{}`: {}", path.display(), e))?;
+
+    serde_json::from_str(&contents).map_err(|e| {
+        Error::new(
+            ErrorKind::Data,
+            format!("json error at path `{}`: {}", path.display(), e),
+        )
+    })
+}
+
+/// Writes the trash at the given path, removing the file if the trash is
+/// empty.
+pub fn write_trash<P: AsRef<Path>>(path: P, trash: &[TrashEntry]) -> Result<()> {
+    let path = path.as_ref();
+    if trash.is_empty() {
+        if path.exists() {
+            fs::remove_file(path)?;
+        }
+        return Ok(());
+    }
+
+    Ok(fs::write(path, serde_json::to_string_pretty(trash)?)?)
+}
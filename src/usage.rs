@@ -0,0 +1,135 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, ErrorKind, Result};
+
+/// How often and when a tag was opened.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct UsageEntry {
+    /// The number of times the tag was opened.
+    pub count: u64,
+    /// When the tag was last opened, in seconds since the Unix epoch.
+    pub last_opened: u64,
+    /// When the tag was last selected in an interactive picker, such as to
+    /// update it, in seconds since the Unix epoch.
+    #[serde(default)]
+    pub last_selected: u64,
+}
+
+impl UsageEntry {
+    /// Returns when the tag was last opened or selected, in seconds since the
+    /// Unix epoch.
+    pub fn last_used(&self) -> u64 {
+        self.last_opened.max(self.last_selected)
+    }
+}
+
+/// Usage of the tags, keyed by the tag path such as `web/github`.
+pub type Usage = BTreeMap<String, UsageEntry>;
+
+/// Whether opened and selected tags are recorded.
+static RECORDING: AtomicBool = AtomicBool::new(true);
+
+/// Stops recording opened and selected tags if `recording` is `false`, such
+/// as when the usage file cannot be written.
+pub fn set_recording(recording: bool) {
+    RECORDING.store(recording, Ordering::Relaxed);
+}
+
+/// Returns whether opened and selected tags are recorded.
+pub fn is_recording() -> bool {
+    RECORDING.load(Ordering::Relaxed)
+}
+
+/// Returns the path to the usage file kept next to the tags file.
+pub fn get_usage_path(tags_path: &Path) -> PathBuf {
+    tags_path.with_extension("usage.json")
+}
+
+/// Returns the usage present at the given path.
+///
+/// Empty usage is returned if the file does not exist.
+pub fn get_usage<P: AsRef<Path>>(path: P) -> Result<Usage> {
+    let path = path.as_ref();
+    if !path.exists() {
+        return Ok(Usage::new());
+    }
+
+    let contents = fs::read_to_string(path)
+        .map_err(|e| format!("usage file error at path `{}`: {}", path.display(), e))?;
+
+    serde_json::from_str(&contents).map_err(|e| {
+        Error::new(
+            ErrorKind::Data,
+            format!("json error at path `{}`: {}", path.display(), e),
+        )
+    })
+}
+
+/// Records that the tag with the given tag path was opened now.
+pub fn record_open<P: AsRef<Path>>(path: P, tag_path: &str) -> Result<()> {
+    if !RECORDING.load(Ordering::Relaxed) {
+        return Ok(());
+    }
+    let path = path.as_ref();
+    let mut usage = get_usage(path)?;
+
+    let entry = usage.entry(tag_path.to_string()).or_default();
+    entry.count += 1;
+    entry.last_opened = now();
+
+    Ok(fs::write(path, serde_json::to_string_pretty(&usage)?)?)
+}
+
+/// Records that the tag with the given tag path was selected in an interactive
+/// picker now.
+pub fn record_select<P: AsRef<Path>>(path: P, tag_path: &str) -> Result<()> {
+    if !RECORDING.load(Ordering::Relaxed) {
+        return Ok(());
+    }
+    let path = path.as_ref();
+    let mut usage = get_usage(path)?;
+
+    usage.entry(tag_path.to_string()).or_default().last_selected = now();
+
+    Ok(fs::write(path, serde_json::to_string_pretty(&usage)?)?)
+}
+
+/// Returns when the tag with the given tag path or any of its subtags was last
+/// opened or selected, in seconds since the Unix epoch, or 0 if never.
+pub fn last_used(usage: &Usage, tag_path: &str) -> u64 {
+    entries_under(usage, tag_path)
+        .map(UsageEntry::last_used)
+        .max()
+        .unwrap_or(0)
+}
+
+/// Returns how many times the tag with the given tag path and its subtags were
+/// opened.
+pub fn count(usage: &Usage, tag_path: &str) -> u64 {
+    entries_under(usage, tag_path).map(|e| e.count).sum()
+}
+
+/// Returns the entries of the tag with the given tag path and its subtags.
+fn entries_under<'a>(usage: &'a Usage, tag_path: &'a str) -> impl Iterator<Item = &'a UsageEntry> {
+    usage
+        .iter()
+        .filter(move |(k, _)| {
+            k.strip_prefix(tag_path)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+        })
+        .map(|(_, e)| e)
+}
+
+/// Returns the current time in seconds since the Unix epoch.
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}